@@ -1,6 +1,5 @@
-use agent_backend::{AgentContractService, ContractConfig};
+use agent_backend::{AgentContractService, ContractConfig, SignerSource};
 use anyhow::Result;
-use std::env;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -10,7 +9,9 @@ async fn main() -> Result<()> {
     // Example configuration - replace with your actual values
     let config = ContractConfig {
         rpc_url: "https://api.calibration.node.glif.io/rpc/v1".to_string(), // Filecoin calibration testnet
-        private_key: env::var("PRIVATE_KEY")?, // Your private key
+        signer: SignerSource::PrivateKey {
+            key: "$PRIVATE_KEY".to_string(), // Read from the PRIVATE_KEY environment variable
+        },
         identity_address: "0xYourContractAddress".to_string(), // Your deployed contract address
     };
 
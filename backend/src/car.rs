@@ -0,0 +1,670 @@
+//! CAR (Content Addressable aRchive) files
+//!
+//! `pack` writes a file or directory as a CARv1 or CARv2 holding its UnixFS
+//! DAG, built with the same settings as `unixfs::compute_cid` so the root is
+//! what the chosen service would return for the same content. `unpack` and
+//! `verify` read either version back, checking every block against its CID.
+//! CARv2 files are written without an index.
+
+use crate::unixfs::{self, Block, CidBuilder, CidProfile, Link, DAG_PB, RAW, UNIXFS_DIRECTORY, UNIXFS_FILE};
+use anyhow::{Context, Result};
+use cid::multihash::Multihash;
+use cid::{Cid, Version};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The CARv2 pragma, a CARv1 header that only says `version: 2`
+const CARV2_PRAGMA: [u8; 11] = [0x0a, 0xa1, 0x67, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0x02];
+
+/// Characteristics, data offset, data size and index offset
+const CARV2_HEADER_LEN: u64 = 40;
+
+/// File bytes read per `CidBuilder::update` while packing
+const READ_SIZE: usize = 1024 * 1024;
+
+/// How deep a DAG may nest before it is treated as hostile
+const MAX_DEPTH: usize = 256;
+
+/// The most a CAR may unpack to; shared blocks let a small CAR describe far more
+const MAX_UNPACK_BYTES: u64 = 64 * 1024 * 1024 * 1024;
+const MAX_UNPACK_ENTRIES: u64 = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarVersion {
+    V1,
+    V2,
+}
+
+/// What a CAR holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarSummary {
+    pub root: Cid,
+    pub blocks: usize,
+}
+
+/// Pack the file or directory at `input` into a CAR at `output`
+pub fn pack(input: &Path, output: &Path, profile: CidProfile, version: CarVersion) -> Result<CarSummary> {
+    // A CAR written into the tree it packs would pick up its own partial contents
+    let input = input.canonicalize().with_context(|| format!("Failed to read {}", input.display()))?;
+    if input.is_dir() && resolve_output(output)?.starts_with(&input) {
+        anyhow::bail!("{} is inside {}, which is being packed", output.display(), input.display());
+    }
+
+    let file = File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut out = BufWriter::new(file);
+
+    if version == CarVersion::V2 {
+        out.write_all(&CARV2_PRAGMA)?;
+        out.write_all(&[0; CARV2_HEADER_LEN as usize])?;
+    }
+
+    // The root is only known at the end, so write a placeholder of the same
+    // length now and overwrite it once the DAG is built
+    let data_offset = out.stream_position()?;
+    let placeholder = match profile.version {
+        Version::V0 => Cid::new_v0(sha256_of_nothing())?,
+        Version::V1 => Cid::new_v1(DAG_PB, sha256_of_nothing()),
+    };
+    out.write_all(&encode_header(&placeholder))?;
+
+    let mut writer = BlockWriter {
+        out: &mut out,
+        seen: HashSet::new(),
+    };
+    let root = pack_path(&mut writer, &input, &profile)?.cid;
+    let blocks = writer.seen.len();
+
+    let data_end = out.stream_position()?;
+    out.seek(SeekFrom::Start(data_offset))?;
+    out.write_all(&encode_header(&root))?;
+
+    if version == CarVersion::V2 {
+        out.seek(SeekFrom::Start(CARV2_PRAGMA.len() as u64))?;
+        out.write_all(&[0; 16])?;
+        out.write_all(&data_offset.to_le_bytes())?;
+        out.write_all(&(data_end - data_offset).to_le_bytes())?;
+        out.write_all(&0u64.to_le_bytes())?;
+    }
+    out.flush()?;
+
+    Ok(CarSummary { root, blocks })
+}
+
+/// Where `output` will be created, with its directory's symlinks resolved
+fn resolve_output(output: &Path) -> Result<PathBuf> {
+    let name = output.file_name().with_context(|| format!("{} is not a file name", output.display()))?;
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = dir.canonicalize().with_context(|| format!("Failed to read {}", dir.display()))?;
+    Ok(dir.join(name))
+}
+
+/// The root CID recorded in a CAR's header
+pub fn root(car: &Path) -> Result<Cid> {
+    let mut reader = CarReader::open(car)?;
+    reader.root()
+}
+
+/// Check that every block in a CAR matches its CID and that the whole DAG
+/// under the root is present
+pub fn verify(car: &Path) -> Result<CarSummary> {
+    let mut reader = CarReader::open(car)?;
+    reader.index()?;
+
+    let root = reader.root()?;
+    measure(&mut reader, &root, 0, &mut HashMap::new())?;
+
+    Ok(CarSummary { root, blocks: reader.blocks.len() })
+}
+
+/// Write the file or directory held in a CAR to `output`
+pub fn unpack(car: &Path, output: &Path) -> Result<CarSummary> {
+    let mut reader = CarReader::open(car)?;
+    reader.index()?;
+
+    let root = reader.root()?;
+    if output.exists() {
+        anyhow::bail!("{} already exists", output.display());
+    }
+    let size = measure(&mut reader, &root, 0, &mut HashMap::new())?;
+    if size.bytes > MAX_UNPACK_BYTES {
+        anyhow::bail!("CAR unpacks to {} bytes, more than the {} allowed", size.bytes, MAX_UNPACK_BYTES);
+    }
+    if size.entries > MAX_UNPACK_ENTRIES {
+        anyhow::bail!("CAR unpacks to {} entries, more than the {} allowed", size.entries, MAX_UNPACK_ENTRIES);
+    }
+    extract(&mut reader, &root, output)?;
+
+    Ok(CarSummary { root, blocks: reader.blocks.len() })
+}
+
+struct BlockWriter<'a, W: Write> {
+    out: &'a mut W,
+    seen: HashSet<Cid>,
+}
+
+impl<W: Write> BlockWriter<'_, W> {
+    fn write(&mut self, block: Block) -> Result<()> {
+        // Repeated chunks produce the same block, which only needs storing once
+        if !self.seen.insert(block.cid) {
+            return Ok(());
+        }
+
+        let cid = block.cid.to_bytes();
+        let mut len = Vec::new();
+        unixfs::put_varint(&mut len, (cid.len() + block.data.len()) as u64);
+        self.out.write_all(&len)?;
+        self.out.write_all(&cid)?;
+        self.out.write_all(&block.data)?;
+        Ok(())
+    }
+}
+
+fn pack_path<W: Write>(writer: &mut BlockWriter<'_, W>, path: &Path, profile: &CidProfile) -> Result<Link> {
+    // Symlinks are not followed: a loop would never end and a link could
+    // pull in files from outside the tree
+    let metadata = std::fs::symlink_metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if metadata.is_symlink() {
+        anyhow::bail!("{} is a symlink, which cannot be packed", path.display())
+    } else if metadata.is_dir() {
+        pack_directory(writer, path, profile)
+    } else if metadata.is_file() {
+        pack_file(writer, path, profile)
+    } else {
+        anyhow::bail!("{} is neither a file nor a directory", path.display())
+    }
+}
+
+fn pack_file<W: Write>(writer: &mut BlockWriter<'_, W>, path: &Path, profile: &CidProfile) -> Result<Link> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut builder = CidBuilder::new(*profile).keep_blocks();
+
+    let mut buf = vec![0; READ_SIZE];
+    loop {
+        let read = file.read(&mut buf).with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        builder.update(&buf[..read]);
+        for block in builder.take_blocks() {
+            writer.write(block)?;
+        }
+    }
+
+    let (link, blocks) = builder.finish_link();
+    for block in blocks {
+        writer.write(block)?;
+    }
+    Ok(link)
+}
+
+fn pack_directory<W: Write>(writer: &mut BlockWriter<'_, W>, path: &Path, profile: &CidProfile) -> Result<Link> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(path).with_context(|| format!("Failed to list {}", path.display()))? {
+        let entry = entry?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| anyhow::anyhow!("{:?} is not valid UTF-8", name))?;
+        entries.push((name, pack_path(writer, &entry.path(), profile)?));
+    }
+
+    let (link, block) = unixfs::directory(profile, entries);
+    writer.write(block)?;
+    Ok(link)
+}
+
+fn sha256_of_nothing() -> Multihash<64> {
+    *unixfs::compute_cid(b"", CidProfile::KUBO_V1).hash()
+}
+
+/// A varint-prefixed DAG-CBOR `{ roots: [root], version: 1 }`
+fn encode_header(root: &Cid) -> Vec<u8> {
+    let cid = root.to_bytes();
+
+    let mut cbor = vec![0xa2];
+    cbor.push(0x65);
+    cbor.extend_from_slice(b"roots");
+    cbor.push(0x81);
+    // Tag 42 (CID) over a byte string of the CID behind a zero byte
+    cbor.extend_from_slice(&[0xd8, 0x2a, 0x58, (cid.len() + 1) as u8, 0x00]);
+    cbor.extend_from_slice(&cid);
+    cbor.push(0x67);
+    cbor.extend_from_slice(b"version");
+    cbor.push(0x01);
+
+    let mut header = Vec::new();
+    unixfs::put_varint(&mut header, cbor.len() as u64);
+    header.extend_from_slice(&cbor);
+    header
+}
+
+/// The parts of a CAR header this module understands
+struct Header {
+    version: u64,
+    roots: Vec<Cid>,
+}
+
+/// Decode the DAG-CBOR header, which is a map of `roots` and `version`
+fn decode_header(mut buf: &[u8]) -> Result<Header> {
+    let mut header = Header { version: 0, roots: Vec::new() };
+
+    let entries = cbor_head(&mut buf, 5)?;
+    for _ in 0..entries {
+        let len = cbor_head(&mut buf, 3)? as usize;
+        match take(&mut buf, len)? {
+            b"version" => header.version = cbor_head(&mut buf, 0)?,
+            b"roots" => {
+                for _ in 0..cbor_head(&mut buf, 4)? {
+                    anyhow::ensure!(cbor_head(&mut buf, 6)? == 42, "CAR root is not a CID");
+                    let len = cbor_head(&mut buf, 2)? as usize;
+                    let bytes = take(&mut buf, len)?;
+                    anyhow::ensure!(bytes.first() == Some(&0), "CAR root is not a CID");
+                    header.roots.push(Cid::try_from(&bytes[1..])?);
+                }
+            }
+            key => anyhow::bail!("Unexpected CAR header field {:?}", String::from_utf8_lossy(key)),
+        }
+    }
+
+    Ok(header)
+}
+
+/// Read a CBOR item head of the `expected` major type and return its argument
+fn cbor_head(buf: &mut &[u8], expected: u8) -> Result<u64> {
+    let first = take(buf, 1)?[0];
+    anyhow::ensure!(first >> 5 == expected, "Malformed CAR header");
+
+    let value = match first & 0x1f {
+        info @ 0..=23 => u64::from(info),
+        24 => u64::from(take(buf, 1)?[0]),
+        25 => u64::from(u16::from_be_bytes(take(buf, 2)?.try_into()?)),
+        26 => u64::from(u32::from_be_bytes(take(buf, 4)?.try_into()?)),
+        27 => u64::from_be_bytes(take(buf, 8)?.try_into()?),
+        _ => anyhow::bail!("Malformed CAR header"),
+    };
+    Ok(value)
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    anyhow::ensure!(len <= buf.len(), "Truncated CAR header");
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+/// Reads blocks from a CARv1 or CARv2
+struct CarReader {
+    file: BufReader<File>,
+    roots: Vec<Cid>,
+    /// Where the blocks start and end in the file
+    data: (u64, u64),
+    /// Offset and length of each block's data
+    blocks: HashMap<Cid, (u64, usize)>,
+}
+
+impl CarReader {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+
+        let mut header = read_header(&mut file)?;
+        let mut data = (file.stream_position()?, len);
+
+        if header.version == 2 {
+            let mut v2 = [0; CARV2_HEADER_LEN as usize];
+            file.read_exact(&mut v2).context("Truncated CARv2 header")?;
+            let offset = u64::from_le_bytes(v2[16..24].try_into()?);
+            let size = u64::from_le_bytes(v2[24..32].try_into()?);
+
+            let end = offset.checked_add(size).filter(|&end| end <= len);
+            let end = end.context("CARv2 data section runs past the end of the file")?;
+            file.seek(SeekFrom::Start(offset))?;
+            header = read_header(&mut file)?;
+            data = (file.stream_position()?, end);
+        }
+        anyhow::ensure!(header.version == 1, "Unsupported CAR version {}", header.version);
+
+        Ok(Self {
+            file,
+            roots: header.roots,
+            data,
+            blocks: HashMap::new(),
+        })
+    }
+
+    fn root(&mut self) -> Result<Cid> {
+        match self.roots.as_slice() {
+            [root] => Ok(*root),
+            roots => anyhow::bail!("Expected one root in the CAR, found {}", roots.len()),
+        }
+    }
+
+    /// Read through every block, checking it against its CID
+    fn index(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.data.0))?;
+
+        let mut position = self.data.0;
+        while position < self.data.1 {
+            let len = read_stream_varint(&mut self.file)?;
+            // The length is untrusted; check it before allocating for it
+            let remaining = self.data.1.saturating_sub(self.file.stream_position()?);
+            anyhow::ensure!(len <= remaining, "CAR block length {} runs past the end of the data", len);
+            let len = len as usize;
+            let mut section = vec![0; len];
+            self.file.read_exact(&mut section).context("Truncated CAR block")?;
+
+            let mut cursor = Cursor::new(&section);
+            let cid = Cid::read_bytes(&mut cursor).context("Malformed CID in CAR")?;
+            let start = cursor.position() as usize;
+            unixfs::check_block(&cid, &section[start..])?;
+
+            let header_len = self.file.stream_position()? - position - len as u64;
+            self.blocks.insert(cid, (position + header_len + start as u64, len - start));
+            position += header_len + len as u64;
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, cid: &Cid) -> Result<Vec<u8>> {
+        let (offset, len) = *self.blocks.get(cid).with_context(|| format!("CAR is missing block {}", cid))?;
+        self.file.seek(SeekFrom::Start(offset))?;
+
+        let mut data = vec![0; len];
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn read_header(file: &mut impl Read) -> Result<Header> {
+    let len = read_stream_varint(file)? as usize;
+    anyhow::ensure!(len <= 1024 * 1024, "CAR header is too large");
+
+    let mut header = vec![0; len];
+    file.read_exact(&mut header).context("Truncated CAR header")?;
+    decode_header(&header)
+}
+
+fn read_stream_varint(file: &mut impl Read) -> Result<u64> {
+    let mut bytes = Vec::new();
+    loop {
+        // A u64 takes at most ten bytes
+        anyhow::ensure!(bytes.len() < 10, "Malformed varint in CAR");
+        let mut byte = [0];
+        file.read_exact(&mut byte).context("Truncated CAR")?;
+        bytes.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            return unixfs::read_varint(&mut bytes.as_slice());
+        }
+    }
+}
+
+/// What a DAG unpacks to
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    bytes: u64,
+    /// Files and directories
+    entries: u64,
+}
+
+/// Check the DAG under `cid` is complete and well formed, and return how
+/// much it unpacks to. `sizes` remembers each subtree so one that is
+/// linked many times is only walked once.
+fn measure(reader: &mut CarReader, cid: &Cid, depth: usize, sizes: &mut HashMap<Cid, Extent>) -> Result<Extent> {
+    if let Some(&size) = sizes.get(cid) {
+        return Ok(size);
+    }
+    anyhow::ensure!(depth < MAX_DEPTH, "CAR DAG is nested more than {} levels deep", MAX_DEPTH);
+
+    let block = reader.get(cid)?;
+    if cid.codec() == RAW {
+        let size = Extent { bytes: block.len() as u64, entries: 1 };
+        sizes.insert(*cid, size);
+        return Ok(size);
+    }
+
+    let node = unixfs::decode_node(&block)?;
+    match node.kind {
+        UNIXFS_DIRECTORY => {
+            for (name, _) in &node.links {
+                if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+                    anyhow::bail!("Refusing to unpack directory entry {:?}", name);
+                }
+            }
+        }
+        UNIXFS_FILE => {}
+        kind => anyhow::bail!("{} has unsupported UnixFS type {}", cid, kind),
+    }
+
+    let mut size = Extent { bytes: node.data.len() as u64, entries: 1 };
+    for (_, child) in &node.links {
+        let child = measure(reader, child, depth + 1, sizes)?;
+        size.bytes = size.bytes.saturating_add(child.bytes);
+        // A file's chunks are all one entry
+        if node.kind == UNIXFS_DIRECTORY {
+            size.entries = size.entries.saturating_add(child.entries);
+        }
+    }
+    sizes.insert(*cid, size);
+    Ok(size)
+}
+
+/// Write the DAG under `cid` to `output`. The DAG must have been through
+/// `measure` first, which bounds its depth and checks its names.
+fn extract(reader: &mut CarReader, cid: &Cid, output: &Path) -> Result<()> {
+    if cid.codec() != RAW {
+        let node = unixfs::decode_node(&reader.get(cid)?)?;
+        if node.kind == UNIXFS_DIRECTORY {
+            std::fs::create_dir(output).with_context(|| format!("Failed to create {}", output.display()))?;
+            for (name, child) in &node.links {
+                extract(reader, child, &output.join(name))?;
+            }
+            return Ok(());
+        }
+    }
+
+    let file = File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut out = BufWriter::new(file);
+    write_file(reader, cid, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a file's bytes in order, leaf by leaf
+fn write_file(reader: &mut CarReader, cid: &Cid, out: &mut BufWriter<File>) -> Result<()> {
+    let block = reader.get(cid)?;
+    if cid.codec() == RAW {
+        out.write_all(&block)?;
+        return Ok(());
+    }
+
+    let node = unixfs::decode_node(&block)?;
+    out.write_all(&node.data)?;
+    for (_, child) in &node.links {
+        write_file(reader, child, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unixfs::compute_cid;
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("agent-cli-car-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_pack_file_round_trip() {
+        let dir = scratch("file");
+        let data: Vec<u8> = (0..700 * 1024).map(|i| (i % 241) as u8).collect();
+        std::fs::write(dir.join("media.bin"), &data).unwrap();
+
+        for (profile, version) in [(CidProfile::KUBO_V0, CarVersion::V1), (CidProfile::WEB3_STORAGE, CarVersion::V2)] {
+            let car = dir.join("media.car");
+            let packed = pack(&dir.join("media.bin"), &car, profile, version).unwrap();
+            assert_eq!(packed.root, compute_cid(&data, profile));
+            assert_eq!(root(&car).unwrap(), packed.root);
+            assert_eq!(verify(&car).unwrap(), packed);
+
+            let out = dir.join("unpacked.bin");
+            assert_eq!(unpack(&car, &out).unwrap(), packed);
+            assert_eq!(std::fs::read(&out).unwrap(), data);
+            std::fs::remove_file(&out).unwrap();
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pack_directory_round_trip() {
+        let dir = scratch("dir");
+        let input = dir.join("collection");
+        std::fs::create_dir_all(input.join("images")).unwrap();
+        std::fs::write(input.join("metadata.json"), b"{\"name\":\"agent\"}").unwrap();
+        std::fs::write(input.join("images/1.png"), b"png").unwrap();
+        std::fs::write(input.join("images/2.png"), b"png").unwrap();
+        std::fs::write(input.join("empty"), b"").unwrap();
+
+        let car = dir.join("collection.car");
+        let packed = pack(&input, &car, CidProfile::KUBO_V1, CarVersion::V1).unwrap();
+        // Identical files share a block
+        assert_eq!(packed.blocks, 5);
+
+        let out = dir.join("out");
+        unpack(&car, &out).unwrap();
+        assert_eq!(std::fs::read(out.join("metadata.json")).unwrap(), b"{\"name\":\"agent\"}");
+        assert_eq!(std::fs::read(out.join("images/2.png")).unwrap(), b"png");
+        assert_eq!(std::fs::read(out.join("empty")).unwrap(), b"");
+
+        // Packing the unpacked tree gives the same root
+        let again = pack(&out, &dir.join("again.car"), CidProfile::KUBO_V1, CarVersion::V1).unwrap();
+        assert_eq!(again.root, packed.root);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_inside_input_is_refused() {
+        let dir = scratch("inside");
+        std::fs::write(dir.join("data.txt"), b"data").unwrap();
+
+        for input in [dir.clone(), dir.join(".")] {
+            let err = pack(&input, &dir.join("self.car"), CidProfile::KUBO_V1, CarVersion::V1).unwrap_err();
+            assert!(err.to_string().contains("which is being packed"), "{}", err);
+        }
+        assert!(!dir.join("self.car").exists());
+
+        // Next to the directory is fine
+        let beside = dir.with_extension("car");
+        pack(&dir.join("."), &beside, CidProfile::KUBO_V1, CarVersion::V1).unwrap();
+        std::fs::remove_file(&beside).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A CARv1 of `blocks` under `root`
+    fn write_car(path: &Path, root: &Cid, blocks: Vec<Block>) {
+        let mut out = File::create(path).unwrap();
+        out.write_all(&encode_header(root)).unwrap();
+        let mut writer = BlockWriter {
+            out: &mut out,
+            seen: HashSet::new(),
+        };
+        for block in blocks {
+            writer.write(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_hostile_dags_are_bounded() {
+        let dir = scratch("hostile");
+        let profile = CidProfile::KUBO_V1;
+        let (file, mut blocks) = {
+            let mut builder = CidBuilder::new(profile).keep_blocks();
+            builder.update(b"data");
+            builder.finish_link()
+        };
+
+        // Each level links the one below twice, so the tree doubles per level
+        let mut level = file;
+        for _ in 0..40 {
+            let (link, block) = unixfs::directory(&profile, vec![("a".into(), level.clone()), ("b".into(), level)]);
+            blocks.push(block);
+            level = link;
+        }
+        let car = dir.join("wide.car");
+        write_car(&car, &level.cid, blocks.clone());
+
+        // Shared subtrees are only checked once
+        verify(&car).unwrap();
+        let err = unpack(&car, &dir.join("wide")).unwrap_err();
+        assert!(err.to_string().contains("more than"), "{}", err);
+        assert!(!dir.join("wide").exists());
+
+        // A chain deeper than any real tree
+        for _ in 0..MAX_DEPTH {
+            let (link, block) = unixfs::directory(&profile, vec![("a".into(), level)]);
+            blocks.push(block);
+            level = link;
+        }
+        write_car(&car, &level.cid, blocks);
+        let err = verify(&car).unwrap_err();
+        assert!(err.to_string().contains("levels deep"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_refused() {
+        let dir = scratch("symlink");
+        let input = dir.join("tree");
+        std::fs::create_dir_all(input.join("sub")).unwrap();
+        std::fs::write(input.join("data.txt"), b"data").unwrap();
+        // A loop back to the top would recurse forever if it were followed
+        std::os::unix::fs::symlink(&input, input.join("sub/loop")).unwrap();
+
+        let car = dir.join("tree.car");
+        let err = pack(&input, &car, CidProfile::KUBO_V1, CarVersion::V1).unwrap_err();
+        assert!(err.to_string().contains("is a symlink"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_corruption() {
+        let dir = scratch("corrupt");
+        std::fs::write(dir.join("data.txt"), b"hello world\n").unwrap();
+        let car = dir.join("data.car");
+        pack(&dir.join("data.txt"), &car, CidProfile::KUBO_V0, CarVersion::V1).unwrap();
+
+        let mut bytes = std::fs::read(&car).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&car, &bytes).unwrap();
+
+        let err = verify(&car).unwrap_err();
+        assert!(err.to_string().contains("does not match its contents"), "{}", err);
+
+        // A block length far beyond the file is rejected before allocating
+        pack(&dir.join("data.txt"), &car, CidProfile::KUBO_V0, CarVersion::V1).unwrap();
+        let mut bytes = std::fs::read(&car).unwrap();
+        bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        std::fs::write(&car, &bytes).unwrap();
+
+        let err = verify(&car).unwrap_err();
+        assert!(err.to_string().contains("runs past the end"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
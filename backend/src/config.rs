@@ -1,5 +1,13 @@
+use crate::signer::{redact, SignerSource};
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Prefix for environment overrides, e.g. `AGENT_CLI_STORAGE__LIGHTHOUSE_API_KEY`
+pub const ENV_PREFIX: &str = "AGENT_CLI";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -35,19 +43,247 @@ pub struct StorageConfig {
     pub lighthouse_api_key: String,
     #[serde(default)]
     pub web3_storage_token: String,
+    /// Where uploads go; Lighthouse when an API key is set, otherwise IPFS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<StorageBackendKind>,
+    /// `[mcp.servers]` entry to store through when `backend = "mcp"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_server: Option<String>,
+    /// Write every upload to several backends; replaces `backend` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replication: Option<ReplicationConfig>,
+}
+
+impl StorageConfig {
+    /// The configured backend, or the one implied by the credentials present
+    pub fn backend_kind(&self) -> StorageBackendKind {
+        match self.backend {
+            Some(kind) => kind,
+            None if !self.lighthouse_api_key.is_empty() => StorageBackendKind::Lighthouse,
+            None => StorageBackendKind::Ipfs,
+        }
+    }
+}
+
+/// Which backends hold a copy of each upload, and how many must succeed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    pub backends: Vec<StorageBackendKind>,
+    /// Backends that must store each upload; all of them when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<usize>,
+    #[serde(default)]
+    pub below_quorum: QuorumAction,
+}
+
+impl ReplicationConfig {
+    /// The effective quorum, checked against the number of backends
+    pub fn quorum(&self) -> anyhow::Result<usize> {
+        if self.backends.is_empty() {
+            anyhow::bail!("storage.replication.backends is empty");
+        }
+        for (i, backend) in self.backends.iter().enumerate() {
+            // The MCP server answers with piece CIDs, which cannot be checked
+            // against the uploaded bytes like the others' CIDs can
+            if *backend == StorageBackendKind::Mcp {
+                anyhow::bail!("storage.replication.backends cannot include \"mcp\"");
+            }
+            if self.backends[..i].contains(backend) {
+                anyhow::bail!("storage.replication.backends lists {:?} twice", backend);
+            }
+        }
+
+        let quorum = self.quorum.unwrap_or(self.backends.len());
+        if quorum == 0 || quorum > self.backends.len() {
+            anyhow::bail!(
+                "storage.replication.quorum must be between 1 and {}, got {}",
+                self.backends.len(),
+                quorum
+            );
+        }
+        Ok(quorum)
+    }
+}
+
+/// What to do when fewer backends than the quorum stored an upload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuorumAction {
+    /// Fail the upload
+    #[default]
+    Fail,
+    /// Log a warning and carry on with the copies that were made
+    Warn,
+}
+
+/// Storage services an upload can go to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackendKind {
+    /// The node at `ipfs_api`
+    Ipfs,
+    /// Lighthouse, which pins to IPFS and makes Filecoin deals
+    Lighthouse,
+    Web3Storage,
+    /// The Filecoin MCP server named by `mcp_server`
+    Mcp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletConfig {
-    #[serde(skip_serializing)]
-    pub private_key: String,
+    /// Where the signing key comes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<SignerSource>,
+    /// Legacy plaintext key, still accepted when `signer` is not set
+    #[serde(default, skip_serializing)]
+    pub private_key: Option<String>,
     pub address: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl WalletConfig {
+    /// The configured signer source, falling back to the legacy `private_key`
+    pub fn signer_source(&self) -> anyhow::Result<SignerSource> {
+        match (&self.signer, &self.private_key) {
+            (Some(signer), _) => Ok(signer.clone()),
+            (None, Some(key)) => Ok(SignerSource::PrivateKey { key: key.clone() }),
+            (None, None) => anyhow::bail!(
+                "No wallet signer configured. Set [wallet.signer] in the configuration file."
+            ),
+        }
+    }
+
+    /// Load the wallet signer
+    pub fn load_signer(&self) -> anyhow::Result<PrivateKeySigner> {
+        self.signer_source()?.load()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPConfig {
     #[serde(default = "default_timeout")]
     pub default_timeout: u64,
+    /// Local MCP servers launched over stdio, keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub servers: BTreeMap<String, MCPServerConfig>,
+}
+
+// Used when the [mcp] table is missing entirely, so it must match the field default
+impl Default for MCPConfig {
+    fn default() -> Self {
+        Self {
+            default_timeout: default_timeout(),
+            servers: BTreeMap::new(),
+        }
+    }
+}
+
+impl MCPConfig {
+    /// Look up a named server from `[mcp.servers.<name>]`
+    pub fn server(&self, name: &str) -> anyhow::Result<&MCPServerConfig> {
+        self.servers.get(name).with_context(|| {
+            let known: Vec<_> = self.servers.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                format!("Unknown MCP server '{}'; no [mcp.servers] are configured", name)
+            } else {
+                format!("Unknown MCP server '{}'. Configured: {}", name, known.join(", "))
+            }
+        })
+    }
+}
+
+/// How to launch a stdio MCP server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MCPServerConfig {
+    /// Program to run, e.g. `node`, `npx`, `python3` or a binary path
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables; values may be `$VAR` references
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Working directory for the server process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub stderr: StderrMode,
+    /// Restart the server when it exits unexpectedly; off when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Whether the server sees this machine's files, so uploads can hand it
+    /// a path; turn off for servers run through docker, ssh and the like
+    #[serde(default = "default_shared_fs")]
+    pub shared_fs: bool,
+}
+
+impl MCPServerConfig {
+    /// A Node.js server started as `node <script>`
+    pub fn node(script: &str) -> Self {
+        Self {
+            command: "node".to_string(),
+            args: vec![script.to_string()],
+            env: BTreeMap::new(),
+            cwd: None,
+            stderr: StderrMode::default(),
+            restart: None,
+            shared_fs: default_shared_fs(),
+        }
+    }
+}
+
+/// What to do with a server's stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StderrMode {
+    /// Forward each line to the log, tagged with the server name
+    #[default]
+    Log,
+    /// Share the CLI's stderr
+    Inherit,
+    /// Discard it
+    Null,
+}
+
+/// Exponential backoff between restarts of a crashed server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    /// Consecutive restarts before giving up
+    #[serde(default = "default_max_restarts")]
+    pub max_attempts: u32,
+    /// Delay before the first restart; doubled for each further attempt
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_restarts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `attempt`, counting from zero
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.initial_backoff_ms.saturating_mul(1 << attempt.min(32));
+        Duration::from_millis(delay.min(self.max_backoff_ms))
+    }
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
 }
 
 fn default_ipfs_api() -> String {
@@ -62,6 +298,10 @@ fn default_timeout() -> u64 {
     30
 }
 
+fn default_shared_fs() -> bool {
+    true
+}
+
 impl Config {
     pub fn load(path: &PathBuf) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
@@ -69,6 +309,76 @@ impl Config {
         Ok(config)
     }
 
+    /// Load `path` with the named profile and `AGENT_CLI_*` environment overrides applied.
+    ///
+    /// Layers are applied in order: the base file, then `[profiles.<name>]` from the
+    /// same file, then environment variables, where `__` separates nested keys.
+    pub fn load_layered(path: &Path, profile: Option<&str>) -> anyhow::Result<Self> {
+        let content = if path.exists() {
+            Some(
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+            )
+        } else {
+            None
+        };
+
+        Self::from_layers(content.as_deref(), profile, None)
+    }
+
+    fn from_layers(
+        content: Option<&str>,
+        profile: Option<&str>,
+        env: Option<::config::Map<String, String>>,
+    ) -> anyhow::Result<Self> {
+        let mut base: toml::Table = match content {
+            Some(content) => toml::from_str(content).context("Invalid configuration file")?,
+            None => toml::Table::new(),
+        };
+
+        let profiles = base.remove("profiles");
+        if let Some(name) = profile {
+            let overlay = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(name))
+                .and_then(|profile| profile.as_table())
+                .with_context(|| format!("Profile '{}' not found under [profiles]", name))?;
+            merge_tables(&mut base, overlay);
+        }
+
+        let layered = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                &toml::to_string(&base)?,
+                ::config::FileFormat::Toml,
+            ))
+            .add_source(
+                ::config::Environment::with_prefix(ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .source(env),
+            )
+            .build()?;
+
+        layered
+            .try_deserialize()
+            .context("Invalid configuration")
+    }
+
+    /// A copy that is safe to print, with API keys and wallet secrets redacted
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        config.storage.lighthouse_api_key = redact(&config.storage.lighthouse_api_key).to_string();
+        config.storage.web3_storage_token = redact(&config.storage.web3_storage_token).to_string();
+        config.wallet.signer = config.wallet.signer.as_ref().map(SignerSource::redacted);
+        config.wallet.private_key = None;
+        for server in config.mcp.servers.values_mut() {
+            for value in server.env.values_mut() {
+                *value = redact(value).to_string();
+            }
+        }
+        config
+    }
+
     pub fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
         let content = toml::to_string_pretty(self)?;
         std::fs::write(path, content)?;
@@ -83,6 +393,20 @@ impl Config {
     }
 }
 
+/// Recursively merge `overlay` into `base`; non-table values in `overlay` win
+fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
 pub fn ensure_config_dir() -> anyhow::Result<PathBuf> {
     let config_dir = dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -94,3 +418,252 @@ pub fn ensure_config_dir() -> anyhow::Result<PathBuf> {
 
     Ok(config_dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallet_signer_source() {
+        let wallet: WalletConfig = toml::from_str(
+            r#"
+            address = "0x0000000000000000000000000000000000000000"
+
+            [signer]
+            type = "keystore"
+            path = "~/.agent-cli/keystore.json"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(wallet.signer_source().unwrap(), SignerSource::Keystore { .. }));
+
+        // Older configs stored a plaintext key directly under [wallet]
+        let legacy: WalletConfig = toml::from_str(
+            r#"
+            private_key = "0x01"
+            address = "0x0000000000000000000000000000000000000000"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            legacy.signer_source().unwrap(),
+            SignerSource::PrivateKey { key: "0x01".to_string() }
+        );
+
+        let empty: WalletConfig = toml::from_str(r#"address = "0x00""#).unwrap();
+        assert!(empty.signer_source().is_err());
+    }
+
+    const LAYERED: &str = r#"
+        [network]
+        name = "calibration"
+        rpc_url = "https://api.calibration.node.glif.io/rpc/v1"
+        chain_id = 314159
+
+        [contracts]
+        identity = "0x01"
+        reputation = "0x02"
+        validation = "0x03"
+
+        [storage]
+        lighthouse_api_key = "base-key"
+
+        [wallet]
+        address = "0x00"
+
+        [wallet.signer]
+        type = "private_key"
+        key = "0xabcdef"
+
+        [profiles.mainnet.network]
+        name = "mainnet"
+        rpc_url = "https://api.node.glif.io/rpc/v1"
+        chain_id = 314
+
+        [profiles.mainnet.contracts]
+        identity = "0x11"
+    "#;
+
+    fn env(vars: &[(&str, &str)]) -> Option<::config::Map<String, String>> {
+        Some(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn test_profile_overrides_base() {
+        let base = Config::from_layers(Some(LAYERED), None, env(&[])).unwrap();
+        assert_eq!(base.network.chain_id, 314159);
+        assert_eq!(base.contracts.identity, "0x01");
+
+        let mainnet = Config::from_layers(Some(LAYERED), Some("mainnet"), env(&[])).unwrap();
+        assert_eq!(mainnet.network.name, "mainnet");
+        assert_eq!(mainnet.network.chain_id, 314);
+        assert_eq!(mainnet.contracts.identity, "0x11");
+        // Keys the profile does not mention come from the base file
+        assert_eq!(mainnet.contracts.reputation, "0x02");
+
+        assert!(Config::from_layers(Some(LAYERED), Some("devnet"), env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_profile() {
+        let config = Config::from_layers(
+            Some(LAYERED),
+            Some("mainnet"),
+            env(&[
+                ("AGENT_CLI_STORAGE__LIGHTHOUSE_API_KEY", "env-key"),
+                ("AGENT_CLI_NETWORK__CHAIN_ID", "31337"),
+                ("AGENT_CLI_WALLET__SIGNER__KEY", "$CI_PRIVATE_KEY"),
+                ("UNRELATED", "ignored"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.storage.lighthouse_api_key, "env-key");
+        assert_eq!(config.network.chain_id, 31337);
+        assert_eq!(config.network.name, "mainnet");
+        assert_eq!(
+            config.wallet.signer,
+            Some(SignerSource::PrivateKey { key: "$CI_PRIVATE_KEY".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_env_only_config() {
+        let config = Config::from_layers(
+            None,
+            None,
+            env(&[
+                ("AGENT_CLI_NETWORK__NAME", "calibration"),
+                ("AGENT_CLI_NETWORK__RPC_URL", "http://localhost:8545"),
+                ("AGENT_CLI_NETWORK__CHAIN_ID", "314159"),
+                ("AGENT_CLI_CONTRACTS__IDENTITY", "0x01"),
+                ("AGENT_CLI_CONTRACTS__REPUTATION", "0x02"),
+                ("AGENT_CLI_CONTRACTS__VALIDATION", "0x03"),
+                ("AGENT_CLI_STORAGE__LIGHTHOUSE_API_KEY", "key"),
+                ("AGENT_CLI_WALLET__ADDRESS", "0x00"),
+                ("AGENT_CLI_WALLET__PRIVATE_KEY", "$CI_PRIVATE_KEY"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.storage.ipfs_gateway, "https://ipfs.io/ipfs/");
+        assert_eq!(config.mcp.default_timeout, 30);
+        assert!(Config::from_layers(None, None, env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_storage_backend() {
+        let config = Config::from_layers(Some(LAYERED), None, env(&[])).unwrap();
+        assert_eq!(config.storage.backend, None);
+        assert_eq!(config.storage.backend_kind(), StorageBackendKind::Lighthouse);
+
+        let config = Config::from_layers(
+            Some(LAYERED),
+            None,
+            env(&[
+                ("AGENT_CLI_STORAGE__BACKEND", "mcp"),
+                ("AGENT_CLI_STORAGE__MCP_SERVER", "filecoin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.storage.backend_kind(), StorageBackendKind::Mcp);
+        assert_eq!(config.storage.mcp_server.as_deref(), Some("filecoin"));
+    }
+
+    #[test]
+    fn test_replication() {
+        let content = LAYERED.replace(
+            "[wallet]",
+            r#"
+            [storage.replication]
+            backends = ["ipfs", "lighthouse", "web3_storage"]
+            quorum = 2
+
+            [wallet]"#,
+        );
+        let config = Config::from_layers(Some(&content), None, env(&[])).unwrap();
+
+        let replication = config.storage.replication.unwrap();
+        assert_eq!(replication.backends.len(), 3);
+        assert_eq!(replication.quorum().unwrap(), 2);
+        assert_eq!(replication.below_quorum, QuorumAction::Fail);
+
+        let all = ReplicationConfig { quorum: None, ..replication.clone() };
+        assert_eq!(all.quorum().unwrap(), 3);
+
+        let too_many = ReplicationConfig { quorum: Some(4), ..replication.clone() };
+        assert!(too_many.quorum().unwrap_err().to_string().contains("between 1 and 3"));
+
+        let twice = ReplicationConfig {
+            backends: vec![StorageBackendKind::Ipfs, StorageBackendKind::Ipfs],
+            ..replication
+        };
+        assert!(twice.quorum().unwrap_err().to_string().contains("twice"));
+
+        let mcp = ReplicationConfig {
+            backends: vec![StorageBackendKind::Ipfs, StorageBackendKind::Mcp],
+            quorum: None,
+            below_quorum: QuorumAction::Fail,
+        };
+        assert!(mcp.quorum().unwrap_err().to_string().contains("cannot include \"mcp\""));
+    }
+
+    #[test]
+    fn test_mcp_servers() {
+        let config = Config::from_layers(
+            Some(&format!(
+                "{}{}",
+                LAYERED,
+                r#"
+                [mcp.servers.filecoin]
+                command = "node"
+                args = ["mcp-server/build/index.js"]
+                env = { LIGHTHOUSE_API_KEY = "$LIGHTHOUSE_API_KEY" }
+
+                [mcp.servers.nft]
+                command = "npx"
+                args = ["-y", "mcp-nft-migration"]
+                cwd = "/srv/nft"
+                stderr = "null"
+                restart = { max_attempts = 3 }
+                shared_fs = false
+                "#
+            )),
+            None,
+            env(&[]),
+        )
+        .unwrap();
+
+        assert_eq!(config.mcp.default_timeout, 30);
+        let filecoin = config.mcp.server("filecoin").unwrap();
+        assert_eq!(filecoin.args, vec!["mcp-server/build/index.js"]);
+        assert_eq!(filecoin.stderr, StderrMode::Log);
+        assert!(filecoin.restart.is_none());
+        assert!(filecoin.shared_fs);
+        assert_eq!(filecoin.env["LIGHTHOUSE_API_KEY"], "$LIGHTHOUSE_API_KEY");
+
+        let nft = config.mcp.server("nft").unwrap();
+        assert_eq!(nft.cwd.as_deref(), Some(Path::new("/srv/nft")));
+        assert_eq!(nft.stderr, StderrMode::Null);
+        assert!(!nft.shared_fs);
+        let restart = nft.restart.as_ref().unwrap();
+        assert_eq!(restart.max_attempts, 3);
+        assert_eq!(restart.backoff(0), Duration::from_millis(500));
+        assert_eq!(restart.backoff(2), Duration::from_millis(2000));
+        assert_eq!(restart.backoff(40), Duration::from_secs(30));
+
+        let err = config.mcp.server("python").unwrap_err();
+        assert!(err.to_string().contains("filecoin, nft"));
+    }
+
+    #[test]
+    fn test_redacted() {
+        let config = Config::from_layers(Some(LAYERED), None, env(&[])).unwrap();
+        let shown = toml::to_string_pretty(&config.redacted()).unwrap();
+
+        assert!(!shown.contains("base-key"));
+        assert!(!shown.contains("0xabcdef"));
+        assert!(shown.contains("<redacted>"));
+        assert!(shown.contains("https://api.calibration.node.glif.io/rpc/v1"));
+    }
+}
@@ -1,3 +1,51 @@
 mod agent_identity;
+mod agent_reputation;
+mod agent_validation;
 
-pub use agent_identity::{AgentIdentityClient, AgentInfo, RegisterOptions, StatusUpdateOptions, UriUpdateOptions};
+use alloy::network::EthereumWallet;
+use alloy::providers::{DynProvider, Provider, ProviderBuilder};
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result};
+
+pub use agent_identity::{
+    AgentIdentity, AgentIdentityClient, AgentInfo, AgentRegisteredEvent, AgentStatusChangedEvent,
+    AgentUriUpdatedEvent, RegisterOptions, StatusUpdateOptions, UriUpdateOptions,
+};
+pub use agent_reputation::{
+    AgentReputation, AgentReputationClient, FeedbackError, FeedbackInfo, FeedbackOptions,
+    ReputationBreakdown, ReputationSummary, ResponseOptions,
+};
+pub use agent_validation::{
+    compute_request_hash, parse_request_hash, AgentValidation, AgentValidationClient,
+    ValidationRequest, ValidationRequestOptions, ValidationStats, ValidationStatus,
+    ValidationSubmitOptions,
+};
+
+/// Build an HTTP provider that signs transactions locally with `signer`.
+///
+/// `ProviderBuilder::new()` installs the recommended fillers (gas, nonce and
+/// chain ID), and the wallet filler signs the filled transaction before it is
+/// sent as `eth_sendRawTransaction`, so the RPC node never needs the key.
+pub(crate) fn signing_provider(rpc_url: &str, signer: PrivateKeySigner) -> Result<DynProvider> {
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer))
+        .connect_http(rpc_url.parse().context("Invalid RPC URL")?);
+
+    Ok(provider.erased())
+}
+
+/// Build a read-only HTTP provider for calls that need no signer
+pub fn read_provider(rpc_url: &str) -> Result<DynProvider> {
+    let provider = ProviderBuilder::new()
+        .connect_http(rpc_url.parse().context("Invalid RPC URL")?);
+
+    Ok(provider.erased())
+}
+
+/// Ask the RPC endpoint which chain it serves
+pub async fn fetch_chain_id(rpc_url: &str) -> Result<u64> {
+    read_provider(rpc_url)?
+        .get_chain_id()
+        .await
+        .with_context(|| format!("Failed to query chain ID from {}", rpc_url))
+}
@@ -1,8 +1,10 @@
-use alloy::primitives::{Address, U256};
+use alloy::primitives::{Address, TxHash, U256};
 use alloy::sol;
-use alloy::providers::{Provider, ProviderBuilder, DynProvider};
+use alloy::sol_types::SolEvent;
+use alloy::providers::DynProvider;
 use alloy::rpc::types::TransactionReceipt;
 use alloy::signers::local::PrivateKeySigner;
+use super::{read_provider, signing_provider};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -12,7 +14,7 @@ sol! {
     #[sol(rpc)]
     contract AgentIdentity {
         event AgentRegistered(uint256 indexed agentId, address indexed owner, string metadataURI, uint256 timestamp);
-        event AgentStatusChanged(uint256 indexed agentId, bool indexed isActive, uint256 timestamp);
+        event AgentStatusChanged(uint256 indexed agentId, bool isActive, uint256 timestamp);
         event AgentURIUpdated(uint256 indexed agentId, string oldURI, string newURI, uint256 timestamp);
 
         function getAgent(uint256 agentId) external view returns (address owner, string memory metadataURI, uint256 registeredAt, bool isActive);
@@ -25,6 +27,7 @@ sol! {
         function registrationFee() external view returns (uint256);
         function setAgentStatus(uint256 agentId, bool isActive) external;
         function setRegistrationFee(uint256 newFee) external;
+        function supportsInterface(bytes4 interfaceId) external view returns (bool);
         function totalAgents() external view returns (uint256);
         function updateURI(uint256 agentId, string calldata metadataURI) external;
         function withdrawFees() external;
@@ -40,6 +43,94 @@ pub struct AgentInfo {
     pub is_active: bool,
 }
 
+/// Decoded `AgentRegistered` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRegisteredEvent {
+    pub agent_id: U256,
+    pub owner: Address,
+    pub metadata_uri: String,
+    pub timestamp: U256,
+    pub transaction_hash: TxHash,
+}
+
+/// Decoded `AgentStatusChanged` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStatusChangedEvent {
+    pub agent_id: U256,
+    pub is_active: bool,
+    pub timestamp: U256,
+    pub transaction_hash: TxHash,
+}
+
+/// Decoded `AgentURIUpdated` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentUriUpdatedEvent {
+    pub agent_id: U256,
+    pub old_uri: String,
+    pub new_uri: String,
+    pub timestamp: U256,
+    pub transaction_hash: TxHash,
+}
+
+/// Decode the first `E` event emitted by `contract_address` in a receipt
+fn decode_receipt_event<E: SolEvent>(receipt: &TransactionReceipt, contract_address: Address) -> Result<E> {
+    if !receipt.status() {
+        anyhow::bail!("Transaction {:?} reverted", receipt.transaction_hash);
+    }
+
+    receipt
+        .logs()
+        .iter()
+        .filter(|log| log.address() == contract_address)
+        .find_map(|log| log.log_decode::<E>().ok())
+        .map(|log| log.inner.data)
+        .with_context(|| format!("{} event not found in receipt", E::SIGNATURE))
+}
+
+impl AgentRegisteredEvent {
+    /// Decode from a `register` transaction receipt
+    pub fn from_receipt(receipt: &TransactionReceipt, contract_address: Address) -> Result<Self> {
+        let event = decode_receipt_event::<AgentIdentity::AgentRegistered>(receipt, contract_address)?;
+
+        Ok(Self {
+            agent_id: event.agentId,
+            owner: event.owner,
+            metadata_uri: event.metadataURI,
+            timestamp: event.timestamp,
+            transaction_hash: receipt.transaction_hash,
+        })
+    }
+}
+
+impl AgentStatusChangedEvent {
+    /// Decode from a `setAgentStatus` transaction receipt
+    pub fn from_receipt(receipt: &TransactionReceipt, contract_address: Address) -> Result<Self> {
+        let event = decode_receipt_event::<AgentIdentity::AgentStatusChanged>(receipt, contract_address)?;
+
+        Ok(Self {
+            agent_id: event.agentId,
+            is_active: event.isActive,
+            timestamp: event.timestamp,
+            transaction_hash: receipt.transaction_hash,
+        })
+    }
+}
+
+impl AgentUriUpdatedEvent {
+    /// Decode from an `updateURI` transaction receipt
+    pub fn from_receipt(receipt: &TransactionReceipt, contract_address: Address) -> Result<Self> {
+        let event = decode_receipt_event::<AgentIdentity::AgentURIUpdated>(receipt, contract_address)?;
+
+        Ok(Self {
+            agent_id: event.agentId,
+            old_uri: event.oldURI,
+            new_uri: event.newURI,
+            timestamp: event.timestamp,
+            transaction_hash: receipt.transaction_hash,
+        })
+    }
+}
+
 /// Agent registration options
 #[derive(Debug, Clone)]
 pub struct RegisterOptions {
@@ -64,32 +155,50 @@ pub struct UriUpdateOptions {
 /// AgentIdentity contract client
 pub struct AgentIdentityClient {
     contract: AgentIdentity::AgentIdentityInstance<DynProvider>,
-    signer: Arc<PrivateKeySigner>,
+    /// None for read-only clients, which can only make calls
+    signer: Option<Arc<PrivateKeySigner>>,
     contract_address: Address,
 }
 
 impl AgentIdentityClient {
-    /// Create a new AgentIdentity client
+    /// Create a new AgentIdentity client from a hex private key
     pub fn new(
         contract_address: Address,
         rpc_url: &str,
         private_key: &str,
     ) -> Result<Self> {
-        let signer = Arc::new(private_key.parse::<PrivateKeySigner>()
-            .context("Invalid private key")?);
+        let signer = private_key.parse::<PrivateKeySigner>()
+            .context("Invalid private key")?;
 
-        // Create a basic HTTP provider
-        let provider = ProviderBuilder::new()
-            .connect_http(rpc_url.parse().context("Invalid RPC URL")?);
+        Self::with_signer(contract_address, rpc_url, signer)
+    }
+
+    /// Create a new AgentIdentity client from an already loaded signer
+    pub fn with_signer(
+        contract_address: Address,
+        rpc_url: &str,
+        signer: PrivateKeySigner,
+    ) -> Result<Self> {
+        let provider = signing_provider(rpc_url, signer.clone())?;
+        let signer = Arc::new(signer);
 
         let contract = AgentIdentity::new(
             contract_address,
-            provider.erased(),
+            provider,
         );
 
         Ok(Self {
             contract,
-            signer,
+            signer: Some(signer),
+            contract_address,
+        })
+    }
+
+    /// Create an AgentIdentity client for view calls only, without a wallet
+    pub fn read_only(contract_address: Address, rpc_url: &str) -> Result<Self> {
+        Ok(Self {
+            contract: AgentIdentity::new(contract_address, read_provider(rpc_url)?),
+            signer: None,
             contract_address,
         })
     }
@@ -99,13 +208,16 @@ impl AgentIdentityClient {
         self.contract_address
     }
 
-    /// Get the signer address
-    pub fn signer_address(&self) -> Address {
-        self.signer.address()
+    /// Get the signer address; read-only clients have none
+    pub fn signer_address(&self) -> Result<Address> {
+        self.signer
+            .as_ref()
+            .map(|signer| signer.address())
+            .context("This client is read-only; a wallet signer is needed to send transactions")
     }
 
     /// Register a new agent
-    pub async fn register(&self, options: RegisterOptions) -> Result<TransactionReceipt> {
+    pub async fn register(&self, options: RegisterOptions) -> Result<AgentRegisteredEvent> {
         tracing::info!("Registering agent with metadata URI: {}", options.metadata_uri);
 
         let call = self.contract.register(options.metadata_uri);
@@ -117,7 +229,7 @@ impl AgentIdentityClient {
         };
 
         let pending_tx = call
-            .from(self.signer.address()) // Use the signer address
+            .from(self.signer_address()?) // Use the signer address
             .send()
             .await
             .context("Failed to send register transaction")?;
@@ -127,9 +239,15 @@ impl AgentIdentityClient {
             .await
             .context("Failed to get transaction receipt")?;
 
-        tracing::info!("Agent registered successfully. Transaction hash: {:?}", receipt.transaction_hash);
+        let event = AgentRegisteredEvent::from_receipt(&receipt, self.contract_address)?;
 
-        Ok(receipt)
+        tracing::info!(
+            "Agent {} registered successfully. Transaction hash: {:?}",
+            event.agent_id,
+            receipt.transaction_hash
+        );
+
+        Ok(event)
     }
 
     /// Get agent information
@@ -229,12 +347,12 @@ impl AgentIdentityClient {
     }
 
     /// Update agent status (active/inactive)
-    pub async fn set_agent_status(&self, options: StatusUpdateOptions) -> Result<TransactionReceipt> {
+    pub async fn set_agent_status(&self, options: StatusUpdateOptions) -> Result<AgentStatusChangedEvent> {
         tracing::info!("Setting agent {} status to {}", options.agent_id, options.is_active);
 
         let pending_tx = self.contract
             .setAgentStatus(options.agent_id, options.is_active)
-            .from(self.signer.address()) // Use the signer address
+            .from(self.signer_address()?) // Use the signer address
             .send()
             .await
             .context("Failed to send setAgentStatus transaction")?;
@@ -244,9 +362,11 @@ impl AgentIdentityClient {
             .await
             .context("Failed to get transaction receipt")?;
 
+        let event = AgentStatusChangedEvent::from_receipt(&receipt, self.contract_address)?;
+
         tracing::info!("Agent status updated successfully. Transaction hash: {:?}", receipt.transaction_hash);
 
-        Ok(receipt)
+        Ok(event)
     }
 
     /// Set new registration fee (owner only)
@@ -255,7 +375,7 @@ impl AgentIdentityClient {
 
         let pending_tx = self.contract
             .setRegistrationFee(new_fee)
-            .from(self.signer.address()) // Use the signer address
+            .from(self.signer_address()?) // Use the signer address
             .send()
             .await
             .context("Failed to send setRegistrationFee transaction")?;
@@ -284,12 +404,12 @@ impl AgentIdentityClient {
     }
 
     /// Update agent metadata URI
-    pub async fn update_uri(&self, options: UriUpdateOptions) -> Result<TransactionReceipt> {
+    pub async fn update_uri(&self, options: UriUpdateOptions) -> Result<AgentUriUpdatedEvent> {
         tracing::info!("Updating URI for agent {}: {}", options.agent_id, options.metadata_uri);
 
         let pending_tx = self.contract
             .updateURI(options.agent_id, options.metadata_uri)
-            .from(self.signer.address()) // Use the signer address
+            .from(self.signer_address()?) // Use the signer address
             .send()
             .await
             .context("Failed to send updateURI transaction")?;
@@ -299,9 +419,11 @@ impl AgentIdentityClient {
             .await
             .context("Failed to get transaction receipt")?;
 
+        let event = AgentUriUpdatedEvent::from_receipt(&receipt, self.contract_address)?;
+
         tracing::info!("Agent URI updated successfully. Transaction hash: {:?}", receipt.transaction_hash);
 
-        Ok(receipt)
+        Ok(event)
     }
 
     /// Withdraw collected fees (owner only)
@@ -310,7 +432,7 @@ impl AgentIdentityClient {
 
         let pending_tx = self.contract
             .withdrawFees()
-            .from(self.signer.address()) // Use the signer address
+            .from(self.signer_address()?) // Use the signer address
             .send()
             .await
             .context("Failed to send withdrawFees transaction")?;
@@ -329,6 +451,143 @@ impl AgentIdentityClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloy::consensus::{Receipt, ReceiptEnvelope, ReceiptWithBloom};
+    use alloy::primitives::{Log as PrimitiveLog, B256};
+    use alloy::rpc::types::Log;
+
+    const IDENTITY: Address = Address::repeat_byte(0x42);
+
+    fn log_from<E: SolEvent>(address: Address, event: &E) -> Log {
+        Log {
+            inner: PrimitiveLog {
+                address,
+                data: event.encode_log_data(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn receipt_with_logs(logs: Vec<Log>, status: bool) -> TransactionReceipt {
+        TransactionReceipt {
+            inner: ReceiptEnvelope::Legacy(ReceiptWithBloom {
+                receipt: Receipt {
+                    status: status.into(),
+                    cumulative_gas_used: 0,
+                    logs,
+                },
+                logs_bloom: Default::default(),
+            }),
+            transaction_hash: B256::repeat_byte(0xaa),
+            transaction_index: Some(0),
+            block_hash: None,
+            block_number: Some(1),
+            gas_used: 0,
+            effective_gas_price: 0,
+            blob_gas_used: None,
+            blob_gas_price: None,
+            from: Address::ZERO,
+            to: Some(IDENTITY),
+            contract_address: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_agent_registered_large_id() {
+        let agent_id = U256::from(70_000u64);
+        let event = AgentIdentity::AgentRegistered {
+            agentId: agent_id,
+            owner: Address::repeat_byte(0x01),
+            metadataURI: "ipfs://metadata".to_string(),
+            timestamp: U256::from(1_700_000_000u64),
+        };
+        let receipt = receipt_with_logs(vec![log_from(IDENTITY, &event)], true);
+
+        let decoded = AgentRegisteredEvent::from_receipt(&receipt, IDENTITY).unwrap();
+        assert_eq!(decoded.agent_id, agent_id);
+        assert_eq!(decoded.owner, Address::repeat_byte(0x01));
+        assert_eq!(decoded.metadata_uri, "ipfs://metadata");
+        assert_eq!(decoded.timestamp, U256::from(1_700_000_000u64));
+        assert_eq!(decoded.transaction_hash, B256::repeat_byte(0xaa));
+    }
+
+    #[test]
+    fn test_decode_ignores_other_contracts_and_events() {
+        let foreign = AgentIdentity::AgentRegistered {
+            agentId: U256::from(1),
+            owner: Address::ZERO,
+            metadataURI: "ipfs://foreign".to_string(),
+            timestamp: U256::ZERO,
+        };
+        let status = AgentIdentity::AgentStatusChanged {
+            agentId: U256::from(2),
+            isActive: true,
+            timestamp: U256::ZERO,
+        };
+        let ours = AgentIdentity::AgentRegistered {
+            agentId: U256::from(3),
+            owner: Address::ZERO,
+            metadataURI: "ipfs://ours".to_string(),
+            timestamp: U256::ZERO,
+        };
+        let receipt = receipt_with_logs(
+            vec![
+                log_from(Address::repeat_byte(0x99), &foreign),
+                log_from(IDENTITY, &status),
+                log_from(IDENTITY, &ours),
+            ],
+            true,
+        );
+
+        let decoded = AgentRegisteredEvent::from_receipt(&receipt, IDENTITY).unwrap();
+        assert_eq!(decoded.agent_id, U256::from(3));
+        assert_eq!(decoded.metadata_uri, "ipfs://ours");
+    }
+
+    #[test]
+    fn test_decode_agent_status_changed() {
+        let event = AgentIdentity::AgentStatusChanged {
+            agentId: U256::from(512),
+            isActive: false,
+            timestamp: U256::from(99),
+        };
+        let receipt = receipt_with_logs(vec![log_from(IDENTITY, &event)], true);
+
+        let decoded = AgentStatusChangedEvent::from_receipt(&receipt, IDENTITY).unwrap();
+        assert_eq!(decoded.agent_id, U256::from(512));
+        assert!(!decoded.is_active);
+        assert_eq!(decoded.timestamp, U256::from(99));
+    }
+
+    #[test]
+    fn test_decode_agent_uri_updated() {
+        let event = AgentIdentity::AgentURIUpdated {
+            agentId: U256::from(256),
+            oldURI: "ipfs://old".to_string(),
+            newURI: "ipfs://new".to_string(),
+            timestamp: U256::from(100),
+        };
+        let receipt = receipt_with_logs(vec![log_from(IDENTITY, &event)], true);
+
+        let decoded = AgentUriUpdatedEvent::from_receipt(&receipt, IDENTITY).unwrap();
+        assert_eq!(decoded.agent_id, U256::from(256));
+        assert_eq!(decoded.old_uri, "ipfs://old");
+        assert_eq!(decoded.new_uri, "ipfs://new");
+    }
+
+    #[test]
+    fn test_decode_missing_or_reverted() {
+        let empty = receipt_with_logs(vec![], true);
+        assert!(AgentRegisteredEvent::from_receipt(&empty, IDENTITY).is_err());
+
+        let event = AgentIdentity::AgentRegistered {
+            agentId: U256::from(1),
+            owner: Address::ZERO,
+            metadataURI: "ipfs://metadata".to_string(),
+            timestamp: U256::ZERO,
+        };
+        let reverted = receipt_with_logs(vec![log_from(IDENTITY, &event)], false);
+        assert!(AgentRegisteredEvent::from_receipt(&reverted, IDENTITY).is_err());
+    }
 
     #[test]
     fn test_agent_info_creation() {
@@ -377,4 +636,113 @@ mod tests {
         assert_eq!(options.agent_id, U256::from(456));
         assert_eq!(options.metadata_uri, "ipfs://new-metadata");
     }
-}
\ No newline at end of file
+
+    /// Deploy AgentIdentity to a fresh anvil node and drive it with a raw
+    /// private key that anvil does not manage, so every write has to be
+    /// signed locally. Needs `anvil` on PATH and `forge build` in `contracts/`.
+    #[tokio::test]
+    #[ignore = "requires anvil and contracts/out from forge build"]
+    async fn test_register_and_update_uri_on_anvil() {
+        use alloy::network::{EthereumWallet, TransactionBuilder};
+        use alloy::primitives::Bytes;
+        use alloy::providers::{Provider, ProviderBuilder};
+        use alloy::rpc::types::TransactionRequest;
+        use alloy::sol_types::SolValue;
+
+        // First of anvil's well-known dev accounts
+        const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        // Kill anvil even if an assertion below fails
+        struct AnvilGuard(std::process::Child);
+        impl Drop for AnvilGuard {
+            fn drop(&mut self) {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+        }
+        let _anvil = AnvilGuard(
+            std::process::Command::new("anvil")
+                .args(["--port", &port.to_string()])
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .expect("failed to spawn anvil"),
+        );
+        let rpc_url = format!("http://127.0.0.1:{}", port);
+
+        let funder: PrivateKeySigner = ANVIL_KEY.parse().unwrap();
+        let funder_provider = ProviderBuilder::new()
+            .wallet(EthereumWallet::from(funder))
+            .connect_http(rpc_url.parse().unwrap());
+        for _ in 0..50 {
+            if funder_provider.get_chain_id().await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let artifact_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../contracts/out/AgentIdentity.sol/AgentIdentity.json");
+        let artifact: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(artifact_path).unwrap()).unwrap();
+        let bytecode: Bytes = artifact["bytecode"]["object"].as_str().unwrap().parse().unwrap();
+
+        // Fund a brand new key that anvil knows nothing about
+        let signer = PrivateKeySigner::random();
+        let private_key = alloy::hex::encode_prefixed(signer.to_bytes());
+        funder_provider
+            .send_transaction(
+                TransactionRequest::default()
+                    .with_to(signer.address())
+                    .with_value(U256::from(10u64).pow(U256::from(18))),
+            )
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+
+        // Deploy with the new key, constructor(uint256 registrationFee = 0)
+        let deployer = ProviderBuilder::new()
+            .wallet(EthereumWallet::from(signer.clone()))
+            .connect_http(rpc_url.parse().unwrap());
+        let mut deploy_code = bytecode.to_vec();
+        deploy_code.extend_from_slice(&U256::ZERO.abi_encode());
+        let contract_address = deployer
+            .send_transaction(TransactionRequest::default().with_deploy_code(deploy_code))
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap()
+            .contract_address
+            .expect("deployment did not create a contract");
+
+        let client = AgentIdentityClient::new(contract_address, &rpc_url, &private_key).unwrap();
+
+        let registered = client
+            .register(RegisterOptions {
+                metadata_uri: "ipfs://first".to_string(),
+                value: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(registered.owner, signer.address());
+        assert_eq!(registered.metadata_uri, "ipfs://first");
+
+        let updated = client
+            .update_uri(UriUpdateOptions {
+                agent_id: registered.agent_id,
+                metadata_uri: "ipfs://second".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(updated.old_uri, "ipfs://first");
+        assert_eq!(updated.new_uri, "ipfs://second");
+        assert_eq!(client.get_agent_uri(registered.agent_id).await.unwrap(), "ipfs://second");
+    }
+}
@@ -0,0 +1,452 @@
+use alloy::primitives::{Address, U256};
+use alloy::sol;
+use alloy::providers::DynProvider;
+use alloy::rpc::types::TransactionReceipt;
+use alloy::signers::local::PrivateKeySigner;
+use super::{read_provider, signing_provider};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Define the AgentReputation contract interface using alloy's sol! macro
+sol! {
+    #[sol(rpc)]
+    contract AgentReputation {
+        event FeedbackGiven(uint256 indexed feedbackId, uint256 indexed agentId, address indexed from, uint8 score, string[] tags, string fileURI, uint256 timestamp);
+        event FeedbackRevoked(uint256 indexed feedbackId, uint256 indexed agentId, address indexed from, uint256 timestamp);
+        event FeedbackResponseAppended(uint256 indexed feedbackId, uint256 indexed agentId, string response, uint256 timestamp);
+
+        // Raised by the identity registry's ownerOf and bubbled up through agentExists
+        error ERC721NonexistentToken(uint256 tokenId);
+
+        function FEEDBACK_COOLDOWN() external view returns (uint256);
+        function appendResponse(uint256 feedbackId, string calldata response) external;
+        function getAgentFeedbacks(uint256 agentId) external view returns (uint256[] memory);
+        function getFeedback(uint256 feedbackId) external view returns (uint256 agentId, address from, uint8 score, string[] memory tags, string memory fileURI, uint256 timestamp, bool revoked, string memory response);
+        function getFeedbackCount(uint256 agentId) external view returns (uint256);
+        function getReputation(uint256 agentId) external view returns (uint256 avgScore, uint256 totalFeedbacks, uint256 activeFeedbacks);
+        function getReputationBreakdown(uint256 agentId) external view returns (uint8[] memory scores, uint256[] memory timestamps);
+        function giveFeedback(uint256 agentId, uint8 score, string[] calldata tags, string calldata fileURI) external returns (uint256 feedbackId);
+        function identityRegistry() external view returns (address);
+        function revokeFeedback(uint256 feedbackId) external;
+    }
+}
+
+/// Contract reverts from `giveFeedback` that callers are expected to handle
+#[derive(Debug, thiserror::Error)]
+pub enum FeedbackError {
+    #[error("Agent {0} does not exist")]
+    AgentNotFound(U256),
+    #[error("Self-feedback not allowed: the signer owns agent {0}")]
+    SelfFeedback(U256),
+    #[error("Feedback cooldown not expired: this wallet already gave feedback to agent {0} within the FEEDBACK_COOLDOWN period")]
+    Cooldown(U256),
+    #[error("Score must be between 0 and 100")]
+    InvalidScore,
+}
+
+impl FeedbackError {
+    /// Map a `giveFeedback` revert reason onto a typed error
+    pub fn from_revert_reason(agent_id: U256, reason: &str) -> Option<Self> {
+        if reason.contains("Self-feedback not allowed") {
+            Some(FeedbackError::SelfFeedback(agent_id))
+        } else if reason.contains("Feedback cooldown not expired") {
+            Some(FeedbackError::Cooldown(agent_id))
+        } else if reason.contains("Agent does not exist") {
+            Some(FeedbackError::AgentNotFound(agent_id))
+        } else if reason.contains("Score must be 0-100") {
+            Some(FeedbackError::InvalidScore)
+        } else {
+            None
+        }
+    }
+
+    /// Classify a failed `giveFeedback` call.
+    ///
+    /// Prefers the ABI-encoded revert data; falls back to the RPC error message,
+    /// since some Filecoin nodes only report the reason as text.
+    fn from_contract_error(agent_id: U256, error: &alloy::contract::Error) -> Option<Self> {
+        if error
+            .as_decoded_error::<AgentReputation::ERC721NonexistentToken>()
+            .is_some()
+        {
+            return Some(FeedbackError::AgentNotFound(agent_id));
+        }
+
+        if let Some(revert) = error.as_decoded_error::<alloy::sol_types::Revert>() {
+            return Self::from_revert_reason(agent_id, &revert.reason);
+        }
+
+        Self::from_revert_reason(agent_id, &error.to_string())
+    }
+}
+
+/// Feedback information structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackInfo {
+    pub agent_id: U256,
+    pub from: Address,
+    pub score: u8,
+    pub tags: Vec<String>,
+    pub file_uri: String,
+    pub timestamp: U256,
+    pub revoked: bool,
+    pub response: String,
+}
+
+/// Aggregated reputation for an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationSummary {
+    pub avg_score: U256,
+    pub total_feedbacks: U256,
+    pub active_feedbacks: U256,
+}
+
+/// Scores and timestamps of all non-revoked feedback for an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationBreakdown {
+    pub scores: Vec<u8>,
+    pub timestamps: Vec<U256>,
+}
+
+/// Feedback submission options
+#[derive(Debug, Clone)]
+pub struct FeedbackOptions {
+    pub agent_id: U256,
+    pub score: u8,
+    pub tags: Vec<String>,
+    pub file_uri: String,
+}
+
+/// Feedback response options
+#[derive(Debug, Clone)]
+pub struct ResponseOptions {
+    pub feedback_id: U256,
+    pub response: String,
+}
+
+/// AgentReputation contract client
+pub struct AgentReputationClient {
+    contract: AgentReputation::AgentReputationInstance<DynProvider>,
+    /// None for read-only clients, which can only make calls
+    signer: Option<Arc<PrivateKeySigner>>,
+    contract_address: Address,
+}
+
+impl AgentReputationClient {
+    /// Create a new AgentReputation client from a hex private key
+    pub fn new(
+        contract_address: Address,
+        rpc_url: &str,
+        private_key: &str,
+    ) -> Result<Self> {
+        let signer = private_key.parse::<PrivateKeySigner>()
+            .context("Invalid private key")?;
+
+        Self::with_signer(contract_address, rpc_url, signer)
+    }
+
+    /// Create a new AgentReputation client from an already loaded signer
+    pub fn with_signer(
+        contract_address: Address,
+        rpc_url: &str,
+        signer: PrivateKeySigner,
+    ) -> Result<Self> {
+        let provider = signing_provider(rpc_url, signer.clone())?;
+        let signer = Arc::new(signer);
+
+        let contract = AgentReputation::new(
+            contract_address,
+            provider,
+        );
+
+        Ok(Self {
+            contract,
+            signer: Some(signer),
+            contract_address,
+        })
+    }
+
+    /// Create an AgentReputation client for view calls only, without a wallet
+    pub fn read_only(contract_address: Address, rpc_url: &str) -> Result<Self> {
+        Ok(Self {
+            contract: AgentReputation::new(contract_address, read_provider(rpc_url)?),
+            signer: None,
+            contract_address,
+        })
+    }
+
+    /// Get the contract address
+    pub fn address(&self) -> Address {
+        self.contract_address
+    }
+
+    /// Get the signer address; read-only clients have none
+    pub fn signer_address(&self) -> Result<Address> {
+        self.signer
+            .as_ref()
+            .map(|signer| signer.address())
+            .context("This client is read-only; a wallet signer is needed to send transactions")
+    }
+
+    /// Give feedback to an agent
+    pub async fn give_feedback(&self, options: FeedbackOptions) -> Result<TransactionReceipt> {
+        tracing::info!("Giving feedback to agent {} with score {}", options.agent_id, options.score);
+
+        let agent_id = options.agent_id;
+        let pending_tx = self.contract
+            .giveFeedback(options.agent_id, options.score, options.tags, options.file_uri)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .map_err(|e| match FeedbackError::from_contract_error(agent_id, &e) {
+                Some(feedback_error) => anyhow::Error::new(feedback_error),
+                None => anyhow::Error::new(e).context("Failed to send giveFeedback transaction"),
+            })?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        if !receipt.status() {
+            anyhow::bail!("giveFeedback transaction {:?} reverted", receipt.transaction_hash);
+        }
+
+        tracing::info!("Feedback submitted successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Revoke previously given feedback (feedback author only)
+    pub async fn revoke_feedback(&self, feedback_id: U256) -> Result<TransactionReceipt> {
+        tracing::info!("Revoking feedback {}", feedback_id);
+
+        let pending_tx = self.contract
+            .revokeFeedback(feedback_id)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .context("Failed to send revokeFeedback transaction")?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        tracing::info!("Feedback revoked successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Append a response to feedback (agent owner only)
+    pub async fn append_response(&self, options: ResponseOptions) -> Result<TransactionReceipt> {
+        tracing::info!("Appending response to feedback {}", options.feedback_id);
+
+        let pending_tx = self.contract
+            .appendResponse(options.feedback_id, options.response)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .context("Failed to send appendResponse transaction")?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        tracing::info!("Response appended successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Get feedback information
+    pub async fn get_feedback(&self, feedback_id: U256) -> Result<FeedbackInfo> {
+        tracing::debug!("Fetching feedback info for ID: {}", feedback_id);
+
+        let result = self.contract
+            .getFeedback(feedback_id)
+            .call()
+            .await
+            .context("Failed to get feedback information")?;
+
+        Ok(FeedbackInfo {
+            agent_id: result.agentId,
+            from: result.from,
+            score: result.score,
+            tags: result.tags,
+            file_uri: result.fileURI,
+            timestamp: result.timestamp,
+            revoked: result.revoked,
+            response: result.response,
+        })
+    }
+
+    /// Get aggregated reputation for an agent
+    pub async fn get_reputation(&self, agent_id: U256) -> Result<ReputationSummary> {
+        tracing::debug!("Fetching reputation for agent ID: {}", agent_id);
+
+        let result = self.contract
+            .getReputation(agent_id)
+            .call()
+            .await
+            .context("Failed to get reputation")?;
+
+        Ok(ReputationSummary {
+            avg_score: result.avgScore,
+            total_feedbacks: result.totalFeedbacks,
+            active_feedbacks: result.activeFeedbacks,
+        })
+    }
+
+    /// Get scores and timestamps of all non-revoked feedback for an agent
+    pub async fn get_reputation_breakdown(&self, agent_id: U256) -> Result<ReputationBreakdown> {
+        tracing::debug!("Fetching reputation breakdown for agent ID: {}", agent_id);
+
+        let result = self.contract
+            .getReputationBreakdown(agent_id)
+            .call()
+            .await
+            .context("Failed to get reputation breakdown")?;
+
+        Ok(ReputationBreakdown {
+            scores: result.scores,
+            timestamps: result.timestamps,
+        })
+    }
+
+    /// Get all feedback IDs for an agent
+    pub async fn get_agent_feedbacks(&self, agent_id: U256) -> Result<Vec<U256>> {
+        tracing::debug!("Fetching feedback IDs for agent ID: {}", agent_id);
+
+        let feedback_ids = self.contract
+            .getAgentFeedbacks(agent_id)
+            .call()
+            .await
+            .context("Failed to get agent feedbacks")?;
+
+        Ok(feedback_ids.to_vec())
+    }
+
+    /// Get the number of feedback entries for an agent (including revoked)
+    pub async fn get_feedback_count(&self, agent_id: U256) -> Result<U256> {
+        tracing::debug!("Fetching feedback count for agent ID: {}", agent_id);
+
+        let count = self.contract
+            .getFeedbackCount(agent_id)
+            .call()
+            .await
+            .context("Failed to get feedback count")?;
+
+        Ok(count)
+    }
+
+    /// Get the minimum time between feedbacks from the same address, in seconds
+    pub async fn feedback_cooldown(&self) -> Result<U256> {
+        tracing::debug!("Fetching feedback cooldown");
+
+        let cooldown = self.contract
+            .FEEDBACK_COOLDOWN()
+            .call()
+            .await
+            .context("Failed to get feedback cooldown")?;
+
+        Ok(cooldown)
+    }
+
+    /// Get the identity registry this reputation registry is bound to
+    pub async fn identity_registry(&self) -> Result<Address> {
+        tracing::debug!("Fetching identity registry address");
+
+        let registry = self.contract
+            .identityRegistry()
+            .call()
+            .await
+            .context("Failed to get identity registry")?;
+
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_info_creation() {
+        let feedback = FeedbackInfo {
+            agent_id: U256::from(7),
+            from: Address::ZERO,
+            score: 85,
+            tags: vec!["fast".to_string(), "accurate".to_string()],
+            file_uri: "ipfs://feedback".to_string(),
+            timestamp: U256::from(1234567890),
+            revoked: false,
+            response: String::new(),
+        };
+
+        assert_eq!(feedback.agent_id, U256::from(7));
+        assert_eq!(feedback.score, 85);
+        assert_eq!(feedback.tags.len(), 2);
+        assert!(!feedback.revoked);
+    }
+
+    #[test]
+    fn test_feedback_options_creation() {
+        let options = FeedbackOptions {
+            agent_id: U256::from(42),
+            score: 100,
+            tags: vec!["reliable".to_string()],
+            file_uri: "ipfs://details".to_string(),
+        };
+
+        assert_eq!(options.agent_id, U256::from(42));
+        assert_eq!(options.score, 100);
+        assert_eq!(options.tags, vec!["reliable".to_string()]);
+        assert_eq!(options.file_uri, "ipfs://details");
+    }
+
+    #[test]
+    fn test_feedback_error_from_revert_reason() {
+        let agent_id = U256::from(9);
+
+        assert!(matches!(
+            FeedbackError::from_revert_reason(agent_id, "Self-feedback not allowed"),
+            Some(FeedbackError::SelfFeedback(id)) if id == agent_id
+        ));
+        assert!(matches!(
+            FeedbackError::from_revert_reason(
+                agent_id,
+                "server returned an error response: execution reverted: Feedback cooldown not expired"
+            ),
+            Some(FeedbackError::Cooldown(_))
+        ));
+        assert!(matches!(
+            FeedbackError::from_revert_reason(agent_id, "Score must be 0-100"),
+            Some(FeedbackError::InvalidScore)
+        ));
+        assert!(FeedbackError::from_revert_reason(agent_id, "out of gas").is_none());
+    }
+
+    #[test]
+    fn test_feedback_error_survives_context() {
+        let error = anyhow::Error::new(FeedbackError::Cooldown(U256::from(1)))
+            .context("Failed to give feedback");
+
+        assert!(matches!(
+            error.downcast_ref::<FeedbackError>(),
+            Some(FeedbackError::Cooldown(_))
+        ));
+    }
+
+    #[test]
+    fn test_response_options_creation() {
+        let options = ResponseOptions {
+            feedback_id: U256::from(3),
+            response: "Thanks for the feedback".to_string(),
+        };
+
+        assert_eq!(options.feedback_id, U256::from(3));
+        assert_eq!(options.response, "Thanks for the feedback");
+    }
+}
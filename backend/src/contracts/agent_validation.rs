@@ -0,0 +1,420 @@
+use alloy::primitives::{keccak256, Address, B256, U256};
+use alloy::sol;
+use alloy::sol_types::SolValue;
+use alloy::providers::DynProvider;
+use alloy::rpc::types::TransactionReceipt;
+use alloy::signers::local::PrivateKeySigner;
+use super::{read_provider, signing_provider};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Define the AgentValidation contract interface using alloy's sol! macro
+sol! {
+    #[sol(rpc)]
+    contract AgentValidation {
+        enum ValidationStatus { Pending, Completed, Expired }
+
+        event ValidationRequested(bytes32 indexed requestHash, uint256 indexed agentId, address indexed requester, address validator, string workURI, uint256 timestamp);
+        event ValidationSubmitted(bytes32 indexed requestHash, uint256 indexed agentId, address indexed validator, bool isValid, string proofURI, uint256 timestamp);
+        event ValidationExpired(bytes32 indexed requestHash, uint256 timestamp);
+
+        function VALIDATION_EXPIRY() external view returns (uint256);
+        function expireValidation(bytes32 requestHash) external;
+        function getAgentValidations(uint256 agentId) external view returns (bytes32[] memory);
+        function getValidationRequest(bytes32 requestHash) external view returns (uint256 agentId, address requester, address validator, string memory workURI, ValidationStatus status, bool isValid, string memory proofURI, uint256 requestedAt, uint256 completedAt);
+        function getValidationStats(uint256 agentId) external view returns (uint256 totalValidations, uint256 passedValidations, uint256 failedValidations, uint256 pendingValidations);
+        function getValidationStatus(bytes32 requestHash) external view returns (ValidationStatus status, bool isValid);
+        function identityRegistry() external view returns (address);
+        function isExpired(bytes32 requestHash) external view returns (bool);
+        function requestValidation(uint256 agentId, string calldata workURI, address validator) external returns (bytes32 requestHash);
+        function submitValidation(bytes32 requestHash, bool isValid, string calldata proofURI) external;
+    }
+}
+
+/// Lifecycle state of a validation request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValidationStatus {
+    Pending,
+    Completed,
+    Expired,
+}
+
+impl TryFrom<u8> for ValidationStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ValidationStatus::Pending),
+            1 => Ok(ValidationStatus::Completed),
+            2 => Ok(ValidationStatus::Expired),
+            other => anyhow::bail!("Unknown validation status: {}", other),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationStatus::Pending => write!(f, "pending"),
+            ValidationStatus::Completed => write!(f, "completed"),
+            ValidationStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// Validation request information structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRequest {
+    pub request_hash: B256,
+    pub agent_id: U256,
+    pub requester: Address,
+    pub validator: Address,
+    pub work_uri: String,
+    pub status: ValidationStatus,
+    pub is_valid: bool,
+    pub proof_uri: String,
+    pub requested_at: U256,
+    pub completed_at: U256,
+}
+
+/// Validation counters for an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationStats {
+    pub total: U256,
+    pub passed: U256,
+    pub failed: U256,
+    pub pending: U256,
+}
+
+/// Validation request options
+#[derive(Debug, Clone)]
+pub struct ValidationRequestOptions {
+    pub agent_id: U256,
+    pub work_uri: String,
+    pub validator: Address,
+}
+
+/// Validation submission options
+#[derive(Debug, Clone)]
+pub struct ValidationSubmitOptions {
+    pub request_hash: B256,
+    pub is_valid: bool,
+    pub proof_uri: String,
+}
+
+/// Compute the request hash the contract assigns to a validation request.
+///
+/// Mirrors `keccak256(abi.encode(agentId, workURI, validator, msg.sender, block.chainid))`
+/// in `AgentValidation.requestValidation`, so the hash can be known before the
+/// transaction is mined.
+pub fn compute_request_hash(
+    agent_id: U256,
+    work_uri: &str,
+    validator: Address,
+    requester: Address,
+    chain_id: u64,
+) -> B256 {
+    let encoded = (agent_id, work_uri.to_string(), validator, requester, U256::from(chain_id))
+        .abi_encode_params();
+    keccak256(encoded)
+}
+
+/// Parse a 0x-prefixed (or bare) hex string into a request hash
+pub fn parse_request_hash(value: &str) -> Result<B256> {
+    value
+        .parse::<B256>()
+        .with_context(|| format!("Invalid request hash: {}", value))
+}
+
+/// AgentValidation contract client
+pub struct AgentValidationClient {
+    contract: AgentValidation::AgentValidationInstance<DynProvider>,
+    /// None for read-only clients, which can only make calls
+    signer: Option<Arc<PrivateKeySigner>>,
+    contract_address: Address,
+}
+
+impl AgentValidationClient {
+    /// Create a new AgentValidation client from a hex private key
+    pub fn new(
+        contract_address: Address,
+        rpc_url: &str,
+        private_key: &str,
+    ) -> Result<Self> {
+        let signer = private_key.parse::<PrivateKeySigner>()
+            .context("Invalid private key")?;
+
+        Self::with_signer(contract_address, rpc_url, signer)
+    }
+
+    /// Create a new AgentValidation client from an already loaded signer
+    pub fn with_signer(
+        contract_address: Address,
+        rpc_url: &str,
+        signer: PrivateKeySigner,
+    ) -> Result<Self> {
+        let provider = signing_provider(rpc_url, signer.clone())?;
+        let signer = Arc::new(signer);
+
+        let contract = AgentValidation::new(
+            contract_address,
+            provider,
+        );
+
+        Ok(Self {
+            contract,
+            signer: Some(signer),
+            contract_address,
+        })
+    }
+
+    /// Create an AgentValidation client for view calls only, without a wallet
+    pub fn read_only(contract_address: Address, rpc_url: &str) -> Result<Self> {
+        Ok(Self {
+            contract: AgentValidation::new(contract_address, read_provider(rpc_url)?),
+            signer: None,
+            contract_address,
+        })
+    }
+
+    /// Get the contract address
+    pub fn address(&self) -> Address {
+        self.contract_address
+    }
+
+    /// Get the signer address; read-only clients have none
+    pub fn signer_address(&self) -> Result<Address> {
+        self.signer
+            .as_ref()
+            .map(|signer| signer.address())
+            .context("This client is read-only; a wallet signer is needed to send transactions")
+    }
+
+    /// Request validation of an agent's work by a validator
+    pub async fn request_validation(&self, options: ValidationRequestOptions) -> Result<TransactionReceipt> {
+        tracing::info!(
+            "Requesting validation for agent {} from validator {}",
+            options.agent_id,
+            options.validator
+        );
+
+        let pending_tx = self.contract
+            .requestValidation(options.agent_id, options.work_uri, options.validator)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .context("Failed to send requestValidation transaction")?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        tracing::info!("Validation requested successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Submit a validation result (assigned validator only)
+    pub async fn submit_validation(&self, options: ValidationSubmitOptions) -> Result<TransactionReceipt> {
+        tracing::info!(
+            "Submitting validation for request {}: valid={}",
+            options.request_hash,
+            options.is_valid
+        );
+
+        let pending_tx = self.contract
+            .submitValidation(options.request_hash, options.is_valid, options.proof_uri)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .context("Failed to send submitValidation transaction")?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        tracing::info!("Validation submitted successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Mark a pending request as expired once the validation window has passed
+    pub async fn expire_validation(&self, request_hash: B256) -> Result<TransactionReceipt> {
+        tracing::info!("Expiring validation request {}", request_hash);
+
+        let pending_tx = self.contract
+            .expireValidation(request_hash)
+            .from(self.signer_address()?) // Use the signer address
+            .send()
+            .await
+            .context("Failed to send expireValidation transaction")?;
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .context("Failed to get transaction receipt")?;
+
+        tracing::info!("Validation expired successfully. Transaction hash: {:?}", receipt.transaction_hash);
+
+        Ok(receipt)
+    }
+
+    /// Get validation request information
+    pub async fn get_validation_request(&self, request_hash: B256) -> Result<ValidationRequest> {
+        tracing::debug!("Fetching validation request: {}", request_hash);
+
+        let result = self.contract
+            .getValidationRequest(request_hash)
+            .call()
+            .await
+            .context("Failed to get validation request")?;
+
+        Ok(ValidationRequest {
+            request_hash,
+            agent_id: result.agentId,
+            requester: result.requester,
+            validator: result.validator,
+            work_uri: result.workURI,
+            status: ValidationStatus::try_from(u8::from(result.status))?,
+            is_valid: result.isValid,
+            proof_uri: result.proofURI,
+            requested_at: result.requestedAt,
+            completed_at: result.completedAt,
+        })
+    }
+
+    /// Get the status and result of a validation request
+    pub async fn get_validation_status(&self, request_hash: B256) -> Result<(ValidationStatus, bool)> {
+        tracing::debug!("Fetching validation status: {}", request_hash);
+
+        let result = self.contract
+            .getValidationStatus(request_hash)
+            .call()
+            .await
+            .context("Failed to get validation status")?;
+
+        Ok((ValidationStatus::try_from(u8::from(result.status))?, result.isValid))
+    }
+
+    /// Get all validation request hashes for an agent
+    pub async fn get_agent_validations(&self, agent_id: U256) -> Result<Vec<B256>> {
+        tracing::debug!("Fetching validations for agent ID: {}", agent_id);
+
+        let request_hashes = self.contract
+            .getAgentValidations(agent_id)
+            .call()
+            .await
+            .context("Failed to get agent validations")?;
+
+        Ok(request_hashes.to_vec())
+    }
+
+    /// Get validation counters for an agent
+    pub async fn get_validation_stats(&self, agent_id: U256) -> Result<ValidationStats> {
+        tracing::debug!("Fetching validation stats for agent ID: {}", agent_id);
+
+        let result = self.contract
+            .getValidationStats(agent_id)
+            .call()
+            .await
+            .context("Failed to get validation stats")?;
+
+        Ok(ValidationStats {
+            total: result.totalValidations,
+            passed: result.passedValidations,
+            failed: result.failedValidations,
+            pending: result.pendingValidations,
+        })
+    }
+
+    /// Check if a pending request has passed its validation window
+    pub async fn is_expired(&self, request_hash: B256) -> Result<bool> {
+        tracing::debug!("Checking if validation request {} is expired", request_hash);
+
+        let expired = self.contract
+            .isExpired(request_hash)
+            .call()
+            .await
+            .context("Failed to check validation expiry")?;
+
+        Ok(expired)
+    }
+
+    /// Get the validation window, in seconds
+    pub async fn validation_expiry(&self) -> Result<U256> {
+        tracing::debug!("Fetching validation expiry");
+
+        let expiry = self.contract
+            .VALIDATION_EXPIRY()
+            .call()
+            .await
+            .context("Failed to get validation expiry")?;
+
+        Ok(expiry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_status_from_u8() {
+        assert_eq!(ValidationStatus::try_from(0u8).unwrap(), ValidationStatus::Pending);
+        assert_eq!(ValidationStatus::try_from(1u8).unwrap(), ValidationStatus::Completed);
+        assert_eq!(ValidationStatus::try_from(2u8).unwrap(), ValidationStatus::Expired);
+        assert!(ValidationStatus::try_from(3u8).is_err());
+    }
+
+    #[test]
+    fn test_sol_status_matches_typed_status() {
+        let status = AgentValidation::ValidationStatus::Completed;
+        assert_eq!(
+            ValidationStatus::try_from(u8::from(status)).unwrap(),
+            ValidationStatus::Completed
+        );
+    }
+
+    #[test]
+    fn test_compute_request_hash_encoding() {
+        let agent_id = U256::from(1);
+        let validator = Address::repeat_byte(0x11);
+        let requester = Address::repeat_byte(0x22);
+
+        // abi.encode(uint256, string, address, address, uint256): five head words,
+        // followed by the string length word and one padded data word
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&U256::from(1).to_be_bytes::<32>());
+        expected.extend_from_slice(&U256::from(5 * 32).to_be_bytes::<32>());
+        expected.extend_from_slice(validator.into_word().as_slice());
+        expected.extend_from_slice(requester.into_word().as_slice());
+        expected.extend_from_slice(&U256::from(314159).to_be_bytes::<32>());
+        expected.extend_from_slice(&U256::from(8).to_be_bytes::<32>());
+        let mut data = [0u8; 32];
+        data[..8].copy_from_slice(b"ipfs://w");
+        expected.extend_from_slice(&data);
+
+        let hash = compute_request_hash(agent_id, "ipfs://w", validator, requester, 314159);
+        assert_eq!(hash, keccak256(expected));
+    }
+
+    #[test]
+    fn test_compute_request_hash_is_chain_specific() {
+        let validator = Address::repeat_byte(0x11);
+        let requester = Address::repeat_byte(0x22);
+
+        let calibration = compute_request_hash(U256::from(1), "ipfs://work", validator, requester, 314159);
+        let mainnet = compute_request_hash(U256::from(1), "ipfs://work", validator, requester, 314);
+        assert_ne!(calibration, mainnet);
+    }
+
+    #[test]
+    fn test_parse_request_hash() {
+        let hash = B256::repeat_byte(0xab);
+        assert_eq!(parse_request_hash(&hash.to_string()).unwrap(), hash);
+        assert!(parse_request_hash("0x1234").is_err());
+    }
+}
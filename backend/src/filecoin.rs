@@ -1,8 +1,18 @@
+use crate::storage::{verify_car_root, verify_upload, StorageBackend, StorageError, StorageStatus};
+use crate::upload::{FileUpload, Progress};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+/// Lighthouse account API, separate from the upload node
+const LIGHTHOUSE_ACCOUNT_URL: &str = "https://api.lighthouse.storage";
+
+/// Gateways used to read content back from each service
+const LIGHTHOUSE_GATEWAY_URL: &str = "https://gateway.lighthouse.storage/ipfs";
+const WEB3_STORAGE_GATEWAY_URL: &str = "https://w3s.link/ipfs";
+
 /// Lighthouse API client for Filecoin pinning
 pub struct LighthouseClient {
     client: Client,
@@ -42,63 +52,102 @@ impl LighthouseClient {
 
     /// Upload JSON data to Lighthouse and pin to Filecoin
     pub async fn upload_json(&self, data: &serde_json::Value) -> Result<String> {
-        let json_str = serde_json::to_string(data)?;
-        let json_bytes = json_str.as_bytes();
-
-        let form = reqwest::multipart::Form::new()
-            .part(
-                "file",
-                reqwest::multipart::Part::bytes(json_bytes.to_vec())
-                    .file_name("data.json")
-                    .mime_str("application/json")?,
-            );
+        let json_bytes = serde_json::to_vec(data)?;
+        let part = reqwest::multipart::Part::bytes(json_bytes)
+            .file_name("data.json")
+            .mime_str("application/json")?;
+
+        self.upload_part(part).await
+    }
+
+    /// Upload file to Lighthouse
+    pub async fn upload_file(&self, path: &Path) -> Result<String> {
+        self.upload_file_with_progress(path, Progress::none()).await
+    }
+
+    /// Stream a file to Lighthouse and check the returned CID against it
+    pub async fn upload_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        let upload = FileUpload::open(path, progress).await?;
+        let check = upload.check();
+
+        let cid = self.upload_part(upload.into_part()).await?;
+        check.verify(self.name(), cid)
+    }
+
+    /// Upload raw bytes to Lighthouse under `file_name`
+    pub async fn upload_bytes(&self, data: Vec<u8>, file_name: &str) -> Result<String> {
+        let part = reqwest::multipart::Part::bytes(data).file_name(file_name.to_string());
+        self.upload_part(part).await
+    }
+
+    /// Import the DAG in a CAR file and return its root CID
+    pub async fn upload_car(&self, path: &Path, progress: Progress) -> Result<String> {
+        #[derive(Deserialize)]
+        struct Link {
+            #[serde(rename = "/")]
+            cid: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Root {
+            #[serde(rename = "Cid")]
+            cid: Link,
+            #[serde(rename = "PinErrorMsg", default)]
+            pin_error: String,
+        }
+
+        #[derive(Deserialize)]
+        struct ImportLine {
+            #[serde(rename = "Root")]
+            root: Option<Root>,
+        }
+
+        let upload = FileUpload::open(path, progress).await?;
+        let form = reqwest::multipart::Form::new().part("file", upload.into_part());
 
         let response = self
             .client
-            .post(&format!("{}/api/v0/add", self.base_url))
+            .post(format!("{}/api/v0/dag/import", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .multipart(form)
             .send()
             .await
-            .context("Failed to upload to Lighthouse")?;
-
-        let upload_resp: UploadResponse = response
-            .json()
-            .await
-            .context("Failed to parse upload response")?;
-
-        tracing::info!(
-            "Uploaded to Lighthouse: CID={}, Size={}",
-            upload_resp.data.hash,
-            upload_resp.data.size
-        );
+            .context("Failed to upload CAR to Lighthouse")?
+            .error_for_status()
+            .context("Lighthouse rejected the CAR")?;
+
+        // The node answers like Kubo, with one JSON object per line: a `Root`
+        // for each root in the CAR, then `Stats` when asked for
+        let body = response.text().await.context("Failed to read CAR import response")?;
+        let mut roots = Vec::new();
+        for line in body.lines().filter(|line| !line.trim().is_empty()) {
+            let line: ImportLine = serde_json::from_str(line).context("Failed to parse CAR import response")?;
+            let Some(root) = line.root else { continue };
+            if !root.pin_error.is_empty() {
+                anyhow::bail!("Lighthouse could not pin {}: {}", root.cid.cid, root.pin_error);
+            }
+            roots.push(root.cid.cid);
+        }
 
-        Ok(upload_resp.data.hash)
+        let root = match <[String; 1]>::try_from(roots) {
+            Ok([root]) => root,
+            Err(roots) => anyhow::bail!("Expected one root in the CAR import response, found {}", roots.len()),
+        };
+        tracing::info!("Imported CAR into Lighthouse: root={}", root);
+        Ok(root)
     }
 
-    /// Upload file to Lighthouse
-    pub async fn upload_file(&self, path: &Path) -> Result<String> {
-        let file_bytes = tokio::fs::read(path).await?;
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("file");
-
-        let form = reqwest::multipart::Form::new()
-            .part(
-                "file",
-                reqwest::multipart::Part::bytes(file_bytes)
-                    .file_name(file_name.to_string()),
-            );
+    async fn upload_part(&self, part: reqwest::multipart::Part) -> Result<String> {
+        let form = reqwest::multipart::Form::new().part("file", part);
 
         let response = self
             .client
-            .post(&format!("{}/api/v0/add", self.base_url))
+            .post(format!("{}/api/v0/add", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .multipart(form)
             .send()
             .await
-            .context("Failed to upload file to Lighthouse")?;
+            .context("Failed to upload to Lighthouse")?;
 
         let upload_resp: UploadResponse = response
             .json()
@@ -106,7 +155,7 @@ impl LighthouseClient {
             .context("Failed to parse upload response")?;
 
         tracing::info!(
-            "Uploaded file to Lighthouse: CID={}, Size={}",
+            "Uploaded to Lighthouse: CID={}, Size={}",
             upload_resp.data.hash,
             upload_resp.data.size
         );
@@ -118,7 +167,7 @@ impl LighthouseClient {
     pub async fn pin_by_cid(&self, cid: &str) -> Result<()> {
         let response = self
             .client
-            .get(&format!("{}/api/lighthouse/pin", self.base_url))
+            .get(format!("{}/api/lighthouse/pin", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .query(&[("cid", cid)])
             .send()
@@ -134,11 +183,31 @@ impl LighthouseClient {
         }
     }
 
+    /// Check that the API key is accepted by fetching the account's data usage
+    pub async fn check_api_key(&self) -> Result<()> {
+        let response = self
+            .client
+            .get(format!("{}/api/user/user_data_usage", LIGHTHOUSE_ACCOUNT_URL))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await
+            .context("Failed to reach Lighthouse")?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            anyhow::bail!("Lighthouse rejected the API key ({})", status)
+        } else {
+            anyhow::bail!("Lighthouse returned {}", status)
+        }
+    }
+
     /// Get pin status
     pub async fn get_pin_status(&self, cid: &str) -> Result<PinStatus> {
         let response = self
             .client
-            .get(&format!("{}/api/lighthouse/pin_status", self.base_url))
+            .get(format!("{}/api/lighthouse/pin_status", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .query(&[("cid", cid)])
             .send()
@@ -170,20 +239,86 @@ impl Web3StorageClient {
         }
     }
 
+    /// Check that the token is accepted by listing a single upload
+    pub async fn check_token(&self) -> Result<()> {
+        let response = self
+            .client
+            .get(format!("{}/user/uploads", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .query(&[("size", "1")])
+            .send()
+            .await
+            .context("Failed to reach Web3.Storage")?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            anyhow::bail!("Web3.Storage rejected the token ({})", status)
+        } else {
+            anyhow::bail!("Web3.Storage returned {}", status)
+        }
+    }
+
     /// Upload file to Web3.Storage
     pub async fn upload(&self, data: &[u8], filename: &str) -> Result<String> {
-        let form = reqwest::multipart::Form::new()
-            .part(
-                "file",
-                reqwest::multipart::Part::bytes(data.to_vec())
-                    .file_name(filename.to_string()),
-            );
+        self.upload_body(data.to_vec().into(), filename).await
+    }
+
+    /// Stream a file to Web3.Storage and check the returned CID against it
+    pub async fn upload_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        let upload = FileUpload::open(path, progress).await?;
+        let check = upload.check();
+
+        let file_name = upload.file_name.clone();
+        let cid = self.upload_body(upload.into_body(), &file_name).await?;
+        check.verify(self.name(), cid)
+    }
+
+    /// Upload a CAR file as-is and return its root CID
+    pub async fn upload_car(&self, path: &Path, progress: Progress) -> Result<String> {
+        let upload = FileUpload::open(path, progress).await?;
 
         let response = self
             .client
-            .post(&format!("{}/upload", self.base_url))
+            .post(format!("{}/car", self.base_url))
             .header("Authorization", format!("Bearer {}", self.token))
-            .multipart(form)
+            .header("Content-Type", "application/vnd.ipld.car")
+            .body(upload.into_body())
+            .send()
+            .await
+            .context("Failed to upload CAR to Web3.Storage")?
+            .error_for_status()
+            .context("Web3.Storage rejected the CAR")?;
+
+        #[derive(Deserialize)]
+        struct UploadResp {
+            cid: String,
+        }
+
+        let upload_resp: UploadResp = response
+            .json()
+            .await
+            .context("Failed to parse Web3.Storage response")?;
+
+        tracing::info!("Uploaded CAR to Web3.Storage: root={}", upload_resp.cid);
+        Ok(upload_resp.cid)
+    }
+
+    /// Upload one file as the raw request body; a multipart form would be
+    /// stored as a directory around it, under a CID that is not the file's
+    async fn upload_body(&self, body: reqwest::Body, filename: &str) -> Result<String> {
+        let mut request = self
+            .client
+            .post(format!("{}/upload", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.token));
+        // The name is only a label, so one that cannot go in a header is left out
+        if let Ok(name) = reqwest::header::HeaderValue::from_str(filename) {
+            request = request.header("X-Name", name);
+        }
+
+        let response = request
+            .body(body)
             .send()
             .await
             .context("Failed to upload to Web3.Storage")?;
@@ -204,6 +339,153 @@ impl Web3StorageClient {
     }
 }
 
+/// Fetch `cid` through an HTTP gateway
+async fn fetch_from_gateway(client: &Client, gateway: &str, cid: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(format!("{}/{}", gateway, cid))
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {} from {}", cid, gateway))?
+        .error_for_status()
+        .with_context(|| format!("Gateway {} could not serve {}", gateway, cid))?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+#[async_trait]
+impl StorageBackend for LighthouseClient {
+    fn name(&self) -> &'static str {
+        "lighthouse"
+    }
+
+    async fn put(&self, data: &[u8], filename: &str) -> Result<String> {
+        let cid = self.upload_bytes(data.to_vec(), filename).await?;
+        verify_upload(self.name(), data, cid)
+    }
+
+    async fn put_json(&self, data: &serde_json::Value) -> Result<String> {
+        let cid = self.upload_json(data).await?;
+        verify_upload(self.name(), &serde_json::to_vec(data)?, cid)
+    }
+
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        self.upload_file_with_progress(path, progress).await
+    }
+
+    async fn put_car(&self, path: &Path, progress: Progress) -> Result<String> {
+        let cid = self.upload_car(path, progress).await?;
+        verify_car_root(self.name(), path, cid)
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        fetch_from_gateway(&self.client, LIGHTHOUSE_GATEWAY_URL, cid).await
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        self.pin_by_cid(cid).await
+    }
+
+    async fn unpin(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "unpin"))
+    }
+
+    async fn status(&self, cid: &str) -> Result<StorageStatus> {
+        let status = self.get_pin_status(cid).await?;
+        Ok(StorageStatus {
+            pinned: status.status.eq_ignore_ascii_case("pinned"),
+            cid: status.cid,
+            detail: status.status,
+        })
+    }
+
+    async fn delete(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "delete"))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for Web3StorageClient {
+    fn name(&self) -> &'static str {
+        "web3.storage"
+    }
+
+    async fn put(&self, data: &[u8], filename: &str) -> Result<String> {
+        let cid = self.upload(data, filename).await?;
+        verify_upload(self.name(), data, cid)
+    }
+
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        self.upload_file_with_progress(path, progress).await
+    }
+
+    async fn put_car(&self, path: &Path, progress: Progress) -> Result<String> {
+        let cid = self.upload_car(path, progress).await?;
+        verify_car_root(self.name(), path, cid)
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        fetch_from_gateway(&self.client, WEB3_STORAGE_GATEWAY_URL, cid).await
+    }
+
+    async fn pin(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "pin"))
+    }
+
+    async fn unpin(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "unpin"))
+    }
+
+    async fn status(&self, cid: &str) -> Result<StorageStatus> {
+        #[derive(Deserialize)]
+        struct State {
+            status: String,
+        }
+
+        #[derive(Deserialize)]
+        struct StatusResp {
+            #[serde(default)]
+            pins: Vec<State>,
+            #[serde(default)]
+            deals: Vec<State>,
+        }
+
+        let status: StatusResp = self
+            .client
+            .get(format!("{}/status/{}", self.base_url, cid))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .await
+            .context("Failed to get Web3.Storage status")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse Web3.Storage status")?;
+
+        let pinned = status.pins.iter().filter(|pin| pin.status == "Pinned").count();
+        let active_deals = status.deals.iter().filter(|deal| deal.status == "Active").count();
+
+        Ok(StorageStatus {
+            cid: cid.to_string(),
+            pinned: pinned > 0,
+            detail: format!("{} pins, {} active deals", pinned, active_deals),
+        })
+    }
+
+    async fn delete(&self, cid: &str) -> Result<()> {
+        self.client
+            .delete(format!("{}/user/uploads/{}", self.base_url, cid))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .await
+            .context("Failed to delete from Web3.Storage")?
+            .error_for_status()
+            .context("Web3.Storage refused the delete")?;
+
+        tracing::info!("Deleted {} from Web3.Storage", cid);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -214,9 +496,108 @@ mod tests {
         assert_eq!(client.api_key, "test_key");
     }
 
+    /// Serve one HTTP request: check it is a POST to `path` with the test
+    /// token, then answer with what `respond` makes of its body
+    async fn serve_once(path: &'static str, respond: impl FnOnce(&[u8]) -> String + Send + 'static) -> String {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                assert!(socket.read_line(&mut head).await.unwrap() > 0, "{}", head);
+            }
+            assert!(head.starts_with(&format!("POST {} ", path)), "{}", head);
+            assert!(head.contains("Bearer test_key"));
+
+            let content_length = head.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().unwrap())
+            });
+            let mut body = Vec::new();
+            if let Some(len) = content_length {
+                body.resize(len, 0);
+                socket.read_exact(&mut body).await.unwrap();
+            } else {
+                // Chunked: a hex length line, the chunk and CRLF, until an empty chunk
+                loop {
+                    let mut size = String::new();
+                    socket.read_line(&mut size).await.unwrap();
+                    let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                    let mut chunk = vec![0; size + 2];
+                    socket.read_exact(&mut chunk).await.unwrap();
+                    if size == 0 {
+                        break;
+                    }
+                    body.extend_from_slice(&chunk[..size]);
+                }
+            }
+
+            let reply = respond(&body);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            );
+            socket.get_mut().write_all(response.as_bytes()).await.unwrap();
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_lighthouse_car_import_response() {
+        let car = std::env::temp_dir().join(format!("agent-cli-lighthouse-{}.car", std::process::id()));
+        std::fs::write(&car, b"car bytes").unwrap();
+        let mut client = LighthouseClient::new("test_key".to_string());
+
+        // Kubo's dag/import streams a Root line, then a Stats line
+        client.base_url = serve_once("/api/v0/dag/import", |_| {
+            "{\"Root\":{\"Cid\":{\"/\":\"bafyroot\"},\"PinErrorMsg\":\"\"}}\n{\"Stats\":{\"BlockCount\":3,\"BlockBytesCount\":9}}\n".to_string()
+        })
+        .await;
+        assert_eq!(client.upload_car(&car, Progress::none()).await.unwrap(), "bafyroot");
+
+        client.base_url = serve_once("/api/v0/dag/import", |_| {
+            "{\"Root\":{\"Cid\":{\"/\":\"bafyroot\"},\"PinErrorMsg\":\"block not found\"}}\n".to_string()
+        })
+        .await;
+        let err = client.upload_car(&car, Progress::none()).await.unwrap_err();
+        assert!(err.to_string().contains("block not found"), "{}", err);
+
+        std::fs::remove_file(&car).unwrap();
+    }
+
     #[test]
     fn test_web3_storage_client_creation() {
         let client = Web3StorageClient::new("test_token".to_string());
         assert_eq!(client.token, "test_token");
     }
+
+    #[tokio::test]
+    async fn test_web3_storage_upload_keeps_the_file_cid() {
+        use crate::unixfs::{compute_cid, CidProfile};
+
+        // Larger than one chunk, so the CID is of a DAG rather than a block
+        let data: Vec<u8> = (0..600 * 1024).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("agent-cli-web3-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let mut client = Web3StorageClient::new("test_key".to_string());
+
+        // The service adds the body as a single file; a form around it would
+        // change the CID and fail the check
+        let add = |body: &[u8]| format!("{{\"cid\":\"{}\"}}", compute_cid(body, CidProfile::WEB3_STORAGE));
+        let expected = compute_cid(&data, CidProfile::WEB3_STORAGE).to_string();
+
+        client.base_url = serve_once("/upload", add).await;
+        assert_eq!(client.put_file_with_progress(&path, Progress::none()).await.unwrap(), expected);
+
+        client.base_url = serve_once("/upload", add).await;
+        assert_eq!(client.put(&data, "data.bin").await.unwrap(), expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -1,7 +1,12 @@
+use crate::storage::{verify_upload, StorageBackend, StorageStatus};
+use crate::upload::{FileUpload, Progress};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use ipfs_api_backend_hyper::{IpfsApi, IpfsClient as HyperIpfsClient, TryFromUri};
 use serde_json::Value;
 use std::io::Cursor;
+use std::path::Path;
+use tokio_util::compat::TokioAsyncReadCompatExt;
 
 // Add required import for map_ok
 use futures_util::TryStreamExt;
@@ -37,28 +42,37 @@ impl IpfsClient {
     }
 
     /// Upload file to IPFS
-    pub async fn add_file(&self, path: &std::path::Path) -> Result<String> {
-        let file_data = tokio::fs::read(path).await
-            .context("Failed to read file")?;
+    pub async fn add_file(&self, path: &Path) -> Result<String> {
+        self.add_file_with_progress(path, Progress::none()).await
+    }
+
+    /// Stream a file to IPFS and check the returned CID against it
+    pub async fn add_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        let upload = FileUpload::open(path, progress).await?;
+        let check = upload.check();
 
         let response = self
             .client
-            .add(std::io::Cursor::new(file_data))
+            .add_async(upload.into_reader().compat())
             .await
             .context("Failed to add file to IPFS")?;
 
-        Ok(response.hash)
+        check.verify(self.name(), response.hash)
     }
 
-    /// Get JSON data from IPFS
-    pub async fn get_json(&self, cid: &str) -> Result<Value> {
-        let data = self
-            .client
+    /// Get raw data from IPFS
+    pub async fn cat(&self, cid: &str) -> Result<Vec<u8>> {
+        self.client
             .cat(cid)
             .map_ok(|chunk| chunk.to_vec())
             .try_concat()
             .await
-            .context("Failed to get data from IPFS")?;
+            .context("Failed to get data from IPFS")
+    }
+
+    /// Get JSON data from IPFS
+    pub async fn get_json(&self, cid: &str) -> Result<Value> {
+        let data = self.cat(cid).await?;
 
         let json: Value = serde_json::from_slice(&data)
             .context("Failed to parse JSON from IPFS")?;
@@ -93,6 +107,17 @@ impl IpfsClient {
         format!("{}{}", self.gateway, cid)
     }
 
+    /// Check that the IPFS API is reachable and return the node version
+    pub async fn ping(&self) -> Result<String> {
+        let version = self
+            .client
+            .version()
+            .await
+            .context("IPFS API is not reachable")?;
+
+        Ok(version.version)
+    }
+
     /// Verify CID is accessible
     pub async fn verify(&self, cid: &str) -> Result<bool> {
         match self.client.object_stat(cid).await {
@@ -102,6 +127,58 @@ impl IpfsClient {
     }
 }
 
+#[async_trait]
+impl StorageBackend for IpfsClient {
+    fn name(&self) -> &'static str {
+        "ipfs"
+    }
+
+    async fn put(&self, data: &[u8], _filename: &str) -> Result<String> {
+        let response = self
+            .client
+            .add(Cursor::new(data.to_vec()))
+            .await
+            .context("Failed to add data to IPFS")?;
+
+        verify_upload(self.name(), data, response.hash)
+    }
+
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        self.add_file_with_progress(path, progress).await
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        self.cat(cid).await
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        IpfsClient::pin(self, cid).await
+    }
+
+    async fn unpin(&self, cid: &str) -> Result<()> {
+        IpfsClient::unpin(self, cid).await
+    }
+
+    async fn status(&self, cid: &str) -> Result<StorageStatus> {
+        // The node answers `pin ls` for an unpinned CID with an error
+        let detail = match self.client.pin_ls(Some(cid), None).await {
+            Ok(pins) => pins.keys.values().next().map(|pin| pin.typ.clone()),
+            Err(_) => None,
+        };
+
+        Ok(StorageStatus {
+            cid: cid.to_string(),
+            pinned: detail.is_some(),
+            detail: detail.unwrap_or_else(|| "not pinned".to_string()),
+        })
+    }
+
+    /// Unpin `cid`; the node frees the blocks at its next garbage collection
+    async fn delete(&self, cid: &str) -> Result<()> {
+        IpfsClient::unpin(self, cid).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,14 +1,27 @@
+pub mod car;
 pub mod config;
 pub mod contracts;
 pub mod filecoin;
 pub mod ipfs;
 pub mod mcp;
 pub mod mcp_client;
+pub mod mcp_schema;
+pub mod mcp_server;
 pub mod services;
+pub mod signer;
+pub mod storage;
+pub mod unixfs;
+pub mod upload;
 
 pub use config::Config;
 pub use filecoin::{LighthouseClient, Web3StorageClient};
 pub use ipfs::IpfsClient;
-pub use mcp::MCPHandler;
+pub use mcp::{MCPError, MCPHandler};
 pub use mcp_client::MCPClient;
-pub use services::AgentContractService;
+pub use services::{
+    AgentContractService, AgentReputationService, AgentValidationService, ContractConfig,
+    ReputationConfig, ValidationConfig,
+};
+pub use signer::SignerSource;
+pub use storage::{StorageBackend, StorageStatus};
+pub use upload::Progress;
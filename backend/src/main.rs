@@ -1,11 +1,26 @@
-use agent_backend::{Config, IpfsClient, LighthouseClient, MCPHandler, AgentContractService};
-use agent_backend::services::contract_service::ContractConfig;
-use alloy::primitives::U256;
+use agent_backend::{Config, IpfsClient, LighthouseClient, MCPClient, MCPHandler, AgentContractService, AgentReputationService};
+use agent_backend::contracts::FeedbackError;
+use agent_backend::mcp::{MCPCallResult, MCPTool};
+use agent_backend::services::contract_service::{agent_metadata, mcp_endpoint, ContractConfig};
+use agent_backend::services::reputation_service::{feedback_details, ReputationConfig};
+use agent_backend::storage::{open_backend, StorageBackend, UriReader};
+use agent_backend::car::{self, CarVersion};
+use agent_backend::unixfs::{CidBuilder, CidProfile};
+use agent_backend::upload::Progress;
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Endpoint `mcp-test` connects to when neither --endpoint nor --agent-id is given
+const DEFAULT_MCP_ENDPOINT: &str = "http://localhost:3000/mcp";
+
+/// Bytes read per chunk when `agent-cli cid` hashes a file
+const CID_READ_SIZE: usize = 256 * 1024;
+
 #[derive(Parser)]
 #[command(name = "agent-cli")]
 #[command(about = "ERC-8004 Agent CLI for Filecoin", long_about = None)]
@@ -15,6 +30,69 @@ struct Cli {
 
     #[arg(short, long, global = true)]
     config: Option<PathBuf>,
+
+    /// Configuration profile to apply on top of the base file (or set AGENT_CLI_PROFILE)
+    #[arg(short, long, global = true)]
+    profile: Option<String>,
+}
+
+/// Chunking settings for `agent-cli cid`, named after the services that use them
+#[derive(Clone, Copy, ValueEnum)]
+enum CidService {
+    /// `ipfs add` defaults, also used by Lighthouse (CIDv0)
+    Ipfs,
+    /// `ipfs add --cid-version=1`
+    IpfsV1,
+    /// The Web3.Storage upload client
+    Web3Storage,
+}
+
+impl From<CidService> for CidProfile {
+    fn from(service: CidService) -> Self {
+        match service {
+            CidService::Ipfs => CidProfile::KUBO_V0,
+            CidService::IpfsV1 => CidProfile::KUBO_V1,
+            CidService::Web3Storage => CidProfile::WEB3_STORAGE,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum CarCommand {
+    /// Pack a file or directory into a CAR
+    Pack {
+        path: PathBuf,
+
+        /// Where to write the CAR [default: <path>.car]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Build the DAG with this service's chunking settings
+        #[arg(long, value_enum, default_value = "ipfs-v1")]
+        service: CidService,
+
+        /// Write a CARv2 instead of a CARv1
+        #[arg(long)]
+        v2: bool,
+
+        /// Upload the CAR to the configured storage backend
+        #[arg(long)]
+        upload: bool,
+    },
+
+    /// Unpack a CAR into files, checking every block
+    Unpack {
+        car: PathBuf,
+
+        /// Where to write the file or directory [default: the root CID]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check that a CAR is complete and every block matches its CID
+    Verify {
+        car: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -24,9 +102,15 @@ enum Commands {
         #[arg(long)]
         network: String,
 
+        /// Hex private key, encrypted into a keystore under ~/.agent-cli/keystore;
+        /// or `$VAR` to read it from the environment at run time instead
         #[arg(long)]
         private_key: String,
 
+        /// File holding the keystore password; prompts on the terminal if unset
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+
         #[arg(long)]
         identity_contract: String,
 
@@ -38,6 +122,10 @@ enum Commands {
 
         #[arg(long)]
         lighthouse_api_key: Option<String>,
+
+        /// Overwrite an existing configuration file
+        #[arg(long)]
+        force: bool,
     },
 
     /// Register a new agent
@@ -69,7 +157,7 @@ enum Commands {
         #[arg(long)]
         score: u8,
 
-        #[arg(long)]
+        #[arg(long, value_delimiter = ',')]
         tags: Vec<String>,
 
         #[arg(long)]
@@ -80,18 +168,41 @@ enum Commands {
     Reputation {
         #[arg(long)]
         agent_id: u64,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Fetch each feedback's message from IPFS or Filecoin
+        #[arg(long)]
+        with_messages: bool,
     },
 
     /// Test MCP functionality
     McpTest {
-        #[arg(long)]
+        /// Connect to the MCP endpoint advertised in this agent's on-chain metadata
+        #[arg(long, conflicts_with_all = ["endpoint", "server"])]
         agent_id: Option<u64>,
 
+        /// MCP endpoint (Streamable HTTP or HTTP+SSE) [default: http://localhost:3000/mcp]
         #[arg(long)]
-        tool: String,
+        endpoint: Option<String>,
+
+        /// Launch a stdio server from [mcp.servers.<name>] instead of using --endpoint
+        #[arg(long, conflicts_with = "endpoint")]
+        server: Option<String>,
 
+        /// Tool to call; without it the server's tools are listed
         #[arg(long)]
+        tool: Option<String>,
+
+        /// Tool arguments as JSON, checked against the tool's input schema
+        #[arg(long, default_value = "{}")]
         args: String,
+
+        /// Print example arguments generated from the tool's input schema instead of calling it
+        #[arg(long, requires = "tool")]
+        skeleton: bool,
     },
 
     /// Check storage status
@@ -104,6 +215,54 @@ enum Commands {
     Pin {
         #[arg(long)]
         file: PathBuf,
+
+        /// Pack into a CAR and upload that instead of the raw bytes (also takes directories)
+        #[arg(long)]
+        car: bool,
+    },
+
+    /// Pack, unpack and check CAR files
+    Car {
+        #[command(subcommand)]
+        command: CarCommand,
+    },
+
+    /// Compute a file's CID locally, without uploading it
+    Cid {
+        file: PathBuf,
+
+        /// Print only the CID this service would return
+        #[arg(long, value_enum)]
+        service: Option<CidService>,
+    },
+
+    /// Serve the agent registry, reputation, validation and storage tools over MCP
+    ServeMcp {
+        /// Serve Streamable HTTP on this address instead of stdio
+        #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = "127.0.0.1:3000")]
+        http: Option<SocketAddr>,
+
+        /// Let pin_to_filecoin upload files from this directory; off by default
+        #[arg(long, value_name = "DIR")]
+        upload_dir: Option<PathBuf>,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the merged configuration with secrets redacted
+    Show,
+    /// Check the contracts, RPC endpoint and storage credentials against live services
+    Validate {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
     },
 }
 
@@ -115,29 +274,33 @@ async fn main() -> Result<()> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "agent_cli=info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        // Logs go to stderr so that stdout stays free for MCP over stdio
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
     let cli = Cli::parse();
+    let profile = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("AGENT_CLI_PROFILE").ok());
 
     match cli.command {
         Commands::Init {
             network,
             private_key,
+            password_file,
             identity_contract,
             reputation_contract,
             validation_contract,
             lighthouse_api_key,
+            force,
         } => {
-            cmd_init(
-                network,
-                private_key,
-                identity_contract,
-                reputation_contract,
-                validation_contract,
-                lighthouse_api_key,
-            )
-            .await?;
+            let contracts = agent_backend::config::ContractsConfig {
+                identity: identity_contract,
+                reputation: reputation_contract,
+                validation: validation_contract,
+            };
+            cmd_init(network, private_key, password_file, contracts, lighthouse_api_key, force).await?;
         }
         Commands::Register {
             name,
@@ -145,11 +308,11 @@ async fn main() -> Result<()> {
             mcp_endpoint,
             image,
         } => {
-            let config = load_config(cli.config)?;
+            let config = load_config(cli.config, profile.as_deref())?;
             cmd_register(&config, name, description, mcp_endpoint, image).await?;
         }
         Commands::Query { agent_id } => {
-            let config = load_config(cli.config)?;
+            let config = load_config(cli.config, profile.as_deref())?;
             cmd_query(&config, agent_id).await?;
         }
         Commands::Feedback {
@@ -158,25 +321,76 @@ async fn main() -> Result<()> {
             tags,
             message,
         } => {
-            let config = load_config(cli.config)?;
+            let config = load_config(cli.config, profile.as_deref())?;
             cmd_feedback(&config, agent_id, score, tags, message).await?;
         }
-        Commands::Reputation { agent_id } => {
-            let config = load_config(cli.config)?;
-            cmd_reputation(&config, agent_id).await?;
+        Commands::Reputation {
+            agent_id,
+            json,
+            with_messages,
+        } => {
+            let config = load_config(cli.config, profile.as_deref())?;
+            cmd_reputation(&config, agent_id, json, with_messages).await?;
         }
-        Commands::McpTest { agent_id, tool, args } => {
-            let config = load_config(cli.config)?;
-            cmd_mcp_test(&config, agent_id, tool, args).await?;
+        Commands::McpTest {
+            agent_id,
+            endpoint,
+            server,
+            tool,
+            args,
+            skeleton,
+        } => {
+            let config = load_config(cli.config, profile.as_deref())?;
+            match server {
+                Some(server) => cmd_mcp_test_server(&config, &server, tool, args, skeleton).await?,
+                None => cmd_mcp_test(&config, agent_id, endpoint, tool, args, skeleton).await?,
+            }
         }
         Commands::StorageStatus { cid } => {
-            let config = load_config(cli.config)?;
+            let config = load_config(cli.config, profile.as_deref())?;
             cmd_storage_status(&config, cid).await?;
         }
-        Commands::Pin { file } => {
-            let config = load_config(cli.config)?;
-            cmd_pin(&config, file).await?;
+        Commands::Cid { file, service } => {
+            cmd_cid(file, service).await?;
         }
+        Commands::Pin { file, car } => {
+            let config = load_config(cli.config, profile.as_deref())?;
+            if car {
+                cmd_pin_car(&config, file).await?;
+            } else {
+                cmd_pin(&config, file).await?;
+            }
+        }
+        Commands::Car { command } => match command {
+            CarCommand::Pack { path, output, service, v2, upload } => {
+                let config = if upload {
+                    Some(load_config(cli.config, profile.as_deref())?)
+                } else {
+                    None
+                };
+                cmd_car_pack(config.as_ref(), path, output, service, v2).await?;
+            }
+            CarCommand::Unpack { car, output } => {
+                cmd_car_unpack(car, output).await?;
+            }
+            CarCommand::Verify { car } => {
+                cmd_car_verify(car).await?;
+            }
+        },
+        Commands::ServeMcp { http, upload_dir } => {
+            let config = load_config(cli.config, profile.as_deref())?;
+            cmd_serve_mcp(&config, http, upload_dir).await?;
+        }
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => {
+                let config = load_config(cli.config, profile.as_deref())?;
+                cmd_config_show(&config)?;
+            }
+            ConfigCommands::Validate { json } => {
+                let config = load_config(cli.config, profile.as_deref())?;
+                cmd_config_validate(&config, json).await?;
+            }
+        },
     }
 
     Ok(())
@@ -185,21 +399,30 @@ async fn main() -> Result<()> {
 async fn cmd_init(
     network: String,
     private_key: String,
-    identity_contract: String,
-    reputation_contract: String,
-    validation_contract: String,
+    password_file: Option<PathBuf>,
+    contracts: agent_backend::config::ContractsConfig,
     lighthouse_api_key: Option<String>,
+    force: bool,
 ) -> Result<()> {
     use agent_backend::config::{
-        ensure_config_dir, Config, ContractsConfig, MCPConfig, NetworkConfig, StorageConfig,
-        WalletConfig,
+        ensure_config_dir, Config, MCPConfig, NetworkConfig, StorageConfig, WalletConfig,
     };
+    use agent_backend::contracts::fetch_chain_id;
+    use agent_backend::signer::write_keystore;
+    use agent_backend::SignerSource;
 
     println!("Initializing agent-cli configuration...");
 
     let config_dir = ensure_config_dir()?;
     let config_path = config_dir.join("config.toml");
 
+    if config_path.exists() && !force {
+        anyhow::bail!(
+            "Configuration already exists at {}. Use --force to overwrite it",
+            config_path.display()
+        );
+    }
+
     // Determine RPC URL and chain ID based on network
     let (rpc_url, chain_id) = match network.as_str() {
         "calibration" => (
@@ -210,8 +433,45 @@ async fn cmd_init(
         _ => anyhow::bail!("Unknown network: {}. Use 'calibration' or 'mainnet'", network),
     };
 
-    // Derive address from private key (simplified)
-    let address = format!("0x{}", &private_key[2..42]); // Placeholder
+    // Derive the address from the key; `$VAR` keys are resolved from the environment
+    let address = SignerSource::PrivateKey { key: private_key.clone() }
+        .load()?
+        .address()
+        .to_string();
+
+    // Make sure the RPC endpoint actually serves the network we are configuring
+    let reported_chain_id = fetch_chain_id(&rpc_url).await?;
+    if reported_chain_id != chain_id {
+        anyhow::bail!(
+            "RPC endpoint {} reports chain ID {}, but {} expects {}",
+            rpc_url,
+            reported_chain_id,
+            network,
+            chain_id
+        );
+    }
+
+    // Only `$VAR` references go into the config; literal keys are encrypted into a keystore
+    let signer = if private_key.starts_with('$') {
+        SignerSource::PrivateKey { key: private_key }
+    } else {
+        let password = match &password_file {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read password file {}", path.display()))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            None => {
+                let password = rpassword::prompt_password("New keystore password: ")?;
+                if rpassword::prompt_password("Repeat password: ")? != password {
+                    anyhow::bail!("Passwords do not match");
+                }
+                password
+            }
+        };
+        let path = write_keystore(&config_dir.join("keystore"), &private_key, &password)?;
+        println!("🔐 Private key encrypted into {}", path.display());
+        SignerSource::Keystore { path, password_file }
+    };
 
     let config = Config {
         network: NetworkConfig {
@@ -219,22 +479,22 @@ async fn cmd_init(
             rpc_url,
             chain_id,
         },
-        contracts: ContractsConfig {
-            identity: identity_contract,
-            reputation: reputation_contract,
-            validation: validation_contract,
-        },
+        contracts,
         storage: StorageConfig {
             ipfs_api: "http://127.0.0.1:5001".to_string(),
             ipfs_gateway: "https://ipfs.io/ipfs/".to_string(),
             lighthouse_api_key: lighthouse_api_key.unwrap_or_default(),
             web3_storage_token: String::new(),
+            backend: None,
+            mcp_server: None,
+            replication: None,
         },
         wallet: WalletConfig {
-            private_key,
+            signer: Some(signer),
+            private_key: None,
             address,
         },
-        mcp: MCPConfig { default_timeout: 30 },
+        mcp: MCPConfig::default(),
     };
 
     config.save(&config_path)?;
@@ -242,6 +502,7 @@ async fn cmd_init(
     println!("✅ Configuration saved to: {}", config_path.display());
     println!("   Network: {}", config.network.name);
     println!("   Chain ID: {}", config.network.chain_id);
+    println!("   Address: {}", config.wallet.address);
     println!("   Identity Contract: {}", config.contracts.identity);
     println!("   Reputation Contract: {}", config.contracts.reputation);
     println!("   Validation Contract: {}", config.contracts.validation);
@@ -261,60 +522,44 @@ async fn cmd_register(
     println!("  Description: {}", description);
     println!("  MCP Endpoint: {}", mcp_endpoint);
 
-    // Initialize IPFS client
-    let ipfs = IpfsClient::new(&config.storage.ipfs_api, &config.storage.ipfs_gateway)?;
+    let storage = open_backend(config).await?;
 
     // Prepare agent metadata
-    let mut metadata = serde_json::json!({
-        "name": name,
-        "description": description,
-        "endpoints": [
-            {
-                "type": "mcp",
-                "uri": mcp_endpoint
-            }
-        ],
-        "supportedTrust": ["reputation", "validation"]
-    });
+    let mut metadata = agent_metadata(&name, &description, &mcp_endpoint);
 
     // Upload image if provided
     if let Some(image_path) = image {
         println!("  Uploading image...");
-        let image_cid = ipfs.add_file(&image_path).await?;
-        println!("  ✅ Image uploaded: ipfs://{}", image_cid);
-        metadata["image"] = serde_json::json!(format!("ipfs://{}", image_cid));
+        let image_uri = storage.uri(&storage.put_file(&image_path).await?);
+        println!("  ✅ Image uploaded: {}", image_uri);
+        metadata["image"] = serde_json::json!(image_uri);
     }
 
-    // Upload metadata to IPFS
-    println!("  Uploading metadata to IPFS...");
-    let metadata_cid = ipfs.add_json(&metadata).await?;
-    println!("  ✅ Metadata uploaded: ipfs://{}", metadata_cid);
-
-    // Pin to Filecoin via Lighthouse
-    if !config.storage.lighthouse_api_key.is_empty() {
-        println!("  Pinning to Filecoin...");
-        let lighthouse = LighthouseClient::new(config.storage.lighthouse_api_key.clone());
-        lighthouse.pin_by_cid(&metadata_cid).await?;
-        println!("  ✅ Pinned to Filecoin");
-    }
+    println!("  Uploading metadata to {}...", storage.name());
+    let metadata_cid = storage.put_json(&metadata).await?;
+    let metadata_uri = storage.uri(&metadata_cid);
+    println!("  ✅ Metadata uploaded: {}", metadata_uri);
+    print_replication(storage.as_ref(), &metadata_cid);
 
     // Register with contract
     println!("  Registering with smart contract...");
     let contract_config = ContractConfig {
         rpc_url: config.network.rpc_url.clone(),
-        private_key: config.wallet.private_key.clone(),
+        signer: config.wallet.signer_source()?,
         identity_address: config.contracts.identity.clone(),
     };
     let contract_client = AgentContractService::new(contract_config).await?;
 
     let agent_id = contract_client
-        .register_agent(format!("ipfs://{}", metadata_cid))
+        .register_agent(metadata_uri.clone())
         .await?;
 
     println!("\n🎉 Agent registered successfully!");
     println!("   Agent ID: {}", agent_id);
     println!("   Metadata CID: {}", metadata_cid);
-    println!("   View at: {}{}", config.storage.ipfs_gateway, metadata_cid);
+    if metadata_uri.starts_with("ipfs://") {
+        println!("   View at: {}{}", config.storage.ipfs_gateway, metadata_cid);
+    }
 
     Ok(())
 }
@@ -322,11 +567,7 @@ async fn cmd_register(
 async fn cmd_query(config: &Config, agent_id: u64) -> Result<()> {
     println!("Querying agent #{}...", agent_id);
 
-    let contract_client = AgentContractService::new(ContractConfig {
-        rpc_url: config.network.rpc_url.clone(),
-        private_key: config.wallet.private_key.clone(),
-        identity_address: config.contracts.identity.clone(),
-    }).await?;
+    let contract_client = AgentContractService::read_only(&config.network.rpc_url, &config.contracts.identity)?;
 
     let agent = contract_client
         .get_agent_info(agent_id.to_string())
@@ -338,15 +579,13 @@ async fn cmd_query(config: &Config, agent_id: u64) -> Result<()> {
     println!("   Registered At: {}", agent.registered_at);
     println!("   Active: {}", agent.is_active);
 
-    // Fetch metadata from IPFS
-    if agent.metadata_uri.starts_with("ipfs://") {
-        let cid = agent.metadata_uri.strip_prefix("ipfs://").unwrap();
-        let ipfs = IpfsClient::new(&config.storage.ipfs_api, &config.storage.ipfs_gateway)?;
-
-        if let Ok(metadata) = ipfs.get_json(cid).await {
+    // Fetch metadata from IPFS or Filecoin
+    match UriReader::new(config)?.get_json(&agent.metadata_uri).await {
+        Ok(metadata) => {
             println!("\n📄 Metadata:");
             println!("{}", serde_json::to_string_pretty(&metadata)?);
         }
+        Err(e) => tracing::warn!("Failed to fetch metadata {}: {}", agent.metadata_uri, e),
     }
 
     Ok(())
@@ -369,28 +608,37 @@ async fn cmd_feedback(
 
     let mut file_uri = String::new();
 
-    // If message provided, upload to IPFS
+    // If message provided, upload it to the configured storage
     if let Some(msg) = message {
-        let ipfs = IpfsClient::new(&config.storage.ipfs_api, &config.storage.ipfs_gateway)?;
-        let feedback_data = serde_json::json!({
-            "message": msg,
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-        });
-
-        let cid = ipfs.add_json(&feedback_data).await?;
-        file_uri = format!("ipfs://{}", cid);
+        let storage = open_backend(config).await?;
+        file_uri = storage.uri(&storage.put_json(&feedback_details(&msg)).await?);
         println!("  ✅ Feedback details uploaded: {}", file_uri);
     }
 
-    let contract_client = AgentContractService::new(ContractConfig {
+    let reputation = AgentReputationService::new(ReputationConfig {
         rpc_url: config.network.rpc_url.clone(),
-        private_key: config.wallet.private_key.clone(),
-        identity_address: config.contracts.identity.clone(),
+        signer: config.wallet.signer_source()?,
+        reputation_address: config.contracts.reputation.clone(),
     }).await?;
 
-    let feedback_id = contract_client
-        .register_agent(file_uri)
-        .await?;
+    let feedback_id = match reputation
+        .give_feedback(agent_id.to_string(), score, tags, file_uri)
+        .await
+    {
+        Ok(feedback_id) => feedback_id,
+        Err(e) => match e.downcast_ref::<FeedbackError>() {
+            Some(FeedbackError::SelfFeedback(_)) => anyhow::bail!(
+                "You own agent #{} and cannot leave feedback for it. Use a different wallet.",
+                agent_id
+            ),
+            Some(FeedbackError::Cooldown(_)) => anyhow::bail!(
+                "You already gave feedback to agent #{} recently. Wait for the feedback cooldown to expire and try again.",
+                agent_id
+            ),
+            Some(feedback_error) => anyhow::bail!("{}", feedback_error),
+            None => return Err(e),
+        },
+    };
 
     println!("\n✅ Feedback submitted!");
     println!("   Feedback ID: {}", feedback_id);
@@ -398,43 +646,200 @@ async fn cmd_feedback(
     Ok(())
 }
 
-async fn cmd_reputation(config: &Config, agent_id: u64) -> Result<()> {
-    println!("Fetching reputation for agent #{}...", agent_id);
+async fn cmd_reputation(
+    config: &Config,
+    agent_id: u64,
+    json: bool,
+    with_messages: bool,
+) -> Result<()> {
+    if !json {
+        println!("Fetching reputation for agent #{}...", agent_id);
+    }
 
-    let contract_client = AgentContractService::new(ContractConfig {
-        rpc_url: config.network.rpc_url.clone(),
-        private_key: config.wallet.private_key.clone(),
-        identity_address: config.contracts.identity.clone(),
-    }).await?;
+    let reputation = AgentReputationService::read_only(&config.network.rpc_url, &config.contracts.reputation)?;
 
-    let total_agents = contract_client
-        .get_total_agents()
+    let mut report = reputation
+        .get_reputation_report(agent_id.to_string())
         .await?;
 
-    println!("\n⭐ Agent Statistics:");
-    println!("   Total Registered Agents: {}", total_agents);
+    // Fetch feedback messages from IPFS or Filecoin
+    if with_messages {
+        let reader = UriReader::new(config)?;
+        for feedback in report.feedbacks.iter_mut() {
+            if feedback.file_uri.is_empty() {
+                continue;
+            }
+
+            match reader.get_json(&feedback.file_uri).await {
+                Ok(data) => {
+                    feedback.message = data["message"].as_str().map(|m| m.to_string());
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to fetch feedback message {}: {}", feedback.file_uri, e);
+                }
+            }
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("\n⭐ Reputation:");
+    println!("   Average Score: {}/100", report.avg_score);
+    println!("   Total Feedback: {}", report.total_feedbacks);
+    println!("   Active Feedback: {}", report.active_feedbacks);
+
+    if report.active_feedbacks == 0 {
+        println!("\n   No active feedback yet.");
+        return Ok(());
+    }
+
+    println!("\n📊 Score Distribution:");
+    let max_count = report.histogram.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    for bucket in report.histogram.iter().rev() {
+        let bar = "█".repeat(bucket.count * 30 / max_count);
+        println!("   {:>3}-{:<3} | {} {}", bucket.min, bucket.max, bar, bucket.count);
+    }
+
+    if !report.tags.is_empty() {
+        println!("\n🏷️  Tags:");
+        let mut tags: Vec<_> = report.tags.iter().collect();
+        tags.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (tag, count) in tags {
+            println!("   {}: {}", tag, count);
+        }
+    }
+
+    println!("\n📈 History:");
+    for point in &report.time_series {
+        println!(
+            "   {}  score {:>3}  avg {:>5.1}",
+            format_timestamp(point.timestamp),
+            point.score,
+            point.running_average
+        );
+    }
+
+    if with_messages {
+        println!("\n💬 Messages:");
+        for feedback in report.feedbacks.iter().filter(|f| !f.revoked) {
+            if let Some(message) = &feedback.message {
+                println!("   #{} ({}/100): {}", feedback.feedback_id, feedback.score, message);
+            }
+            if !feedback.response.is_empty() {
+                println!("      ↳ Response: {}", feedback.response);
+            }
+        }
+    }
 
     Ok(())
 }
 
+fn format_timestamp(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
 async fn cmd_mcp_test(
     config: &Config,
-    _agent_id: Option<u64>,
-    tool: String,
+    agent_id: Option<u64>,
+    endpoint: Option<String>,
+    tool: Option<String>,
     args: String,
+    skeleton: bool,
 ) -> Result<()> {
+    let endpoint = match (agent_id, endpoint) {
+        (Some(agent_id), _) => resolve_mcp_endpoint(config, agent_id).await?,
+        (None, Some(endpoint)) => endpoint,
+        (None, None) => DEFAULT_MCP_ENDPOINT.to_string(),
+    };
+
+    let mcp = MCPHandler::new(endpoint.clone(), config.mcp.default_timeout);
+
+    let server = mcp.initialize().await?;
+    println!(
+        "  Server: {} {} at {}",
+        server.server_info.name, server.server_info.version, endpoint
+    );
+
+    let tools = mcp.list_tools().await?;
+    let Some(tool) = tool else {
+        print_tools(&tools);
+        return Ok(());
+    };
+
     println!("Testing MCP tool: {}", tool);
+    let Some(args_value) = prepare_tool_call(&tools, &tool, &args, skeleton)? else {
+        return Ok(());
+    };
+
+    let result = mcp.call_tool(&tool, args_value).await?;
+    print_call_result(result)
+}
 
-    let mcp = MCPHandler::new("mcp://localhost:3000".to_string(), config.mcp.default_timeout);
+/// Look up the MCP endpoint an agent advertises in its registration metadata
+async fn resolve_mcp_endpoint(config: &Config, agent_id: u64) -> Result<String> {
+    println!("Resolving MCP endpoint for agent #{}...", agent_id);
+
+    let contract_client = AgentContractService::read_only(&config.network.rpc_url, &config.contracts.identity)?;
+
+    let metadata_uri = contract_client.get_agent_uri(agent_id.to_string()).await?;
+    println!("  Metadata URI: {}", metadata_uri);
+
+    let metadata = UriReader::new(config)?
+        .get_json(&metadata_uri)
+        .await
+        .with_context(|| format!("Failed to fetch metadata for agent #{}", agent_id))?;
+
+    mcp_endpoint(&metadata)
+        .map(str::to_string)
+        .with_context(|| format!("Agent #{} does not advertise an MCP endpoint", agent_id))
+}
+
+fn print_tools(tools: &[MCPTool]) {
+    println!("\n🔧 Available tools ({}):", tools.len());
+    for tool in tools {
+        println!("   {} - {}", tool.name, tool.description);
+    }
+}
+
+/// Find `name` among the server's tools and check `args` against its input schema
+///
+/// With `skeleton` set, print example arguments instead and return `None`.
+fn prepare_tool_call(
+    tools: &[MCPTool],
+    name: &str,
+    args: &str,
+    skeleton: bool,
+) -> Result<Option<serde_json::Value>> {
+    let Some(tool) = tools.iter().find(|t| t.name == name) else {
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        anyhow::bail!("Server has no tool named '{}'. Available: {}", name, names.join(", "));
+    };
+
+    let example = serde_json::to_string_pretty(&tool.argument_skeleton())?;
+    if skeleton {
+        println!("\nExample arguments for {}:\n{}", name, example);
+        return Ok(None);
+    }
 
     // Parse args JSON
-    let args_value: serde_json::Value = serde_json::from_str(&args)
+    let args_value: serde_json::Value = serde_json::from_str(args)
         .context("Failed to parse args as JSON")?;
+    if let Err(e) = tool.validate_arguments(&args_value) {
+        anyhow::bail!("{}\n\nExample arguments:\n{}", e, example);
+    }
 
     println!("  Arguments: {}", args_value);
+    Ok(Some(args_value))
+}
 
-    let result = mcp.call_tool(&tool, args_value).await?;
-
+fn print_call_result(result: MCPCallResult) -> Result<()> {
     if result.success {
         println!("\n✅ Tool executed successfully!");
         println!("   Result: {}", serde_json::to_string_pretty(&result.result)?);
@@ -448,6 +853,37 @@ async fn cmd_mcp_test(
     Ok(())
 }
 
+async fn cmd_mcp_test_server(
+    config: &Config,
+    name: &str,
+    tool: Option<String>,
+    args: String,
+    skeleton: bool,
+) -> Result<()> {
+    let spec = config.mcp.server(name)?;
+    let client = MCPClient::spawn(name, spec).await?;
+    if let Some(server) = client.server_info() {
+        println!(
+            "  Server: {} {} ({})",
+            server.server_info.name, server.server_info.version, name
+        );
+    }
+
+    let tools = client.list_tools().await?;
+    match tool {
+        Some(tool) => {
+            println!("Testing MCP tool: {}", tool);
+            if let Some(args_value) = prepare_tool_call(&tools, &tool, &args, skeleton)? {
+                let result = client.call_tool(&tool, args_value).await?;
+                print_call_result(result.into())?;
+            }
+        }
+        None => print_tools(&tools),
+    }
+
+    client.shutdown().await
+}
+
 async fn cmd_storage_status(config: &Config, cid: String) -> Result<()> {
     println!("Checking storage status for CID: {}", cid);
 
@@ -472,31 +908,302 @@ async fn cmd_storage_status(config: &Config, cid: String) -> Result<()> {
 }
 
 async fn cmd_pin(config: &Config, file: PathBuf) -> Result<()> {
-    println!("Pinning file to Filecoin: {}", file.display());
+    let storage = open_backend(config).await?;
+    println!("Storing file with {}: {}", storage.name(), file.display());
 
-    if !config.storage.lighthouse_api_key.is_empty() {
-        let lighthouse = LighthouseClient::new(config.storage.lighthouse_api_key.clone());
-        let cid = lighthouse.upload_file(&file).await?;
+    let cid = storage.put_file_with_progress(&file, progress_bar()).await?;
+    eprintln!();
+    let uri = storage.uri(&cid);
 
-        println!("\n✅ File pinned successfully!");
-        println!("   CID: {}", cid);
+    println!("\n✅ File pinned successfully!");
+    println!("   CID: {}", cid);
+    if uri.starts_with("ipfs://") {
         println!("   Gateway URL: {}{}", config.storage.ipfs_gateway, cid);
+    }
+    print_replication(storage.as_ref(), &cid);
+
+    Ok(())
+}
+
+async fn cmd_cid(file: PathBuf, service: Option<CidService>) -> Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let profiles = match service {
+        Some(service) => vec![service.into()],
+        None => CidProfile::ALL.to_vec(),
+    };
+    let mut builders: Vec<CidBuilder> = profiles.into_iter().map(CidBuilder::new).collect();
+
+    // Hash the file as it is read rather than loading it whole
+    let mut reader = tokio::fs::File::open(&file)
+        .await
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+    let mut buf = vec![0; CID_READ_SIZE];
+    let mut len = 0u64;
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .await
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        if n == 0 {
+            break;
+        }
+        for builder in &mut builders {
+            builder.update(&buf[..n]);
+        }
+        len += n as u64;
+    }
+    let cids: Vec<_> = builders.into_iter().map(CidBuilder::finish).collect();
+
+    match service {
+        Some(_) => println!("{}", cids[0]),
+        None => {
+            println!("File: {} ({} bytes)", file.display(), len);
+            println!("   CIDv0 (IPFS, Lighthouse): {}", cids[0]);
+            println!("   CIDv1 (IPFS --cid-version=1): {}", cids[1]);
+            println!("   CIDv1 (Web3.Storage): {}", cids[2]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Pack `file` into a temporary CAR and upload that
+async fn cmd_pin_car(config: &Config, file: PathBuf) -> Result<()> {
+    // Created fresh with a random name, and removed when dropped on any path
+    let car = tempfile::Builder::new()
+        .prefix("agent-cli-pin-")
+        .suffix(".car")
+        .tempfile()
+        .context("Failed to create a temporary CAR")?;
+    let (input, output) = (file.clone(), car.path().to_path_buf());
+    let packed = tokio::task::spawn_blocking(move || {
+        car::pack(&input, &output, CidProfile::KUBO_V1, CarVersion::V1)
+    })
+    .await??;
+    println!("Packed {} into a CAR: root {}, {} blocks", file.display(), packed.root, packed.blocks);
+
+    upload_car(config, car.path()).await
+}
+
+async fn cmd_car_pack(
+    config: Option<&Config>,
+    path: PathBuf,
+    output: Option<PathBuf>,
+    service: CidService,
+    v2: bool,
+) -> Result<()> {
+    // `.` and `..` have no file name of their own; the resolved path does
+    let path = tokio::fs::canonicalize(&path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let mut name = path
+                .file_name()
+                .with_context(|| format!("Cannot name a CAR after {}; pass --output", path.display()))?
+                .to_os_string();
+            name.push(".car");
+            path.with_file_name(name)
+        }
+    };
+    let version = if v2 { CarVersion::V2 } else { CarVersion::V1 };
+
+    let (input, car_path) = (path.clone(), output.clone());
+    let packed = tokio::task::spawn_blocking(move || car::pack(&input, &car_path, service.into(), version)).await??;
+
+    println!("✅ Packed {} into {}", path.display(), output.display());
+    println!("   Root: {}", packed.root);
+    println!("   Blocks: {}", packed.blocks);
+
+    if let Some(config) = config {
+        upload_car(config, &output).await?;
+    }
+    Ok(())
+}
+
+async fn cmd_car_unpack(car_path: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let path = car_path.clone();
+            PathBuf::from(tokio::task::spawn_blocking(move || car::root(&path)).await??.to_string())
+        }
+    };
+
+    let (input, out) = (car_path.clone(), output.clone());
+    let unpacked = tokio::task::spawn_blocking(move || car::unpack(&input, &out)).await??;
+
+    println!("✅ Unpacked {} into {}", car_path.display(), output.display());
+    println!("   Root: {}", unpacked.root);
+    println!("   Blocks: {}", unpacked.blocks);
+    Ok(())
+}
+
+async fn cmd_car_verify(car_path: PathBuf) -> Result<()> {
+    let input = car_path.clone();
+    let verified = tokio::task::spawn_blocking(move || car::verify(&input)).await??;
+
+    println!("✅ {} is complete and every block matches its CID", car_path.display());
+    println!("   Root: {}", verified.root);
+    println!("   Blocks: {}", verified.blocks);
+    Ok(())
+}
+
+async fn upload_car(config: &Config, car_path: &std::path::Path) -> Result<()> {
+    let storage = open_backend(config).await?;
+    println!("Uploading CAR with {}: {}", storage.name(), car_path.display());
+
+    let cid = storage.put_car(car_path, progress_bar()).await?;
+    eprintln!();
+
+    println!("\n✅ CAR uploaded successfully!");
+    println!("   Root CID: {}", cid);
+    if storage.uri(&cid).starts_with("ipfs://") {
+        println!("   Gateway URL: {}{}", config.storage.ipfs_gateway, cid);
+    }
+    print_replication(storage.as_ref(), &cid);
+
+    Ok(())
+}
+
+/// Upload progress drawn on stderr as a bar with throughput
+fn progress_bar() -> Progress {
+    const WIDTH: usize = 30;
+    let start = Instant::now();
+    let last_draw = std::sync::Mutex::new(None::<Instant>);
+
+    Progress::new(move |sent, total| {
+        // Redraw at most ten times a second, but always draw the end
+        let mut last_draw = last_draw.lock().unwrap();
+        if sent < total && last_draw.is_some_and(|t| t.elapsed() < Duration::from_millis(100)) {
+            return;
+        }
+        *last_draw = Some(Instant::now());
+
+        // A file that grew while being read can send more than its length
+        let fraction = if total == 0 { 1.0 } else { (sent as f64 / total as f64).clamp(0.0, 1.0) };
+        let filled = (fraction * WIDTH as f64) as usize;
+        let rate = sent as f64 / start.elapsed().as_secs_f64().max(0.001);
+        eprint!(
+            "\r[{}{}] {:>3.0}% {} / {} {}/s   ",
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            fraction * 100.0,
+            format_bytes(sent as f64),
+            format_bytes(total as f64),
+            format_bytes(rate)
+        );
+    })
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[unit])
     } else {
-        anyhow::bail!("Lighthouse API key not configured. Run 'agent-cli init' first.");
+        format!("{:.1} {}", value, UNITS[unit])
     }
+}
+
+/// List which backends hold `cid` when uploads are replicated
+fn print_replication(storage: &dyn StorageBackend, cid: &str) {
+    let Some(report) = storage.replication(cid) else {
+        return;
+    };
+
+    println!(
+        "   Replicas: {} of {} (quorum {})",
+        report.holders().len(),
+        report.replicas.len(),
+        report.quorum
+    );
+    for holder in report.holders() {
+        println!("     ✅ {}", holder);
+    }
+    for problem in report.problems() {
+        println!("     ❌ {}", problem);
+    }
+}
+
+async fn cmd_serve_mcp(config: &Config, http: Option<SocketAddr>, upload_dir: Option<PathBuf>) -> Result<()> {
+    use agent_backend::mcp_server::{serve_http, serve_stdio, AgentMCPServer, HTTP_PATH};
+
+    let mut server = AgentMCPServer::new(config).await?;
+    if let Some(dir) = upload_dir {
+        server = server.with_upload_dir(&dir)?;
+    }
+    let server = Arc::new(server);
+
+    match http {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to bind {}", addr))?;
+            eprintln!("Serving MCP on http://{}{}", listener.local_addr()?, HTTP_PATH);
+            serve_http(server, listener).await
+        }
+        None => serve_stdio(server).await,
+    }
+}
+
+fn cmd_config_show(config: &Config) -> Result<()> {
+    print!("{}", toml::to_string_pretty(&config.redacted())?);
+    Ok(())
+}
 
+async fn cmd_config_validate(config: &Config, json: bool) -> Result<()> {
+    use agent_backend::services::{check_config, CheckStatus};
+
+    if !json {
+        println!("Validating configuration for {}...\n", config.network.name);
+    }
+
+    let report = check_config(config).await;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for check in &report.checks {
+            let mark = match check.status {
+                CheckStatus::Pass => "✅",
+                CheckStatus::Fail => "❌",
+                CheckStatus::Skip => "⏭️ ",
+            };
+            println!("  {} {:<40} {}", mark, check.name, check.detail);
+        }
+        println!();
+    }
+
+    if !report.passed() {
+        anyhow::bail!("{} configuration check(s) failed", report.failures());
+    }
+
+    if !json {
+        println!("✅ Configuration is valid");
+    }
     Ok(())
 }
 
-fn load_config(path: Option<PathBuf>) -> Result<Config> {
+fn load_config(path: Option<PathBuf>, profile: Option<&str>) -> Result<Config> {
     let config_path = path.unwrap_or_else(Config::default_path);
 
+    // Without a file the whole configuration may still come from AGENT_CLI_* variables
     if !config_path.exists() {
-        anyhow::bail!(
-            "Configuration not found at {}. Run 'agent-cli init' first.",
-            config_path.display()
-        );
+        return Config::load_layered(&config_path, profile).with_context(|| {
+            format!(
+                "Configuration not found at {}. Run 'agent-cli init' first.",
+                config_path.display()
+            )
+        });
     }
 
-    Config::load(&config_path)
+    Config::load_layered(&config_path, profile)
 }
@@ -1,14 +1,49 @@
-use anyhow::Result;
+use crate::mcp_schema::FieldError;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+
+/// MCP protocol revision requested during `initialize`
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Protocol revisions this crate can talk to, newest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+const PROTOCOL_VERSION_HEADER: &str = "MCP-Protocol-Version";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MCPTool {
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(alias = "input_schema")]
     pub input_schema: Value,
 }
 
+impl MCPTool {
+    /// Fail with `MCPError::InvalidArguments` unless `arguments` match the input schema
+    pub fn validate_arguments(&self, arguments: &Value) -> Result<(), MCPError> {
+        let errors = crate::mcp_schema::validate(&self.input_schema, arguments);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(MCPError::InvalidArguments { tool: self.name.clone(), errors })
+        }
+    }
+
+    /// Example arguments generated from the input schema
+    pub fn argument_skeleton(&self) -> Value {
+        crate::mcp_schema::argument_skeleton(&self.input_schema)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPCallResult {
     pub success: bool,
@@ -16,11 +51,384 @@ pub struct MCPCallResult {
     pub error: Option<String>,
 }
 
-/// MCP Protocol Handler
-/// This is a simplified implementation for MVP
+/// A block of tool, prompt or embedded resource content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MCPContent {
+    Text {
+        text: String,
+    },
+    /// Base64-encoded image
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    /// Base64-encoded audio
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    /// Resource contents included inline
+    Resource { resource: MCPResourceContents },
+    /// A pointer to a resource the caller can fetch with `resources/read`
+    ResourceLink(MCPResource),
+    /// A content type from a newer protocol revision
+    #[serde(other)]
+    Unknown,
+}
+
+impl MCPContent {
+    /// The text of a text block
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            MCPContent::Text { text } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Raw bytes of text, image, audio and embedded resource blocks
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            MCPContent::Text { text } => Ok(text.as_bytes().to_vec()),
+            MCPContent::Image { data, .. } | MCPContent::Audio { data, .. } => decode_base64(data),
+            MCPContent::Resource { resource } => resource.bytes(),
+            MCPContent::ResourceLink(link) => {
+                anyhow::bail!("{} is a link; read it with resources/read", link.uri)
+            }
+            MCPContent::Unknown => anyhow::bail!("Unsupported content type"),
+        }
+    }
+}
+
+/// Result of `tools/call`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPToolResult {
+    #[serde(default)]
+    pub content: Vec<MCPContent>,
+    /// The tool ran but failed; `content` describes the failure
+    #[serde(default)]
+    pub is_error: bool,
+    /// JSON matching the tool's output schema, if it declares one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+}
+
+impl MCPToolResult {
+    /// All text blocks, one per line
+    pub fn text(&self) -> String {
+        let texts: Vec<&str> = self.content.iter().filter_map(MCPContent::as_text).collect();
+        texts.join("\n")
+    }
+
+    /// What went wrong, for results with `is_error` set
+    pub fn error_message(&self) -> String {
+        let text = self.text();
+        if text.is_empty() {
+            "Tool reported an error".to_string()
+        } else {
+            text
+        }
+    }
+
+    /// Deserialize the output from `structuredContent`, or else from the
+    /// first text block that holds JSON
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        if let Some(structured) = &self.structured_content {
+            return serde_json::from_value(structured.clone())
+                .context("structuredContent does not match the expected type");
+        }
+
+        let mut last_error = None;
+        for text in self.content.iter().filter_map(MCPContent::as_text) {
+            match serde_json::from_str(text) {
+                Ok(value) => return Ok(value),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(e).context("No text content matches the expected type"),
+            None => anyhow::bail!("Tool result has no structured or text content"),
+        }
+    }
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    use base64::Engine;
+
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .context("Invalid base64 content")
+}
+
+/// A resource listed by `resources/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Size in bytes, when the server knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// One item of a `resources/read` result; exactly one of `text` and `blob` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Base64-encoded binary contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+impl MCPResourceContents {
+    /// Raw bytes of the contents, decoding `blob` if necessary
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        match (&self.text, &self.blob) {
+            (Some(text), _) => Ok(text.as_bytes().to_vec()),
+            (None, Some(blob)) => decode_base64(blob)
+                .with_context(|| format!("Invalid blob for resource {}", self.uri)),
+            (None, None) => anyhow::bail!("Resource {} has neither text nor blob", self.uri),
+        }
+    }
+}
+
+/// A prompt template listed by `prompts/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPPrompt {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<MCPPromptArgument>,
+}
+
+/// An argument a prompt template accepts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPPromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// One message of a rendered prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPPromptMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    pub content: MCPContent,
+}
+
+impl MCPPromptMessage {
+    /// The message text, if it is a text message
+    pub fn text(&self) -> Option<&str> {
+        self.content.as_text()
+    }
+}
+
+/// Result of `prompts/get`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPPromptResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<MCPPromptMessage>,
+}
+
+/// Name and version of an MCP client or server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Implementation {
+    pub name: String,
+    pub version: String,
+}
+
+/// `tools` server capability
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolsCapability {
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+/// `resources` server capability
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesCapability {
+    #[serde(default)]
+    pub subscribe: bool,
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+/// `prompts` server capability
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptsCapability {
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+/// Features the server offers; a missing field means the feature is absent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<ToolsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourcesCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<PromptsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Value>,
+}
+
+/// Errors for servers that cannot be used by this client
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum MCPError {
+    #[error("MCP server {server} speaks protocol {version}, but only {supported} are supported", supported = SUPPORTED_PROTOCOL_VERSIONS.join(", "))]
+    UnsupportedProtocolVersion { server: String, version: String },
+    #[error("MCP server {server} does not offer the {capability} capability")]
+    MissingCapability { server: String, capability: &'static str },
+    /// A stdio server process went away; `code` is `None` when it was killed by a signal
+    #[error("MCP server {server} exited with {}{}", describe_exit(*code), describe_stderr(last_stderr))]
+    ServerExited { server: String, code: Option<i32>, last_stderr: String },
+    #[error("Invalid arguments for tool {tool}:{}", describe_field_errors(errors))]
+    InvalidArguments { tool: String, errors: Vec<FieldError> },
+}
+
+fn describe_field_errors(errors: &[FieldError]) -> String {
+    errors.iter().map(|e| format!("\n  - {}", e)).collect()
+}
+
+fn describe_exit(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("code {}", code),
+        None => "no exit code".to_string(),
+    }
+}
+
+fn describe_stderr(last_stderr: &str) -> String {
+    if last_stderr.is_empty() {
+        String::new()
+    } else {
+        format!("; last stderr output:\n{}", last_stderr)
+    }
+}
+
+/// Result of the `initialize` handshake
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: ServerCapabilities,
+    pub server_info: Implementation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+}
+
+impl InitializeResult {
+    /// Reject servers that answered with a protocol revision we cannot speak
+    pub fn check_compatible(&self) -> Result<(), MCPError> {
+        if SUPPORTED_PROTOCOL_VERSIONS.contains(&self.protocol_version.as_str()) {
+            Ok(())
+        } else {
+            Err(MCPError::UnsupportedProtocolVersion {
+                server: self.server_info.name.clone(),
+                version: self.protocol_version.clone(),
+            })
+        }
+    }
+
+    /// Fail with `MissingCapability` unless the server offers `capability`
+    pub fn require(&self, capability: &'static str) -> Result<(), MCPError> {
+        let offered = match capability {
+            "tools" => self.capabilities.tools.is_some(),
+            "resources" => self.capabilities.resources.is_some(),
+            "resources.subscribe" => self.capabilities.resources.as_ref().is_some_and(|r| r.subscribe),
+            "prompts" => self.capabilities.prompts.is_some(),
+            "logging" => self.capabilities.logging.is_some(),
+            _ => false,
+        };
+
+        if offered {
+            Ok(())
+        } else {
+            Err(MCPError::MissingCapability {
+                server: self.server_info.name.clone(),
+                capability,
+            })
+        }
+    }
+}
+
+/// Client `initialize` parameters
+pub(crate) fn initialize_params() -> Value {
+    json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": {},
+        "clientInfo": {
+            "name": "agent-cli",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// Responses waiting for the SSE reader task, keyed by JSON-RPC id
+type Pending = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// How messages reach the server once the session is established
+#[derive(Clone)]
+enum Transport {
+    /// Streamable HTTP: every message is POSTed to the endpoint and the
+    /// response comes back as JSON or as an SSE stream on the same request
+    Streamable { session_id: Option<String> },
+    /// HTTP+SSE (2024-11-05): messages are POSTed to the URL announced by the
+    /// server and responses arrive on the long-lived GET stream
+    Sse { post_url: String, pending: Pending },
+}
+
+struct Session {
+    transport: Transport,
+    info: InitializeResult,
+    reader: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.abort();
+        }
+    }
+}
+
+/// MCP client for agents that advertise an HTTP endpoint.
+///
+/// Connects lazily on first use, speaking Streamable HTTP and falling back to
+/// the older HTTP+SSE transport when the server rejects the initial POST.
 pub struct MCPHandler {
     server_uri: String,
     timeout: std::time::Duration,
+    http: reqwest::Client,
+    next_id: AtomicU64,
+    session: Mutex<Option<Session>>,
+    /// Tools from the last `tools/list`, used to validate arguments
+    tools: std::sync::RwLock<HashMap<String, MCPTool>>,
 }
 
 impl MCPHandler {
@@ -28,98 +436,87 @@ impl MCPHandler {
         Self {
             server_uri,
             timeout: std::time::Duration::from_secs(timeout_secs),
+            http: reqwest::Client::new(),
+            next_id: AtomicU64::new(1),
+            session: Mutex::new(None),
+            tools: std::sync::RwLock::default(),
+        }
+    }
+
+    /// Perform the `initialize` handshake if it has not happened yet
+    pub async fn initialize(&self) -> Result<InitializeResult> {
+        let mut session = self.session.lock().await;
+        if let Some(session) = session.as_ref() {
+            return Ok(session.info.clone());
         }
+
+        let connected = tokio::time::timeout(self.timeout, self.connect())
+            .await
+            .map_err(|_| self.timeout_error("initialize"))??;
+
+        tracing::info!(
+            "Connected to MCP server {} {} (protocol {})",
+            connected.info.server_info.name,
+            connected.info.server_info.version,
+            connected.info.protocol_version
+        );
+
+        let info = connected.info.clone();
+        *session = Some(connected);
+        Ok(info)
     }
 
     /// List available tools from MCP server
     pub async fn list_tools(&self) -> Result<Vec<MCPTool>> {
         tracing::info!("Listing tools from MCP server: {}", self.server_uri);
 
-        // For MVP, return mock tools
-        // In production, this would make actual MCP protocol requests
-        Ok(vec![
-            MCPTool {
-                name: "calculator".to_string(),
-                description: "Perform basic arithmetic operations".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "operation": {"type": "string", "enum": ["add", "subtract", "multiply", "divide"]},
-                        "a": {"type": "number"},
-                        "b": {"type": "number"}
-                    },
-                    "required": ["operation", "a", "b"]
-                }),
-            },
-            MCPTool {
-                name: "echo".to_string(),
-                description: "Echo back the input message".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "message": {"type": "string"}
-                    },
-                    "required": ["message"]
-                }),
-            },
-        ])
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+
+            let page: Vec<MCPTool> = serde_json::from_value(
+                result.get("tools").cloned().unwrap_or(json!([])),
+            )
+            .context("Invalid tools/list response")?;
+            tools.extend(page);
+
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        *self.tools.write().unwrap() =
+            tools.iter().map(|tool| (tool.name.clone(), tool.clone())).collect();
+        Ok(tools)
     }
 
     /// Call a tool on the MCP server
     pub async fn call_tool(&self, name: &str, args: Value) -> Result<MCPCallResult> {
         tracing::info!("Calling MCP tool: {} with args: {}", name, args);
 
-        // Mock implementation for MVP
-        match name {
-            "calculator" => {
-                let operation = args["operation"].as_str().unwrap_or("add");
-                let a = args["a"].as_f64().unwrap_or(0.0);
-                let b = args["b"].as_f64().unwrap_or(0.0);
-
-                let result = match operation {
-                    "add" => a + b,
-                    "subtract" => a - b,
-                    "multiply" => a * b,
-                    "divide" => {
-                        if b != 0.0 {
-                            a / b
-                        } else {
-                            return Ok(MCPCallResult {
-                                success: false,
-                                result: Value::Null,
-                                error: Some("Division by zero".to_string()),
-                            });
-                        }
-                    }
-                    _ => {
-                        return Ok(MCPCallResult {
-                            success: false,
-                            result: Value::Null,
-                            error: Some(format!("Unknown operation: {}", operation)),
-                        });
-                    }
-                };
-
-                Ok(MCPCallResult {
-                    success: true,
-                    result: serde_json::json!({"result": result}),
-                    error: None,
-                })
-            }
-            "echo" => {
-                let message = args["message"].as_str().unwrap_or("");
-                Ok(MCPCallResult {
-                    success: true,
-                    result: serde_json::json!({"message": message}),
-                    error: None,
-                })
-            }
-            _ => Ok(MCPCallResult {
-                success: false,
-                result: Value::Null,
-                error: Some(format!("Unknown tool: {}", name)),
-            }),
+        if self.tools.read().unwrap().is_empty() {
+            self.list_tools().await?;
+        }
+        if let Some(tool) = self.tools.read().unwrap().get(name) {
+            tool.validate_arguments(&args)?;
         }
+
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": args }))
+            .await?;
+
+        let result: MCPToolResult =
+            serde_json::from_value(result).context("Invalid tools/call response")?;
+        Ok(result.into())
     }
 
     /// Register an agent's tools with the MCP server
@@ -134,55 +531,687 @@ impl MCPHandler {
         Ok(())
     }
 
-    /// Validate MCP endpoint connectivity
+    /// Validate MCP endpoint connectivity by completing the handshake
     pub async fn validate_endpoint(&self) -> Result<bool> {
         tracing::info!("Validating MCP endpoint: {}", self.server_uri);
 
-        // For MVP, just check if URI is well-formed
-        Ok(self.server_uri.starts_with("mcp://")
-            || self.server_uri.starts_with("http://")
-            || self.server_uri.starts_with("https://"))
+        if self.endpoint().is_err() {
+            return Ok(false);
+        }
+
+        match self.initialize().await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::warn!("MCP endpoint {} is not usable: {:#}", self.server_uri, e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Send a JSON-RPC request and return its `result`, enforcing the timeout
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        self.initialize().await?;
+
+        let (transport, version) = self
+            .session
+            .lock()
+            .await
+            .as_ref()
+            .map(|session| (session.transport.clone(), session.info.protocol_version.clone()))
+            .context("MCP session is not initialized")?;
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let sent = self.send(&transport, &message, Some(id), Some(&version));
+        let response = tokio::time::timeout(self.timeout, sent)
+            .await
+            .map_err(|_| self.timeout_error(method))??
+            .context("MCP server sent no response")?;
+
+        into_result(response)
+    }
+
+    /// The HTTP URL for the advertised endpoint; `mcp://` is treated as `http://`
+    fn endpoint(&self) -> Result<reqwest::Url> {
+        let uri = match self.server_uri.strip_prefix("mcp://") {
+            Some(rest) => format!("http://{}", rest),
+            None => self.server_uri.clone(),
+        };
+
+        let url = reqwest::Url::parse(&uri)
+            .with_context(|| format!("Invalid MCP endpoint: {}", self.server_uri))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            anyhow::bail!("Unsupported MCP endpoint scheme: {}", url.scheme());
+        }
+
+        Ok(url)
+    }
+
+    fn timeout_error(&self, method: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "MCP request {} to {} timed out after {}s",
+            method,
+            self.server_uri,
+            self.timeout.as_secs()
+        )
+    }
+
+    fn initialize_message(&self) -> (u64, Value) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": initialize_params(),
+        });
+        (id, message)
+    }
+
+    async fn connect(&self) -> Result<Session> {
+        let url = self.endpoint()?;
+        let (id, initialize) = self.initialize_message();
+
+        let response = self
+            .http
+            .post(url.clone())
+            .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+            .json(&initialize)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to MCP server at {}", url))?;
+
+        // Servers that only speak HTTP+SSE reject the POST; the spec says to
+        // retry with a GET and expect an `endpoint` event
+        if matches!(response.status().as_u16(), 400 | 404 | 405) {
+            tracing::debug!("POST initialize returned {}, trying HTTP+SSE", response.status());
+            return self.connect_sse(url).await;
+        }
+
+        let session_id = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let message = read_response(check_status(response).await?, id).await?;
+        let info: InitializeResult = serde_json::from_value(into_result(message)?)
+            .context("Invalid initialize response")?;
+        info.check_compatible()?;
+
+        let transport = Transport::Streamable { session_id };
+        let session = Session { transport, info, reader: None };
+        self.send_initialized(&session).await?;
+
+        Ok(session)
+    }
+
+    async fn connect_sse(&self, url: reqwest::Url) -> Result<Session> {
+        let mut response = check_status(
+            self.http
+                .get(url.clone())
+                .header(reqwest::header::ACCEPT, "text/event-stream")
+                .send()
+                .await
+                .with_context(|| format!("Failed to open SSE stream at {}", url))?,
+        )
+        .await?;
+
+        // The first event names the URL that accepts our POSTed messages
+        let mut parser = SseParser::default();
+        let mut early = Vec::new();
+        let post_url = 'endpoint: loop {
+            let chunk = response
+                .chunk()
+                .await?
+                .context("SSE stream closed before the endpoint event")?;
+            for event in parser.push(&chunk) {
+                if event.event.as_deref() == Some("endpoint") {
+                    break 'endpoint url.join(event.data.trim())?;
+                }
+                early.push(event);
+            }
+        };
+
+        let pending: Pending = Arc::default();
+        let reader = tokio::spawn(sse_reader(response, parser, pending.clone()));
+        let transport = Transport::Sse { post_url: post_url.to_string(), pending };
+
+        let (id, initialize) = self.initialize_message();
+        let message = self
+            .send(&transport, &initialize, Some(id), None)
+            .await?
+            .context("MCP server sent no initialize response")?;
+        let info: InitializeResult = serde_json::from_value(into_result(message)?)
+            .context("Invalid initialize response")?;
+        info.check_compatible()?;
+
+        let session = Session { transport, info, reader: Some(reader) };
+        self.send_initialized(&session).await?;
+
+        Ok(session)
+    }
+
+    async fn send_initialized(&self, session: &Session) -> Result<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized",
+        });
+        let version = Some(session.info.protocol_version.as_str());
+        self.send(&session.transport, &notification, None, version).await?;
+        Ok(())
+    }
+
+    /// POST one message; returns the matching response when `id` is set
+    async fn send(
+        &self,
+        transport: &Transport,
+        message: &Value,
+        id: Option<u64>,
+        protocol_version: Option<&str>,
+    ) -> Result<Option<Value>> {
+        match transport {
+            Transport::Streamable { session_id } => {
+                let mut request = self
+                    .http
+                    .post(self.endpoint()?)
+                    .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+                    .json(message);
+                if let Some(session_id) = session_id {
+                    request = request.header(SESSION_ID_HEADER, session_id);
+                }
+                if let Some(version) = protocol_version {
+                    request = request.header(PROTOCOL_VERSION_HEADER, version);
+                }
+
+                let response = check_status(request.send().await.context("MCP request failed")?).await?;
+                match id {
+                    Some(id) => Ok(Some(read_response(response, id).await?)),
+                    None => Ok(None),
+                }
+            }
+            Transport::Sse { post_url, pending } => {
+                // Dropped on every way out of here, including the caller's
+                // timeout, so no sender outlives the request
+                let waiter = id.map(|id| {
+                    let (sender, receiver) = oneshot::channel();
+                    pending.lock().unwrap().insert(id, sender);
+                    (PendingGuard { pending, id }, receiver)
+                });
+
+                check_status(
+                    self.http
+                        .post(post_url)
+                        .json(message)
+                        .send()
+                        .await
+                        .context("MCP request failed")?,
+                )
+                .await?;
+
+                match waiter {
+                    Some((_guard, receiver)) => Ok(Some(
+                        receiver.await.context("SSE stream closed before the response")?,
+                    )),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Removes a request's entry from `Pending` when the request is abandoned
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u64,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Fail on non-2xx statuses, keeping the body for the error message
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    anyhow::bail!("MCP server returned {}: {}", status, body.trim())
+}
+
+/// Read the JSON-RPC response with `id` from a JSON or SSE response body
+async fn read_response(mut response: reqwest::Response, id: u64) -> Result<Value> {
+    let is_sse = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+
+    if !is_sse {
+        let body: Value = response.json().await.context("Invalid MCP response body")?;
+        return match body {
+            // A batch may carry several responses
+            Value::Array(messages) => messages
+                .into_iter()
+                .find(|m| is_response_to(m, id))
+                .context("MCP response batch did not contain our request"),
+            message => Ok(message),
+        };
+    }
+
+    let mut parser = SseParser::default();
+    while let Some(chunk) = response.chunk().await? {
+        for event in parser.push(&chunk) {
+            let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                continue;
+            };
+            if is_response_to(&message, id) {
+                return Ok(message);
+            }
+            log_server_message(&message);
+        }
+    }
+
+    anyhow::bail!("MCP server closed the stream before responding")
+}
+
+/// Route responses on an HTTP+SSE stream to the callers waiting for them
+async fn sse_reader(mut response: reqwest::Response, mut parser: SseParser, pending: Pending) {
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("MCP SSE stream failed: {}", e);
+                break;
+            }
+        };
+
+        for event in parser.push(&chunk) {
+            let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                continue;
+            };
+            let waiter = message
+                .get("id")
+                .and_then(|id| id.as_u64())
+                .and_then(|id| pending.lock().unwrap().remove(&id));
+            match waiter {
+                Some(waiter) => {
+                    let _ = waiter.send(message);
+                }
+                None => log_server_message(&message),
+            }
+        }
+    }
+
+    // Dropping the senders wakes every remaining caller with an error
+    pending.lock().unwrap().clear();
+}
+
+fn is_response_to(message: &Value, id: u64) -> bool {
+    message.get("id").and_then(|v| v.as_u64()) == Some(id)
+        && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+fn log_server_message(message: &Value) {
+    match message.get("method").and_then(|m| m.as_str()) {
+        Some(method) => tracing::debug!("MCP server message: {}", method),
+        None => tracing::debug!("Unexpected MCP message: {}", message),
+    }
+}
+
+/// Unwrap a JSON-RPC response into its `result`, turning `error` into an Err
+pub(crate) fn into_result(message: Value) -> Result<Value> {
+    if let Some(error) = message.get("error") {
+        let code = error.get("code").and_then(|c| c.as_i64()).unwrap_or_default();
+        let text = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+        anyhow::bail!("MCP error {}: {}", code, text);
+    }
+
+    Ok(message.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Flatten a typed tool result into the handler's success/result/error shape
+impl From<MCPToolResult> for MCPCallResult {
+    fn from(result: MCPToolResult) -> Self {
+        if result.is_error {
+            return MCPCallResult {
+                success: false,
+                result: Value::Null,
+                error: Some(result.error_message()),
+            };
+        }
+
+        // Prefer structured output, then JSON text, then the raw content blocks
+        let texts: Vec<&str> = result.content.iter().filter_map(MCPContent::as_text).collect();
+        let value = match (&result.structured_content, texts.as_slice()) {
+            (Some(structured), _) => structured.clone(),
+            (None, [single]) => {
+                serde_json::from_str(single).unwrap_or_else(|_| json!(single))
+            }
+            (None, _) => serde_json::to_value(&result.content).unwrap_or(Value::Null),
+        };
+
+        MCPCallResult {
+            success: true,
+            result: value,
+            error: None,
+        }
+    }
+}
+
+/// A single server-sent event
+#[derive(Debug, Clone, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+}
+
+/// Incremental `text/event-stream` parser
+#[derive(Default)]
+struct SseParser {
+    /// Raw bytes of the line being received; a multi-byte character may be
+    /// split across chunks, so only complete lines are decoded
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed a chunk and return the events it completes
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(newline) = self.buffer.iter().position(|&b| b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        event: self.event.take(),
+                        data: self.data.join("\n"),
+                    });
+                }
+                self.event = None;
+                self.data.clear();
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                // Comments (empty field), `id` and `retry` are not needed here
+                _ => {}
+            }
+        }
+
+        events
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
 
-    #[tokio::test]
-    async fn test_mcp_handler_creation() {
-        let handler = MCPHandler::new("mcp://localhost:3000".to_string(), 30);
-        assert!(handler.validate_endpoint().await.unwrap());
+    /// Minimal Streamable HTTP server: answers `initialize` and `tools/list`
+    /// as JSON, `tools/call` as an SSE stream, and never answers `slow`
+    async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut raw = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let body = loop {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        raw.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&raw).to_string();
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head
+                                .lines()
+                                .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                                .unwrap_or(0);
+                            if body.len() >= length {
+                                break body.to_string();
+                            }
+                        }
+                    };
+
+                    let request: Value = serde_json::from_str(&body).unwrap();
+                    let id = request["id"].clone();
+                    let (content_type, payload) = match request["method"].as_str().unwrap() {
+                        "initialize" => ("application/json", json!({
+                            "jsonrpc": "2.0", "id": id,
+                            "result": {
+                                "protocolVersion": PROTOCOL_VERSION,
+                                "capabilities": { "tools": {} },
+                                "serverInfo": { "name": "test-server", "version": "1.0.0" }
+                            }
+                        }).to_string()),
+                        "notifications/initialized" => {
+                            let _ = socket.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n").await;
+                            return;
+                        }
+                        "tools/list" => ("application/json", json!({
+                            "jsonrpc": "2.0", "id": id,
+                            "result": { "tools": [{
+                                "name": "echo",
+                                "description": "Echo back the input message",
+                                "inputSchema": {
+                                    "type": "object",
+                                    "properties": { "message": { "type": "string" } },
+                                    "required": ["message"]
+                                }
+                            }]}
+                        }).to_string()),
+                        "tools/call" => ("text/event-stream", format!(
+                            "event: message\ndata: {}\n\nevent: message\ndata: {}\n\n",
+                            json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": {} }),
+                            json!({
+                                "jsonrpc": "2.0", "id": id,
+                                "result": { "content": [{ "type": "text", "text": request["params"]["arguments"].to_string() }] }
+                            })
+                        )),
+                        _ => {
+                            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                            return;
+                        }
+                    };
+
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: {}\r\n{}: test-session\r\ncontent-length: {}\r\n\r\n{}",
+                        content_type,
+                        SESSION_ID_HEADER,
+                        payload.len(),
+                        payload
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        format!("http://{}/mcp", addr)
     }
 
     #[tokio::test]
-    async fn test_list_tools() {
-        let handler = MCPHandler::new("mcp://localhost:3000".to_string(), 30);
+    async fn test_initialize_and_list_tools() {
+        let handler = MCPHandler::new(spawn_server().await, 5);
+
+        let info = handler.initialize().await.unwrap();
+        assert_eq!(info.server_info.name, "test-server");
+        assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+
         let tools = handler.list_tools().await.unwrap();
-        assert!(!tools.is_empty());
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "echo");
+        assert!(handler.validate_endpoint().await.unwrap());
     }
 
     #[tokio::test]
-    async fn test_call_calculator() {
-        let handler = MCPHandler::new("mcp://localhost:3000".to_string(), 30);
-        let args = serde_json::json!({
-            "operation": "add",
-            "a": 5,
-            "b": 3
-        });
+    async fn test_call_tool_over_sse() {
+        let handler = MCPHandler::new(spawn_server().await, 5);
+        let args = json!({ "message": "Hello, MCP!" });
 
-        let result = handler.call_tool("calculator", args).await.unwrap();
+        let result = handler.call_tool("echo", args).await.unwrap();
         assert!(result.success);
-        assert_eq!(result.result["result"], 8.0);
+        assert_eq!(result.result["message"], "Hello, MCP!");
+
+        let err = handler.call_tool("echo", json!({ "message": 5 })).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MCPError>(),
+            Some(MCPError::InvalidArguments { errors, .. }) if errors[0].path == "/message"
+        ));
     }
 
     #[tokio::test]
-    async fn test_call_echo() {
-        let handler = MCPHandler::new("mcp://localhost:3000".to_string(), 30);
-        let args = serde_json::json!({"message": "Hello, MCP!"});
+    async fn test_request_timeout() {
+        let handler = MCPHandler::new(spawn_server().await, 1);
 
-        let result = handler.call_tool("echo", args).await.unwrap();
-        assert!(result.success);
-        assert_eq!(result.result["message"], "Hello, MCP!");
+        let err = handler.request("slow", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("timed out after 1s"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_endpoint() {
+        let handler = MCPHandler::new("ftp://localhost:3000".to_string(), 1);
+        assert!(!handler.validate_endpoint().await.unwrap());
+
+        let handler = MCPHandler::new("mcp://localhost:3000".to_string(), 1);
+        assert_eq!(handler.endpoint().unwrap().as_str(), "http://localhost:3000/");
+    }
+
+    #[test]
+    fn test_initialize_result_compatibility() {
+        let info: InitializeResult = serde_json::from_value(json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": { "tools": { "listChanged": true }, "resources": { "subscribe": true } },
+            "serverInfo": { "name": "filecoin", "version": "0.1.0" }
+        }))
+        .unwrap();
+        assert!(info.check_compatible().is_ok());
+        assert!(info.capabilities.resources.as_ref().unwrap().subscribe);
+        assert!(info.require("tools").is_ok());
+        assert!(matches!(
+            info.require("prompts"),
+            Err(MCPError::MissingCapability { capability: "prompts", .. })
+        ));
+
+        let future = InitializeResult { protocol_version: "2099-01-01".to_string(), ..info };
+        assert!(matches!(
+            future.check_compatible(),
+            Err(MCPError::UnsupportedProtocolVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pending_guard_removes_abandoned_request() {
+        let pending: Pending = Arc::default();
+        let (sender, _receiver) = oneshot::channel();
+        pending.lock().unwrap().insert(7, sender);
+
+        drop(PendingGuard { pending: &pending, id: 7 });
+        assert!(pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sse_parser_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: endpoint\r\nda").is_empty());
+
+        let events = parser.push(b"ta: /messages?sessionId=1\r\n\r\n: ping\n\ndata: {\"a\":\ndata: 1}\n\n");
+        assert_eq!(
+            events,
+            vec![
+                SseEvent { event: Some("endpoint".to_string()), data: "/messages?sessionId=1".to_string() },
+                SseEvent { event: None, data: "{\"a\":\n1}".to_string() },
+            ]
+        );
+
+        // A multi-byte character split between chunks survives intact
+        let text = "data: 数据\n\n".as_bytes();
+        assert!(parser.push(&text[..7]).is_empty());
+        let events = parser.push(&text[7..]);
+        assert_eq!(events, vec![SseEvent { event: None, data: "数据".to_string() }]);
+    }
+
+    #[test]
+    fn test_tool_result_content_model() {
+        let result: MCPToolResult = serde_json::from_value(json!({
+            "content": [
+                { "type": "text", "text": "not json" },
+                { "type": "text", "text": "{\"success\": true, \"piece_cid\": \"baga...\"}" },
+                { "type": "image", "data": "iVBO", "mimeType": "image/png" },
+                { "type": "audio", "data": "AAEC", "mimeType": "audio/wav" },
+                { "type": "resource", "resource": { "uri": "file:///report.json", "text": "{}" } },
+                { "type": "resource_link", "uri": "nft-migration://status", "name": "Migration Status" },
+                { "type": "hologram", "data": "?" }
+            ]
+        }))
+        .unwrap();
+
+        assert!(!result.is_error);
+        assert!(matches!(&result.content[2], MCPContent::Image { mime_type, .. } if mime_type == "image/png"));
+        assert_eq!(result.content[3].bytes().unwrap(), vec![0, 1, 2]);
+        assert_eq!(result.content[4].bytes().unwrap(), b"{}");
+        assert!(matches!(&result.content[5], MCPContent::ResourceLink(link) if link.uri == "nft-migration://status"));
+        assert!(matches!(result.content[6], MCPContent::Unknown));
+
+        // The first text block that fits wins
+        let parsed: crate::mcp_client::MCPResponse = result.parse().unwrap();
+        assert_eq!(parsed.piece_cid.as_deref(), Some("baga..."));
+
+        let structured: MCPToolResult = serde_json::from_value(json!({
+            "content": [],
+            "structuredContent": { "sum": 8 }
+        }))
+        .unwrap();
+        assert_eq!(structured.parse::<Value>().unwrap()["sum"], 8);
+
+        let failed: MCPToolResult = serde_json::from_value(json!({ "content": [], "isError": true })).unwrap();
+        assert_eq!(failed.error_message(), "Tool reported an error");
+        assert!(failed.parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn test_call_result_mapping() {
+        let call_result_from_value =
+            |value| MCPCallResult::from(serde_json::from_value::<MCPToolResult>(value).unwrap());
+
+        let structured = call_result_from_value(json!({
+            "content": [{ "type": "text", "text": "{\"sum\": 8}" }],
+            "structuredContent": { "sum": 8 }
+        }));
+        assert!(structured.success);
+        assert_eq!(structured.result["sum"], 8);
+
+        let failed = call_result_from_value(json!({
+            "content": [{ "type": "text", "text": "Division by zero" }],
+            "isError": true
+        }));
+        assert!(!failed.success);
+        assert_eq!(failed.error.as_deref(), Some("Division by zero"));
+
+        let error = into_result(json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "Unknown tool" } }));
+        assert!(error.unwrap_err().to_string().contains("-32602"));
     }
 }
@@ -1,28 +1,44 @@
+use crate::config::{MCPServerConfig, StderrMode};
+use crate::mcp::{
+    initialize_params, into_result, InitializeResult, MCPError, MCPPrompt, MCPPromptResult,
+    MCPResource, MCPResourceContents, MCPToolResult,
+};
+use crate::signer::resolve_env;
+use crate::storage::{StorageBackend, StorageError, StorageStatus};
+use crate::upload::Progress;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::process::{Child, Command, Stdio};
-use std::io::{BufRead, BufReader, Write};
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MCPTool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
-}
+pub use crate::mcp::MCPTool;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPResponse {
     pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    // The Node.js server answers in camelCase
+    #[serde(alias = "pieceCid", skip_serializing_if = "Option::is_none")]
     pub piece_cid: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "carCid", skip_serializing_if = "Option::is_none")]
     pub car_cid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub size: Option<usize>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "providerAddress", skip_serializing_if = "Option::is_none")]
     pub provider_address: Option<String>,
+    /// Set by `get_storage_status`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -31,144 +47,349 @@ pub struct MCPResponse {
     pub error: Option<String>,
 }
 
+/// Server-initiated notification, e.g. `notifications/progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPNotification {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl MCPNotification {
+    /// The URI from a `notifications/resources/updated` notification
+    pub fn updated_resource(&self) -> Option<&str> {
+        if self.method != "notifications/resources/updated" {
+            return None;
+        }
+        self.params.get("uri").and_then(|u| u.as_str())
+    }
+}
+
+/// Callers waiting for a response, keyed by JSON-RPC id
+type PendingRequests = std::sync::Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>;
+
+/// Removes a request's entry from `PendingRequests` when the caller stops waiting
+struct PendingGuard<'a> {
+    pending: &'a PendingRequests,
+    id: u64,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// The server's stdin; `None` while no server is running
+type SharedWriter = Mutex<Option<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// The current server process, shared with the supervisor that restarts it
+type SharedChild = Arc<Mutex<Option<Child>>>;
+
+/// The server's most recent stderr lines, reported in `MCPError::ServerExited`
+type StderrTail = Arc<std::sync::Mutex<VecDeque<String>>>;
+
+/// Notifications buffered before the caller starts draining them
+const NOTIFICATION_BUFFER: usize = 256;
+
+/// How long the server gets to answer `initialize`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the server gets to exit after its stdin is closed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// How long to wait for an exit status once the server has closed stdout
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// A server that stayed up this long starts over with a fresh restart budget
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// Number of stderr lines kept for error reports
+const STDERR_TAIL_LINES: usize = 20;
+
+/// The largest file sent inline to a server without a shared filesystem;
+/// the whole file goes base64-encoded in one message
+const MAX_INLINE_UPLOAD: u64 = 32 * 1024 * 1024;
+
 /// MCP Client for communicating with Filecoin MCP Server
+///
+/// A single reader task owns the server's stdout and routes each response to
+/// the caller waiting on its `id`, so requests may be issued concurrently.
+/// For spawned servers the same task notices when the process exits and, if
+/// the launch spec has a restart policy, starts it again.
 pub struct MCPClient {
-    process: Arc<Mutex<Option<Child>>>,
-    request_id: Arc<Mutex<u64>>,
+    connection: Arc<Connection>,
+    notifications: std::sync::Mutex<Option<mpsc::Receiver<MCPNotification>>>,
+    supervisor: JoinHandle<()>,
+    process: Option<SharedChild>,
+    /// Files can be uploaded by path rather than sent inline
+    shared_fs: bool,
 }
 
 impl MCPClient {
-    /// Start MCP server process and create client
-    pub fn new(server_command: &str) -> Result<Self> {
-        tracing::info!("Starting MCP server: {}", server_command);
-
-        let mut child = Command::new("node")
-            .arg(server_command)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .context("Failed to start MCP server")?;
-
-        // Wait a bit for server to initialize
-        std::thread::sleep(std::time::Duration::from_millis(500));
-
-        tracing::info!("MCP server started successfully");
-
-        Ok(Self {
-            process: Arc::new(Mutex::new(Some(child))),
-            request_id: Arc::new(Mutex::new(1)),
-        })
+    /// Start a Node.js MCP server script and create client
+    pub async fn new(server_command: &str) -> Result<Self> {
+        Self::spawn(server_command, &MCPServerConfig::node(server_command)).await
     }
 
-    /// Get next request ID
-    fn next_request_id(&self) -> u64 {
-        let mut id = self.request_id.lock().unwrap();
-        let current = *id;
-        *id += 1;
-        current
-    }
+    /// Launch the server described by `spec`, then run the handshake
+    pub async fn spawn(name: &str, spec: &MCPServerConfig) -> Result<Self> {
+        let stderr_tail = StderrTail::default();
+        let launched = launch(name, spec, &stderr_tail)?;
 
-    /// Send JSON-RPC request to MCP server
-    fn send_request(&self, method: &str, params: Value) -> Result<Value> {
-        let mut process_guard = self.process.lock().unwrap();
-        let process = process_guard.as_mut()
-            .context("MCP server process not available")?;
+        let connection = Arc::new(Connection::new(name, Box::new(launched.stdin)));
+        let process: SharedChild = Arc::new(Mutex::new(Some(launched.child)));
+        let (notify_tx, notify_rx) = mpsc::channel(NOTIFICATION_BUFFER);
 
-        let request_id = self.next_request_id();
+        let supervisor = Supervisor {
+            spec: spec.clone(),
+            connection: connection.clone(),
+            process: process.clone(),
+            stderr_tail,
+            notifications: notify_tx,
+        };
+        let supervisor = tokio::spawn(supervisor.run(launched.stdout, launched.stderr_task));
 
-        let request = json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "method": method,
-            "params": params
+        let client = Self {
+            connection,
+            notifications: std::sync::Mutex::new(Some(notify_rx)),
+            supervisor,
+            process: Some(process),
+            shared_fs: spec.shared_fs,
+        };
+
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Create a client over an already connected pair of streams
+    pub fn from_streams<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let connection = Arc::new(Connection::new("stdio", Box::new(writer)));
+        let (notify_tx, notify_rx) = mpsc::channel(NOTIFICATION_BUFFER);
+
+        let reader_connection = connection.clone();
+        let supervisor = tokio::spawn(async move {
+            read_messages(BufReader::new(reader), &reader_connection, &notify_tx).await;
+            // Dropping the senders fails every request still waiting
+            reader_connection.pending.lock().unwrap().clear();
         });
 
-        let request_str = serde_json::to_string(&request)?;
+        Self {
+            connection,
+            notifications: std::sync::Mutex::new(Some(notify_rx)),
+            supervisor,
+            process: None,
+            shared_fs: false,
+        }
+    }
 
-        tracing::debug!("Sending MCP request: {}", request_str);
+    /// Declare whether the server can read this machine's files; servers
+    /// reached over streams are assumed not to
+    pub fn with_shared_fs(mut self, shared_fs: bool) -> Self {
+        self.shared_fs = shared_fs;
+        self
+    }
 
-        // Write request to stdin
-        if let Some(stdin) = process.stdin.as_mut() {
-            writeln!(stdin, "{}", request_str)?;
-            stdin.flush()?;
-        } else {
-            anyhow::bail!("MCP server stdin not available");
+    /// Run the MCP lifecycle handshake: `initialize`, a protocol version
+    /// check, then `notifications/initialized`
+    pub async fn initialize(&self) -> Result<InitializeResult> {
+        if let Some(server) = self.server_info() {
+            return Ok(server);
         }
+        self.connection.handshake().await
+    }
 
-        // Read response from stdout
-        if let Some(stdout) = process.stdout.as_mut() {
-            let mut reader = BufReader::new(stdout);
-            let mut response_line = String::new();
-            reader.read_line(&mut response_line)?;
+    /// What the server reported during the handshake, once it has completed
+    pub fn server_info(&self) -> Option<InitializeResult> {
+        self.connection.server.read().unwrap().clone()
+    }
 
-            tracing::debug!("Received MCP response: {}", response_line);
+    /// Fail with `MCPError::MissingCapability` if the handshake showed the server lacks `capability`
+    fn require(&self, capability: &'static str) -> Result<()> {
+        if let Some(server) = self.connection.server.read().unwrap().as_ref() {
+            server.require(capability)?;
+        }
+        Ok(())
+    }
 
-            let response: Value = serde_json::from_str(&response_line)?;
+    /// Close the server's stdin and wait for it to exit, killing it if it does not
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.supervisor.abort();
+        shutdown_process(self.connection.clone(), self.process.take()).await
+    }
 
-            if let Some(error) = response.get("error") {
-                anyhow::bail!("MCP error: {}", error);
-            }
+    /// Take the stream of server notifications; only the first call gets it
+    pub fn take_notifications(&self) -> Option<mpsc::Receiver<MCPNotification>> {
+        self.notifications.lock().unwrap().take()
+    }
 
-            Ok(response.get("result").cloned().unwrap_or(Value::Null))
-        } else {
-            anyhow::bail!("MCP server stdout not available");
-        }
+    /// Send JSON-RPC request to MCP server and wait for its response
+    ///
+    /// Fails with `MCPError::ServerExited` if the server process goes away first.
+    pub async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
+        self.connection.call(method, params).await
     }
 
-    /// List available tools
-    pub fn list_tools(&self) -> Result<Vec<MCPTool>> {
-        let result = self.send_request("tools/list", json!({}))?;
+    /// Send a JSON-RPC notification, which has no response
+    pub async fn send_notification(&self, method: &str, params: Value) -> Result<()> {
+        self.connection.notify(method, params).await
+    }
 
-        let tools: Vec<MCPTool> = serde_json::from_value(
-            result.get("tools").cloned().unwrap_or(json!([]))
-        )?;
+    /// List available tools
+    pub async fn list_tools(&self) -> Result<Vec<MCPTool>> {
+        self.require("tools")?;
+        let tools: Vec<MCPTool> = self.list_all("tools/list", "tools").await?;
 
+        *self.connection.tools.write().unwrap() =
+            tools.iter().map(|tool| (tool.name.clone(), tool.clone())).collect();
         Ok(tools)
     }
 
     /// Call a tool
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<MCPResponse> {
+    ///
+    /// A tool that ran but failed is still `Ok`, with `is_error` set on the result.
+    /// Arguments are first checked against the tool's input schema, failing
+    /// with `MCPError::InvalidArguments` without contacting the tool.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<MCPToolResult> {
+        self.require("tools")?;
+        self.validate_arguments(name, &arguments).await?;
+
         let result = self.send_request("tools/call", json!({
             "name": name,
             "arguments": arguments
-        }))?;
-
-        // Parse response from content array
-        if let Some(content) = result.get("content").and_then(|c| c.as_array()) {
-            if let Some(first) = content.first() {
-                if let Some(text) = first.get("text").and_then(|t| t.as_str()) {
-                    let response: MCPResponse = serde_json::from_str(text)?;
-                    return Ok(response);
-                }
-            }
+        })).await?;
+
+        serde_json::from_value(result)
+            .with_context(|| format!("Invalid tools/call response from {}", name))
+    }
+
+    /// Check `arguments` against the schema of tool `name`, listing tools first if needed
+    ///
+    /// Unknown tools are left for the server to reject.
+    async fn validate_arguments(&self, name: &str, arguments: &Value) -> Result<()> {
+        if self.connection.tools.read().unwrap().is_empty() {
+            self.list_tools().await?;
+        }
+        if let Some(tool) = self.connection.tools.read().unwrap().get(name) {
+            tool.validate_arguments(arguments)?;
+        }
+        Ok(())
+    }
+
+    /// Call one of the Filecoin server's tools, which answer with `MCPResponse` JSON
+    async fn call_filecoin_tool(&self, name: &str, arguments: Value) -> Result<MCPResponse> {
+        let result = self.call_tool(name, arguments).await?;
+        if result.is_error {
+            anyhow::bail!("{} failed: {}", name, result.error_message());
         }
+        result
+            .parse()
+            .with_context(|| format!("Unexpected {} result", name))
+    }
+
+    /// List the resources the server publishes
+    pub async fn list_resources(&self) -> Result<Vec<MCPResource>> {
+        self.require("resources")?;
+        self.list_all("resources/list", "resources").await
+    }
+
+    /// Read a resource by URI; servers may return several items for one URI
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<MCPResourceContents>> {
+        self.require("resources")?;
+        let result = self.send_request("resources/read", json!({ "uri": uri })).await?;
+
+        let contents = result.get("contents").cloned().unwrap_or(json!([]));
+        serde_json::from_value(contents)
+            .with_context(|| format!("Invalid resources/read response for {}", uri))
+    }
+
+    /// Ask for `notifications/resources/updated` whenever `uri` changes
+    ///
+    /// Updates arrive on the stream from [`MCPClient::take_notifications`].
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        self.require("resources.subscribe")?;
+        self.send_request("resources/subscribe", json!({ "uri": uri })).await?;
+        Ok(())
+    }
+
+    /// Stop receiving updates for `uri`
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<()> {
+        self.require("resources.subscribe")?;
+        self.send_request("resources/unsubscribe", json!({ "uri": uri })).await?;
+        Ok(())
+    }
+
+    /// List the prompt templates the server publishes
+    pub async fn list_prompts(&self) -> Result<Vec<MCPPrompt>> {
+        self.require("prompts")?;
+        self.list_all("prompts/list", "prompts").await
+    }
+
+    /// Render a prompt template with string arguments
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<MCPPromptResult> {
+        self.require("prompts")?;
+        let result = self.send_request("prompts/get", json!({
+            "name": name,
+            "arguments": arguments
+        })).await?;
+
+        serde_json::from_value(result)
+            .with_context(|| format!("Invalid prompts/get response for {}", name))
+    }
+
+    /// Collect every page of a paginated `*/list` method
+    async fn list_all<T: DeserializeOwned>(&self, method: &str, key: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.send_request(method, params).await?;
+
+            let page: Vec<T> = serde_json::from_value(result.get(key).cloned().unwrap_or(json!([])))
+                .with_context(|| format!("Invalid {} response", method))?;
+            items.extend(page);
 
-        anyhow::bail!("Invalid MCP response format")
+            match result.get("nextCursor").and_then(|c| c.as_str()) {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(items),
+            }
+        }
     }
 
     /// Upload data to Filecoin
-    pub fn upload_to_filecoin(&self, data: &[u8], filename: &str) -> Result<MCPResponse> {
+    pub async fn upload_to_filecoin(&self, data: &[u8], filename: &str) -> Result<MCPResponse> {
         let data_base64 = base64::encode(data);
 
-        self.call_tool("upload_to_filecoin", json!({
+        self.call_filecoin_tool("upload_to_filecoin", json!({
             "data": data_base64,
             "filename": filename
-        }))
+        })).await
     }
 
     /// Upload file to Filecoin
-    pub fn upload_file_to_filecoin(&self, filepath: &str) -> Result<MCPResponse> {
-        self.call_tool("upload_file_to_filecoin", json!({
+    pub async fn upload_file_to_filecoin(&self, filepath: &str) -> Result<MCPResponse> {
+        self.call_filecoin_tool("upload_file_to_filecoin", json!({
             "filepath": filepath
-        }))
+        })).await
     }
 
     /// Download from Filecoin
-    pub fn download_from_filecoin(&self, piece_cid: &str) -> Result<Vec<u8>> {
-        let response = self.call_tool("download_from_filecoin", json!({
+    pub async fn download_from_filecoin(&self, piece_cid: &str) -> Result<Vec<u8>> {
+        let response = self.call_filecoin_tool("download_from_filecoin", json!({
             "piece_cid": piece_cid
-        }))?;
+        })).await?;
 
         if !response.success {
             anyhow::bail!("Download failed: {:?}", response.error);
@@ -183,14 +404,14 @@ impl MCPClient {
     }
 
     /// Get storage status
-    pub fn get_storage_status(&self, piece_cid: &str) -> Result<MCPResponse> {
-        self.call_tool("get_storage_status", json!({
+    pub async fn get_storage_status(&self, piece_cid: &str) -> Result<MCPResponse> {
+        self.call_filecoin_tool("get_storage_status", json!({
             "piece_cid": piece_cid
-        }))
+        })).await
     }
 
     /// Create agent metadata and upload to Filecoin
-    pub fn create_agent_metadata(
+    pub async fn create_agent_metadata(
         &self,
         name: &str,
         description: &str,
@@ -207,22 +428,554 @@ impl MCPClient {
             args["image"] = json!(img);
         }
 
-        self.call_tool("create_agent_metadata", args)
+        self.call_filecoin_tool("create_agent_metadata", args).await
+    }
+}
+
+/// Storage through the Filecoin MCP server's tools; CIDs are piece CIDs
+#[async_trait]
+impl StorageBackend for MCPClient {
+    fn name(&self) -> &'static str {
+        "filecoin-mcp"
+    }
+
+    /// Piece CIDs are only retrievable from Filecoin, not from IPFS gateways
+    fn uri(&self, cid: &str) -> String {
+        format!("filecoin://{}", cid)
+    }
+
+    async fn put(&self, data: &[u8], filename: &str) -> Result<String> {
+        let response = self.upload_to_filecoin(data, filename).await?;
+        if !response.success {
+            anyhow::bail!("Upload failed: {}", response.error.unwrap_or_default());
+        }
+
+        response.piece_cid.context("Upload response has no piece CID")
+    }
+
+    /// A server that shares this machine's filesystem is handed the file's
+    /// path and reads the file itself; others receive it base64-encoded, up
+    /// to `MAX_INLINE_UPLOAD` bytes
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        if !self.shared_fs {
+            let file = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            let len = file.metadata().await?.len();
+            if len > MAX_INLINE_UPLOAD {
+                anyhow::bail!(
+                    "{} is {} bytes, more than the {} that can be sent to an MCP server without shared_fs",
+                    path.display(),
+                    len,
+                    MAX_INLINE_UPLOAD
+                );
+            }
+
+            // Stay within the limit should the file grow meanwhile
+            let mut data = Vec::with_capacity(len as usize);
+            file.take(MAX_INLINE_UPLOAD)
+                .read_to_end(&mut data)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+
+            progress.report(0, data.len() as u64);
+            let cid = self.put(&data, filename).await?;
+            progress.report(data.len() as u64, data.len() as u64);
+            return Ok(cid);
+        }
+
+        let path = tokio::fs::canonicalize(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let len = tokio::fs::metadata(&path).await?.len();
+
+        progress.report(0, len);
+        let response = self.upload_file_to_filecoin(&path.to_string_lossy()).await?;
+        if !response.success {
+            anyhow::bail!("Upload failed: {}", response.error.unwrap_or_default());
+        }
+        progress.report(len, len);
+
+        response.piece_cid.context("Upload response has no piece CID")
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        self.download_from_filecoin(cid).await
+    }
+
+    async fn pin(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "pin"))
+    }
+
+    async fn unpin(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "unpin"))
+    }
+
+    async fn status(&self, cid: &str) -> Result<StorageStatus> {
+        let response = self.get_storage_status(cid).await?;
+        Ok(StorageStatus {
+            cid: cid.to_string(),
+            pinned: response.exists.unwrap_or(false),
+            detail: response.message.or(response.error).unwrap_or_default(),
+        })
+    }
+
+    async fn delete(&self, _cid: &str) -> Result<()> {
+        Err(StorageError::unsupported(self.name(), "delete"))
     }
 }
 
 impl Drop for MCPClient {
     fn drop(&mut self) {
-        if let Ok(mut process_guard) = self.process.lock() {
-            if let Some(mut process) = process_guard.take() {
-                tracing::info!("Stopping MCP server");
-                let _ = process.kill();
-                let _ = process.wait();
+        self.supervisor.abort();
+
+        let process = self.process.take();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(shutdown_process(self.connection.clone(), process));
+            }
+            // No runtime left to wait on; kill_on_drop stops the process
+            Err(_) => drop(process),
+        }
+    }
+}
+
+/// Graceful stdio shutdown: close stdin, give the server time to exit, then kill it
+async fn shutdown_process(connection: Arc<Connection>, process: Option<SharedChild>) -> Result<()> {
+    connection.writer.lock().await.take();
+
+    let child = match process {
+        Some(process) => process.lock().await.take(),
+        None => None,
+    };
+    let Some(mut process) = child else {
+        return Ok(());
+    };
+
+    tracing::info!("Stopping MCP server {}", connection.name);
+    match tokio::time::timeout(SHUTDOWN_GRACE, process.wait()).await {
+        Ok(status) => {
+            tracing::debug!("MCP server {} exited with {}", connection.name, status?);
+        }
+        Err(_) => {
+            tracing::warn!("MCP server {} did not exit after stdin closed; killing it", connection.name);
+            process.kill().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// State shared by the client and its background tasks
+struct Connection {
+    name: String,
+    writer: SharedWriter,
+    pending: PendingRequests,
+    next_id: AtomicU64,
+    /// Handshake result for the current server process
+    server: std::sync::RwLock<Option<InitializeResult>>,
+    /// Tools from the last `tools/list`, used to validate arguments
+    tools: std::sync::RwLock<HashMap<String, MCPTool>>,
+    /// Set once the server has exited for good
+    exited: std::sync::Mutex<Option<MCPError>>,
+    /// True from the server's exit until a restarted process has completed its
+    /// handshake; requests wait meanwhile rather than fail or reach the new
+    /// process before `initialize`
+    restarting: watch::Sender<bool>,
+}
+
+impl Connection {
+    fn new(name: &str, writer: Box<dyn AsyncWrite + Send + Unpin>) -> Self {
+        Self {
+            name: name.to_string(),
+            writer: Mutex::new(Some(writer)),
+            pending: PendingRequests::default(),
+            next_id: AtomicU64::new(1),
+            server: std::sync::RwLock::new(None),
+            tools: std::sync::RwLock::default(),
+            exited: std::sync::Mutex::new(None),
+            restarting: watch::Sender::new(false),
+        }
+    }
+
+    /// Wait until a restarted server has completed its handshake
+    async fn ready(&self) {
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = self.restarting.subscribe().wait_for(|restarting| !restarting).await;
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        self.ready().await;
+        self.send_request(method, params).await
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        self.ready().await;
+        self.send_notification(method, params).await
+    }
+
+    /// Send a request without waiting for a restart's handshake, as the
+    /// handshake itself must
+    async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
+        if let Some(error) = self.exited.lock().unwrap().clone() {
+            return Err(error.into());
+        }
+
+        let request_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, sender);
+        let _guard = PendingGuard { pending: &self.pending, id: request_id };
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "method": method,
+            "params": params
+        });
+
+        self.write(&request).await?;
+        receiver
+            .await
+            .context("MCP server closed the connection before responding")?
+    }
+
+    async fn send_notification(&self, method: &str, params: Value) -> Result<()> {
+        self.write(&json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        }))
+        .await
+    }
+
+    async fn handshake(&self) -> Result<InitializeResult> {
+        let result = tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            self.send_request("initialize", initialize_params()),
+        )
+        .await
+        .context("MCP server did not answer initialize in time")??;
+
+        let info: InitializeResult =
+            serde_json::from_value(result).context("Invalid initialize response")?;
+        info.check_compatible()?;
+
+        self.send_notification("notifications/initialized", json!({})).await?;
+
+        tracing::info!(
+            "MCP server {} {} ready (protocol {})",
+            info.server_info.name,
+            info.server_info.version,
+            info.protocol_version
+        );
+
+        *self.server.write().unwrap() = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Write one newline-delimited JSON-RPC message
+    async fn write(&self, message: &Value) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        tracing::debug!("Sending MCP message: {}", line);
+        line.push('\n');
+
+        let mut writer = self.writer.lock().await;
+        let writer = writer
+            .as_mut()
+            .with_context(|| format!("MCP server {} is not running", self.name))?;
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .context("Failed to write to MCP server")?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Fail every request still waiting with `error`
+    fn fail_pending(&self, error: &MCPError) {
+        for (_, waiter) in self.pending.lock().unwrap().drain() {
+            let _ = waiter.send(Err(error.clone().into()));
+        }
+    }
+
+    /// Record that the server is gone for good, so later requests fail fast
+    fn close(&self, error: MCPError) {
+        *self.exited.lock().unwrap() = Some(error.clone());
+        self.fail_pending(&error);
+        // Release waiting callers; they now see the exit
+        self.restarting.send_replace(false);
+    }
+}
+
+/// A freshly started server process and its pipes
+struct Launched {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    stderr_task: Option<JoinHandle<()>>,
+}
+
+/// Start the process described by `spec`, forwarding its stderr if asked to
+fn launch(name: &str, spec: &MCPServerConfig, stderr_tail: &StderrTail) -> Result<Launched> {
+    tracing::info!("Starting MCP server {}: {} {}", name, spec.command, spec.args.join(" "));
+
+    let mut command = Command::new(&spec.command);
+    command
+        .args(&spec.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(match spec.stderr {
+            StderrMode::Log => Stdio::piped(),
+            StderrMode::Inherit => Stdio::inherit(),
+            StderrMode::Null => Stdio::null(),
+        })
+        .kill_on_drop(true);
+    for (key, value) in &spec.env {
+        command.env(key, resolve_env(value)?);
+    }
+    if let Some(cwd) = &spec.cwd {
+        command.current_dir(cwd);
+    }
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to start MCP server {} ({})", name, spec.command))?;
+
+    let stdin = child.stdin.take().context("MCP server stdin not available")?;
+    let stdout = child.stdout.take().context("MCP server stdout not available")?;
+
+    stderr_tail.lock().unwrap().clear();
+    let stderr_task = child
+        .stderr
+        .take()
+        .map(|stderr| tokio::spawn(forward_stderr(name.to_string(), stderr, stderr_tail.clone())));
+
+    Ok(Launched { child, stdin, stdout, stderr_task })
+}
+
+/// Log each stderr line under the server's name, keeping the last few for error reports
+///
+/// Draining the pipe here also keeps a chatty server from blocking on a full buffer.
+async fn forward_stderr(name: String, stderr: ChildStderr, tail: StderrTail) {
+    let mut reader = BufReader::new(stderr);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+        tracing::info!(server = %name, "{}", line);
+
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+}
+
+/// Watches a spawned server: reads its stdout, reports its exit and restarts it
+struct Supervisor {
+    spec: MCPServerConfig,
+    connection: Arc<Connection>,
+    process: SharedChild,
+    stderr_tail: StderrTail,
+    notifications: mpsc::Sender<MCPNotification>,
+}
+
+impl Supervisor {
+    async fn run(self, mut stdout: ChildStdout, mut stderr_task: Option<JoinHandle<()>>) {
+        let name = self.connection.name.clone();
+        let mut attempt = 0;
+
+        loop {
+            let started = Instant::now();
+            read_messages(BufReader::new(stdout), &self.connection, &self.notifications).await;
+
+            // Hold new requests back until the server is back, or `close` releases them
+            self.connection.restarting.send_replace(true);
+            self.connection.writer.lock().await.take();
+            let code = self.wait_for_exit().await;
+            if let Some(task) = stderr_task.take() {
+                // Let the last lines drain so they make it into the error
+                let _ = tokio::time::timeout(EXIT_WAIT, task).await;
+            }
+
+            let last_stderr = Vec::from(self.stderr_tail.lock().unwrap().clone()).join("\n");
+            let error = MCPError::ServerExited { server: name.clone(), code, last_stderr };
+            tracing::warn!("{}", error);
+            *self.connection.server.write().unwrap() = None;
+            self.connection.tools.write().unwrap().clear();
+
+            let Some(policy) = &self.spec.restart else {
+                self.connection.close(error);
+                return;
+            };
+            self.connection.fail_pending(&error);
+            if started.elapsed() >= STABLE_UPTIME {
+                attempt = 0;
+            }
+
+            let launched = loop {
+                if attempt >= policy.max_attempts {
+                    tracing::error!("MCP server {} gave up after {} restarts", name, attempt);
+                    self.connection.close(error);
+                    return;
+                }
+
+                let delay = policy.backoff(attempt);
+                attempt += 1;
+                tracing::info!(
+                    "Restarting MCP server {} in {:?} (attempt {}/{})",
+                    name,
+                    delay,
+                    attempt,
+                    policy.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+
+                match launch(&name, &self.spec, &self.stderr_tail) {
+                    Ok(launched) => break launched,
+                    Err(e) => tracing::warn!("{:#}", e),
+                }
+            };
+
+            *self.process.lock().await = Some(launched.child);
+            *self.connection.writer.lock().await = Some(Box::new(launched.stdin));
+            stdout = launched.stdout;
+            stderr_task = launched.stderr_task;
+
+            // The handshake needs this task reading responses, so it runs on its own
+            let connection = self.connection.clone();
+            let process = self.process.clone();
+            tokio::spawn(async move {
+                match connection.handshake().await {
+                    Ok(_) => {
+                        connection.restarting.send_replace(false);
+                    }
+                    Err(e) => {
+                        tracing::warn!("MCP server {} failed the handshake: {:#}", connection.name, e);
+                        // Exiting hands it back to the supervisor for another attempt
+                        if let Some(child) = process.lock().await.as_mut() {
+                            let _ = child.start_kill();
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    /// Reap the process once it has closed stdout, killing it if it is still running
+    async fn wait_for_exit(&self) -> Option<i32> {
+        let mut child = self.process.lock().await.take()?;
+        match tokio::time::timeout(EXIT_WAIT, child.wait()).await {
+            Ok(status) => status.ok()?.code(),
+            Err(_) => {
+                let _ = child.kill().await;
+                None
             }
         }
     }
 }
 
+/// Read server messages until EOF, routing responses, requests and notifications
+async fn read_messages<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    connection: &Connection,
+    notifications: &mpsc::Sender<MCPNotification>,
+) {
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("Failed to read from MCP server: {}", e);
+                break;
+            }
+        }
+        // A stray non-UTF-8 line must not end the session
+        let Ok(line) = std::str::from_utf8(&buf) else {
+            tracing::debug!("Ignoring non-UTF-8 output from MCP server: {}", String::from_utf8_lossy(&buf));
+            continue;
+        };
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        tracing::debug!("Received MCP message: {}", line);
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => {
+                // Some servers log to stdout; that must not break the session
+                tracing::debug!("Ignoring non-JSON output from MCP server: {}", line);
+                continue;
+            }
+        };
+
+        let messages = match message {
+            Value::Array(batch) => batch,
+            message => vec![message],
+        };
+        for message in messages {
+            route_message(message, connection, notifications).await;
+        }
+    }
+}
+
+async fn route_message(
+    message: Value,
+    connection: &Connection,
+    notifications: &mpsc::Sender<MCPNotification>,
+) {
+    let method = message.get("method").and_then(|m| m.as_str()).map(str::to_string);
+    let id = message.get("id").cloned().filter(|id| !id.is_null());
+
+    match (method, id) {
+        // Response to one of our requests
+        (None, Some(id)) => {
+            let waiter = id.as_u64().and_then(|id| connection.pending.lock().unwrap().remove(&id));
+            match waiter {
+                Some(waiter) => {
+                    let _ = waiter.send(into_result(message));
+                }
+                None => tracing::warn!("Dropping MCP response with unknown id {}", id),
+            }
+        }
+        // Request from the server; only `ping` is supported
+        (Some(method), Some(id)) => {
+            let reply = if method == "ping" {
+                json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+            } else {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {}", method) }
+                })
+            };
+            if let Err(e) = connection.write(&reply).await {
+                tracing::warn!("Failed to answer MCP server request {}: {}", method, e);
+            }
+        }
+        (Some(method), None) => {
+            if method == "notifications/tools/list_changed" {
+                connection.tools.write().unwrap().clear();
+            }
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            if notifications.try_send(MCPNotification { method, params }).is_err() {
+                tracing::debug!("Dropping MCP notification; nobody is listening");
+            }
+        }
+        (None, None) => tracing::debug!("Ignoring malformed MCP message: {}", message),
+    }
+}
+
 // Add base64 encoding/decoding helpers
 mod base64 {
     use anyhow::Result;
@@ -249,6 +1002,8 @@ mod base64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::RestartPolicy;
+    use futures_util::FutureExt;
 
     #[test]
     fn test_mcp_response_parsing() {
@@ -265,4 +1020,521 @@ mod tests {
         assert!(response.success);
         assert!(response.piece_cid.is_some());
     }
+
+    /// In-process fake server answering each request with `handler`'s result,
+    /// or not at all for `None`; `handler` also sees notifications.
+    ///
+    /// Like a chatty real server it precedes every response with a line of
+    /// non-UTF-8 log output and a progress notification, ends lines with CRLF,
+    /// and confirms `resources/subscribe` with an update for the URI. Requests
+    /// that arrive together are answered newest first, so callers must match
+    /// responses by id. The handler is dropped when the client closes stdin.
+    fn fake_server(handler: impl Fn(&Value) -> Option<Value> + Send + 'static) -> MCPClient {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, mut server_write) = tokio::io::split(server_io);
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(server_read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let mut batch = vec![line];
+                while let Some(Ok(Some(line))) = lines.next_line().now_or_never() {
+                    batch.push(line);
+                }
+
+                let mut replies = Vec::new();
+                for line in batch {
+                    let message: Value = serde_json::from_str(&line).unwrap();
+                    let result = handler(&message);
+                    if let (Some(result), Some(id)) = (result, message.get("id")) {
+                        let mut reply = vec![
+                            json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": { "progress": 1 } }),
+                            json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        ];
+                        if message["method"] == "resources/subscribe" {
+                            reply.push(json!({
+                                "jsonrpc": "2.0",
+                                "method": "notifications/resources/updated",
+                                "params": { "uri": message["params"]["uri"] }
+                            }));
+                        }
+                        replies.push(reply);
+                    }
+                }
+
+                for reply in replies.into_iter().rev() {
+                    let mut out = b"progress \xff\xfe 50%\r\n".to_vec();
+                    for message in reply {
+                        out.extend_from_slice(format!("{}\r\n", message).as_bytes());
+                    }
+                    server_write.write_all(&out).await.unwrap();
+                }
+            }
+        });
+
+        MCPClient::from_streams(client_read, client_write)
+    }
+
+    /// Tool output as the text content of a `tools/call` result
+    fn text_result(text: impl std::fmt::Display) -> Value {
+        json!({ "content": [{ "type": "text", "text": text.to_string() }] })
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_are_correlated_by_id() {
+        // Echoes the arguments of each call back as its output
+        let client = fake_server(|request| Some(text_result(&request["params"]["arguments"])));
+        let mut notifications = client.take_notifications().unwrap();
+        assert!(client.take_notifications().is_none());
+
+        let first = client.call_tool("echo", json!({ "success": true, "message": "first" }));
+        let second = client.call_tool("echo", json!({ "success": true, "message": "second" }));
+        let (first, second) = tokio::join!(first, second);
+
+        let first: MCPResponse = first.unwrap().parse().unwrap();
+        let second: MCPResponse = second.unwrap().parse().unwrap();
+        assert_eq!(first.message.as_deref(), Some("first"));
+        assert_eq!(second.message.as_deref(), Some("second"));
+
+        for _ in 0..2 {
+            let notification = notifications.recv().await.unwrap();
+            assert_eq!(notification.method, "notifications/progress");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filecoin_helper_reports_tool_errors() {
+        let client = fake_server(|request| match request["method"].as_str().unwrap() {
+            "tools/list" => Some(json!({ "tools": [] })),
+            _ => Some(json!({ "content": [{ "type": "text", "text": "piece not found" }], "isError": true })),
+        });
+
+        let err = client.get_storage_status("baga...").await.unwrap_err();
+        assert_eq!(err.to_string(), "get_storage_status failed: piece not found");
+    }
+
+    #[tokio::test]
+    async fn test_storage_backend_over_filecoin_server() {
+        // Answers the way mcp-server/src/index.ts does, in camelCase
+        let client = fake_server(|request| {
+            if request["method"] == "tools/list" {
+                return Some(json!({ "tools": [] }));
+            }
+            let body = match request["params"]["name"].as_str() {
+                Some("upload_to_filecoin") => json!({ "success": true, "pieceCid": "baga6ea4", "carCid": "bafy" }),
+                Some("upload_file_to_filecoin") => {
+                    let path = request["params"]["arguments"]["filepath"].as_str().unwrap();
+                    json!({ "success": Path::new(path).is_absolute(), "pieceCid": "baga6ea5" })
+                }
+                Some("get_storage_status") => json!({ "success": true, "exists": true, "message": "Piece baga6ea4 exists on Filecoin" }),
+                _ => Value::Null,
+            };
+            Some(text_result(body))
+        });
+
+        assert_eq!(client.put(b"hello", "hello.txt").await.unwrap(), "baga6ea4");
+
+        // Without a shared filesystem files are sent inline
+        let path = std::env::temp_dir().join(format!("agent-cli-mcp-upload-{}.txt", std::process::id()));
+        std::fs::write(&path, b"hello").unwrap();
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress = {
+            let reports = reports.clone();
+            Progress::new(move |sent, total| reports.lock().unwrap().push((sent, total)))
+        };
+        assert_eq!(client.put_file_with_progress(&path, progress.clone()).await.unwrap(), "baga6ea4");
+
+        // Too large to send in one message
+        let large = path.with_extension("large");
+        std::fs::File::create(&large).unwrap().set_len(MAX_INLINE_UPLOAD + 1).unwrap();
+        let err = client.put_file_with_progress(&large, Progress::none()).await.unwrap_err();
+        assert!(err.to_string().contains("without shared_fs"), "{}", err);
+        std::fs::remove_file(&large).unwrap();
+
+        // With one they are passed by absolute path
+        let client = client.with_shared_fs(true);
+        assert_eq!(client.put_file_with_progress(&path, progress).await.unwrap(), "baga6ea5");
+        assert_eq!(*reports.lock().unwrap(), vec![(0, 5), (5, 5), (0, 5), (5, 5)]);
+        std::fs::remove_file(&path).unwrap();
+
+        let status = client.status("baga6ea4").await.unwrap();
+        assert!(status.pinned);
+        assert_eq!(status.detail, "Piece baga6ea4 exists on Filecoin");
+
+        let err = client.unpin("baga6ea4").await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(StorageError::Unsupported { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_validates_arguments() {
+        let (calls_tx, mut calls) = mpsc::unbounded_channel();
+        let client = fake_server(move |request| match request["method"].as_str().unwrap() {
+            "tools/list" => Some(json!({ "tools": [{
+                "name": "get_storage_status",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": { "piece_cid": { "type": "string" } },
+                    "required": ["piece_cid"]
+                }
+            }] })),
+            _ => {
+                let _ = calls_tx.send(request["params"].clone());
+                Some(text_result("{\"success\": true}"))
+            }
+        });
+
+        let err = client.call_tool("get_storage_status", json!({ "piece_cid": 7 })).await.unwrap_err();
+        match err.downcast_ref::<MCPError>() {
+            Some(MCPError::InvalidArguments { tool, errors }) => {
+                assert_eq!(tool, "get_storage_status");
+                assert_eq!(errors[0].path, "/piece_cid");
+            }
+            other => panic!("expected InvalidArguments, got {:?}", other),
+        }
+        assert!(calls.try_recv().is_err(), "invalid call reached the server");
+
+        let response = client.get_storage_status("baga...").await.unwrap();
+        assert!(response.success);
+        assert_eq!(calls.recv().await.unwrap()["arguments"]["piece_cid"], "baga...");
+    }
+
+    /// Answer `initialize` with `version`, then report every later message on `seen`
+    fn spawn_handshake_server(version: &'static str, seen: mpsc::UnboundedSender<Value>) -> MCPClient {
+        fake_server(move |message| {
+            if message["method"] != "initialize" {
+                let _ = seen.send(message.clone());
+                return None;
+            }
+            Some(json!({
+                "protocolVersion": version,
+                "capabilities": { "resources": { "subscribe": true } },
+                "serverInfo": { "name": "fake", "version": "0.0.1" }
+            }))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_initialize_handshake() {
+        let (seen_tx, mut seen) = mpsc::unbounded_channel();
+        let client = spawn_handshake_server(crate::mcp::PROTOCOL_VERSION, seen_tx);
+
+        let info = client.initialize().await.unwrap();
+        assert_eq!(info.server_info.name, "fake");
+        assert!(info.capabilities.resources.as_ref().unwrap().subscribe);
+        assert!(client.server_info().is_some());
+
+        let initialized = seen.recv().await.unwrap();
+        assert_eq!(initialized["method"], "notifications/initialized");
+        assert!(initialized.get("id").is_none());
+
+        // The server did not advertise tools
+        let err = client.list_tools().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::mcp::MCPError>(),
+            Some(crate::mcp::MCPError::MissingCapability { capability: "tools", .. })
+        ));
+
+        // Dropping the client closes the server's stdin, which ends the
+        // server and drops `seen`'s sender with its handler
+        drop(client);
+        let eof = tokio::time::timeout(Duration::from_secs(5), seen.recv()).await.unwrap();
+        assert_eq!(eof, None);
+    }
+
+    #[tokio::test]
+    async fn test_incompatible_protocol_version() {
+        let (seen_tx, _seen) = mpsc::unbounded_channel();
+        let client = spawn_handshake_server("2099-01-01", seen_tx);
+
+        let err = client.initialize().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::mcp::MCPError>(),
+            Some(crate::mcp::MCPError::UnsupportedProtocolVersion { .. })
+        ));
+        assert!(client.server_info().is_none());
+    }
+
+    /// Fake server publishing two pages of resources and one prompt
+    fn spawn_catalog_server() -> MCPClient {
+        fake_server(|request| {
+            let params = &request["params"];
+            let result = match request["method"].as_str().unwrap() {
+                "initialize" => json!({
+                    "protocolVersion": crate::mcp::PROTOCOL_VERSION,
+                    "capabilities": { "resources": { "subscribe": true }, "prompts": {} },
+                    "serverInfo": { "name": "catalog", "version": "1.0.0" }
+                }),
+                "resources/list" if params.get("cursor").is_none() => json!({
+                    "resources": [{ "uri": "nft-migration://status", "name": "Migration Status", "mimeType": "application/json" }],
+                    "nextCursor": "page-2"
+                }),
+                "resources/list" => json!({
+                    "resources": [{ "uri": "nft-migration://report.bin", "name": "Report" }]
+                }),
+                "resources/read" => json!({
+                    "contents": [
+                        { "uri": params["uri"], "mimeType": "application/json", "text": "{\"migrated\":3}" },
+                        { "uri": params["uri"], "blob": "AAEC" }
+                    ]
+                }),
+                "resources/subscribe" => json!({}),
+                "prompts/list" => json!({
+                    "prompts": [{
+                        "name": "migration_workflow",
+                        "arguments": [{ "name": "contract_address", "required": true }]
+                    }]
+                }),
+                "prompts/get" => json!({
+                    "messages": [{
+                        "role": "user",
+                        "content": { "type": "text", "text": format!("Migrate {}", params["arguments"]["contract_address"].as_str().unwrap()) }
+                    }]
+                }),
+                _ => return None,
+            };
+            Some(result)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_resources_and_prompts() {
+        let client = spawn_catalog_server();
+        let mut notifications = client.take_notifications().unwrap();
+        client.initialize().await.unwrap();
+
+        let resources = client.list_resources().await.unwrap();
+        let uris: Vec<_> = resources.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(uris, ["nft-migration://status", "nft-migration://report.bin"]);
+        assert_eq!(resources[0].mime_type.as_deref(), Some("application/json"));
+
+        let contents = client.read_resource("nft-migration://status").await.unwrap();
+        assert_eq!(contents[0].text.as_deref(), Some("{\"migrated\":3}"));
+        assert_eq!(contents[1].bytes().unwrap(), vec![0, 1, 2]);
+
+        client.subscribe_resource("nft-migration://status").await.unwrap();
+        let update = loop {
+            let notification = notifications.recv().await.unwrap();
+            if notification.method != "notifications/progress" {
+                break notification;
+            }
+        };
+        assert_eq!(update.updated_resource(), Some("nft-migration://status"));
+
+        let prompts = client.list_prompts().await.unwrap();
+        assert_eq!(prompts[0].name, "migration_workflow");
+        assert!(prompts[0].arguments[0].required);
+
+        let arguments = HashMap::from([("contract_address".to_string(), "0xabc".to_string())]);
+        let prompt = client.get_prompt("migration_workflow", &arguments).await.unwrap();
+        assert_eq!(prompt.messages[0].role, "user");
+        assert_eq!(prompt.messages[0].text(), Some("Migrate 0xabc"));
+    }
+
+    #[tokio::test]
+    async fn test_prompts_require_capability() {
+        let (seen_tx, _seen) = mpsc::unbounded_channel();
+        let client = spawn_handshake_server(crate::mcp::PROTOCOL_VERSION, seen_tx);
+        client.initialize().await.unwrap();
+
+        // The handshake server offers resources with subscribe, but not prompts
+        assert!(client.require("resources.subscribe").is_ok());
+        let err = client.list_prompts().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MCPError>(),
+            Some(MCPError::MissingCapability { capability: "prompts", .. })
+        ));
+    }
+
+    /// A stdio server in plain sh: answers `initialize` with `$GREETING` and
+    /// its working directory as the server name, then waits for stdin to close
+    #[cfg(unix)]
+    fn sh_server_spec() -> MCPServerConfig {
+        let script = r#"
+            read line
+            id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"%s","version":"%s"}}}\n' "$id" "$GREETING" "$(pwd)"
+            cat > /dev/null
+        "#;
+
+        let mut env = std::collections::BTreeMap::new();
+        env.insert("GREETING".to_string(), "$AGENT_CLI_TEST_MCP_GREETING".to_string());
+        MCPServerConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env,
+            cwd: Some(std::env::temp_dir()),
+            stderr: StderrMode::Null,
+            restart: None,
+            shared_fs: true,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_with_launch_spec() {
+        std::env::set_var("AGENT_CLI_TEST_MCP_GREETING", "hello-from-sh");
+
+        let client = MCPClient::spawn("sh", &sh_server_spec()).await.unwrap();
+        let info = client.server_info().unwrap();
+        assert_eq!(info.server_info.name, "hello-from-sh");
+        assert_eq!(
+            std::fs::canonicalize(&info.server_info.version).unwrap(),
+            std::fs::canonicalize(std::env::temp_dir()).unwrap()
+        );
+
+        // `cat` exits once stdin is closed, so no kill is needed
+        tokio::time::timeout(Duration::from_secs(5), client.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    /// sh that answers `initialize` with `$n` as the server version and reads
+    /// `notifications/initialized` and one request, leaving its id in `$id`
+    #[cfg(unix)]
+    const SH_HANDSHAKE: &str = r#"
+        read line
+        id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"sh","version":"%s"}}}\n' "$id" "$n"
+        read line
+        read line
+        id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+    "#;
+
+    #[cfg(unix)]
+    fn sh_spec(script: &str) -> MCPServerConfig {
+        MCPServerConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            ..MCPServerConfig::node("unused")
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_exit_is_reported_with_stderr() {
+        let script = format!(
+            "n=1\n{}\necho 'loading model' >&2\necho 'fatal: out of memory' >&2\nexit 3",
+            SH_HANDSHAKE
+        );
+        let client = MCPClient::spawn("crashy", &sh_spec(&script)).await.unwrap();
+
+        let err = client.send_request("tools/list", json!({})).await.unwrap_err();
+        match err.downcast_ref::<MCPError>() {
+            Some(MCPError::ServerExited { server, code, last_stderr }) => {
+                assert_eq!(server, "crashy");
+                assert_eq!(*code, Some(3));
+                assert_eq!(last_stderr, "loading model\nfatal: out of memory");
+            }
+            other => panic!("expected ServerExited, got {:?}", other),
+        }
+        assert!(err.to_string().contains("exited with code 3"));
+
+        // Without a restart policy the client stays closed
+        let err = client.send_request("tools/list", json!({})).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<MCPError>(), Some(MCPError::ServerExited { .. })));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_is_restarted_after_exit() {
+        let counter = std::env::temp_dir().join(format!("agent-cli-mcp-restart-{}", std::process::id()));
+        let _ = std::fs::remove_file(&counter);
+
+        // Crashes on its first request the first time it runs, then answers normally
+        let script = format!(
+            r#"n=$(( $(cat "$COUNTER" 2>/dev/null || echo 0) + 1 ))
+            echo "$n" > "$COUNTER"
+            {}
+            if [ "$n" = 1 ]; then echo 'first run crashes' >&2; exit 3; fi
+            printf '{{"jsonrpc":"2.0","id":%s,"result":{{"run":%s}}}}\n' "$id" "$n"
+            cat > /dev/null"#,
+            SH_HANDSHAKE
+        );
+        let mut spec = sh_spec(&script);
+        spec.env.insert("COUNTER".to_string(), counter.display().to_string());
+        spec.restart = Some(RestartPolicy { max_attempts: 2, initial_backoff_ms: 200, max_backoff_ms: 200 });
+
+        let client = MCPClient::spawn("flaky", &spec).await.unwrap();
+        let err = client.send_request("tools/list", json!({})).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MCPError>(),
+            Some(MCPError::ServerExited { code: Some(3), .. })
+        ));
+
+        // Sent during the backoff, the request is held back until the second
+        // process has started and completed its handshake
+        let result = tokio::time::timeout(Duration::from_secs(5), client.send_request("tools/list", json!({})))
+            .await
+            .expect("request reached the server before initialize")
+            .unwrap();
+        assert_eq!(result["run"], 2);
+        assert_eq!(client.server_info().unwrap().server_info.version, "2");
+
+        client.shutdown().await.unwrap();
+        let _ = std::fs::remove_file(&counter);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_missing_program() {
+        let spec = MCPServerConfig {
+            command: "agent-cli-no-such-mcp-server".to_string(),
+            ..MCPServerConfig::node("index.js")
+        };
+        let err = MCPClient::spawn("missing", &spec).await.err().unwrap();
+        assert!(err.to_string().contains("Failed to start MCP server missing"));
+    }
+
+    #[tokio::test]
+    async fn test_pending_request_fails_when_server_closes() {
+        let (client_io, server_io) = tokio::io::duplex(1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let client = MCPClient::from_streams(client_read, client_write);
+
+        // The server reads the request and then goes away
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(server_io).lines();
+            let _ = lines.next_line().await;
+        });
+
+        let err = client.send_request("tools/list", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("closed the connection"));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_request_is_forgotten() {
+        // The server reads requests but never answers
+        let client = fake_server(|_| None);
+
+        let request = client.send_request("tools/list", json!({}));
+        assert!(tokio::time::timeout(Duration::from_millis(50), request).await.is_err());
+        assert!(client.connection.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8_output_is_skipped() {
+        // The fake server logs a non-UTF-8 line before every response
+        let client = fake_server(|_| Some(json!({ "ok": true })));
+
+        let result = client.send_request("tools/list", json!({})).await.unwrap();
+        assert_eq!(result["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn test_server_ping_is_answered() {
+        let (client_io, server_io) = tokio::io::duplex(1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let _client = MCPClient::from_streams(client_read, client_write);
+
+        let (server_read, mut server_write) = tokio::io::split(server_io);
+        server_write
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":\"srv-1\",\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+
+        let mut lines = BufReader::new(server_read).lines();
+        let reply: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(reply["id"], "srv-1");
+        assert_eq!(reply["result"], json!({}));
+    }
 }
@@ -0,0 +1,234 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// How deep `argument_skeleton` follows nested schemas and `$ref`s
+const MAX_SKELETON_DEPTH: usize = 8;
+
+/// One argument that does not match a tool's input schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// JSON pointer into the arguments; empty for the arguments object itself
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "arguments{}: {}", self.path, self.message)
+    }
+}
+
+/// Check `arguments` against a JSON Schema, returning every mismatch
+///
+/// A schema that cannot be compiled (for example one with remote `$ref`s) is
+/// not held against the caller: it is logged and the arguments pass.
+pub fn validate(schema: &Value, arguments: &Value) -> Vec<FieldError> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(e) => {
+            tracing::debug!("Skipping argument validation; unusable input schema: {}", e);
+            return Vec::new();
+        }
+    };
+
+    validator
+        .iter_errors(arguments)
+        .map(|error| FieldError {
+            path: error.instance_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect()
+}
+
+/// Example arguments for a schema: defaults and examples where the schema
+/// has them, otherwise a placeholder of the right type
+pub fn argument_skeleton(schema: &Value) -> Value {
+    skeleton(schema, schema, 0)
+}
+
+fn skeleton(schema: &Value, root: &Value, depth: usize) -> Value {
+    if depth > MAX_SKELETON_DEPTH {
+        return Value::Null;
+    }
+
+    if let Some(pointer) = schema.get("$ref").and_then(|r| r.as_str()) {
+        return match pointer.strip_prefix('#').and_then(|p| root.pointer(p)) {
+            Some(target) => skeleton(target, root, depth + 1),
+            None => Value::Null,
+        };
+    }
+
+    if let Some(value) = schema.get("default").or_else(|| schema.get("const")) {
+        return value.clone();
+    }
+    for key in ["examples", "enum"] {
+        if let Some(first) = schema.get(key).and_then(|v| v.as_array()).and_then(|v| v.first()) {
+            return first.clone();
+        }
+    }
+    for key in ["anyOf", "oneOf", "allOf"] {
+        let Some(branches) = schema.get(key).and_then(|v| v.as_array()).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        // A bare combinator stands for its first branch; next to `type` or
+        // `properties` it only narrows the parent, so fold it in instead
+        if schema.get("type").is_none() && schema.get("properties").is_none() {
+            return skeleton(&branches[0], root, depth + 1);
+        }
+        return skeleton(&merge_branches(schema, key, branches), root, depth + 1);
+    }
+
+    // `type` may be a list such as ["string", "null"]; the first one wins
+    let ty = match schema.get("type") {
+        Some(Value::String(ty)) => ty.as_str(),
+        Some(Value::Array(types)) => types.first().and_then(|t| t.as_str()).unwrap_or(""),
+        _ if schema.get("properties").is_some() => "object",
+        _ => "",
+    };
+
+    match ty {
+        "object" => {
+            let mut object = Map::new();
+            if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+                for (name, property) in properties {
+                    object.insert(name.clone(), skeleton(property, root, depth + 1));
+                }
+            }
+            Value::Object(object)
+        }
+        "array" => match schema.get("items") {
+            Some(items) => json!([skeleton(items, root, depth + 1)]),
+            None => json!([]),
+        },
+        "string" => match schema.get("format").and_then(|f| f.as_str()) {
+            Some(format) => json!(format!("<{}>", format)),
+            None => json!("<string>"),
+        },
+        "integer" | "number" => schema.get("minimum").cloned().unwrap_or(json!(0)),
+        "boolean" => json!(false),
+        _ => Value::Null,
+    }
+}
+
+/// `schema` with its `key` combinator folded in: every branch for `allOf`,
+/// the first for `anyOf` and `oneOf`
+fn merge_branches(schema: &Value, key: &str, branches: &[Value]) -> Value {
+    let mut merged = schema.as_object().cloned().unwrap_or_default();
+    merged.remove(key);
+
+    let chosen = if key == "allOf" { branches } else { &branches[..1] };
+    for branch in chosen.iter().filter_map(|b| b.as_object()) {
+        for (name, value) in branch {
+            match (merged.get_mut(name), value) {
+                (Some(Value::Object(existing)), Value::Object(extra)) => existing.extend(extra.clone()),
+                (Some(Value::Array(existing)), Value::Array(extra)) if name == "required" => {
+                    existing.extend(extra.iter().cloned())
+                }
+                _ => {
+                    merged.insert(name.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    // Properties only the other `oneOf` branches require would make the
+    // arguments match more than one branch
+    if key == "oneOf" {
+        let required = |branch: &Value| branch.get("required").and_then(|r| r.as_array()).cloned().unwrap_or_default();
+        let keep = required(&branches[0]);
+        if let Some(Value::Object(properties)) = merged.get_mut("properties") {
+            for name in branches[1..].iter().flat_map(required) {
+                if let Some(name) = name.as_str().filter(|_| !keep.contains(&name)) {
+                    properties.remove(name);
+                }
+            }
+        }
+    }
+
+    Value::Object(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "filename": { "type": "string" },
+                "copies": { "type": "integer", "minimum": 1 },
+                "network": { "type": "string", "enum": ["calibration", "mainnet"] },
+                "encrypt": { "type": "boolean", "default": true },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "owner": { "$ref": "#/$defs/address" }
+            },
+            "required": ["filename", "copies"],
+            "additionalProperties": false,
+            "$defs": {
+                "address": { "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$", "examples": ["0x0000000000000000000000000000000000000000"] }
+            }
+        })
+    }
+
+    #[test]
+    fn test_field_level_errors() {
+        let errors = validate(&upload_schema(), &json!({ "copies": "two", "colour": "red" }));
+        let shown: Vec<String> = errors.iter().map(ToString::to_string).collect();
+
+        assert_eq!(errors.len(), 3, "{:?}", shown);
+        assert!(shown.iter().any(|e| e.starts_with("arguments/copies:") && e.contains("integer")));
+        assert!(shown.iter().any(|e| e.starts_with("arguments:") && e.contains("filename")));
+        assert!(shown.iter().any(|e| e.starts_with("arguments:") && e.contains("colour")));
+
+        assert!(validate(&upload_schema(), &json!({ "filename": "a.txt", "copies": 2 })).is_empty());
+    }
+
+    #[test]
+    fn test_unusable_schema_is_skipped() {
+        assert!(validate(&json!({ "type": 12 }), &json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_argument_skeleton() {
+        let skeleton = argument_skeleton(&upload_schema());
+        assert_eq!(
+            skeleton,
+            json!({
+                "filename": "<string>",
+                "copies": 1,
+                "network": "calibration",
+                "encrypt": true,
+                "tags": ["<string>"],
+                "owner": "0x0000000000000000000000000000000000000000"
+            })
+        );
+
+        // A skeleton built from the schema passes it
+        assert!(validate(&upload_schema(), &skeleton).is_empty());
+    }
+
+    #[test]
+    fn test_skeleton_with_combinator_beside_properties() {
+        // pin_to_filecoin: exactly one of `cid` and `path`
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "cid": { "type": "string" },
+                "path": { "type": "string", "description": "File path on the machine running agent-cli" }
+            },
+            "oneOf": [{ "required": ["cid"] }, { "required": ["path"] }],
+            "additionalProperties": false
+        });
+        let skeleton = argument_skeleton(&schema);
+        assert_eq!(skeleton, json!({ "cid": "<string>" }));
+        assert!(validate(&schema, &skeleton).is_empty());
+
+        let schema = json!({
+            "properties": { "name": { "type": "string" } },
+            "allOf": [{ "properties": { "copies": { "type": "integer", "minimum": 1 } } }]
+        });
+        assert_eq!(argument_skeleton(&schema), json!({ "name": "<string>", "copies": 1 }));
+    }
+}
@@ -0,0 +1,791 @@
+//! agent-backend as an MCP server
+//!
+//! `AgentMCPServer` answers MCP JSON-RPC messages with tools backed by the
+//! contract services, the configured storage backend and the NFT scanner. `serve_stdio` and
+//! `serve_http` put it on the newline-delimited stdio transport and on
+//! Streamable HTTP respectively.
+
+use crate::config::Config;
+use crate::ipfs::IpfsClient;
+use crate::mcp::{
+    Implementation, InitializeResult, MCPContent, MCPError, MCPTool, MCPToolResult,
+    ServerCapabilities, ToolsCapability, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::services::contract_service::agent_metadata;
+use crate::services::nft_scanner::NFTScannerAgent;
+use crate::services::reputation_service::feedback_details;
+use crate::services::{AgentContractService, AgentReputationService, AgentValidationService};
+use crate::storage::{open_backend, StorageBackend, UriReader};
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+
+/// Path the HTTP transport serves MCP on
+pub const HTTP_PATH: &str = "/mcp";
+
+/// Retries per token when scanning NFT metadata
+const SCAN_MAX_RETRIES: u32 = 3;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// A JSON-RPC error response body
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+}
+
+/// Serves the agent registry, reputation, validation and storage flows as MCP tools
+pub struct AgentMCPServer {
+    tools: Vec<MCPTool>,
+    rpc_url: String,
+    contracts: AgentContractService,
+    reputation: AgentReputationService,
+    validation: AgentValidationService,
+    ipfs: IpfsClient,
+    /// Reads agent metadata from IPFS or Filecoin
+    reader: UriReader,
+    storage: Box<dyn StorageBackend>,
+    /// The only directory `pin_to_filecoin` may upload files from by path
+    upload_dir: Option<PathBuf>,
+}
+
+impl AgentMCPServer {
+    /// Build the server from configuration, loading the wallet signer once up front
+    pub async fn new(config: &Config) -> Result<Self> {
+        let signer = config.wallet.load_signer().context("Failed to load wallet signer")?;
+        let rpc_url = &config.network.rpc_url;
+
+        Ok(Self {
+            tools: tool_definitions(),
+            rpc_url: rpc_url.clone(),
+            contracts: AgentContractService::with_signer(rpc_url, &config.contracts.identity, signer.clone())?,
+            reputation: AgentReputationService::with_signer(rpc_url, &config.contracts.reputation, signer.clone())?,
+            validation: AgentValidationService::with_signer(rpc_url, &config.contracts.validation, signer)?,
+            ipfs: IpfsClient::new(&config.storage.ipfs_api, &config.storage.ipfs_gateway)?,
+            reader: UriReader::new(config)?,
+            storage: open_backend(config).await?,
+            upload_dir: None,
+        })
+    }
+
+    /// Let `pin_to_filecoin` upload files under `dir`; without it, uploads by
+    /// path are refused, since any client could otherwise read local files
+    pub fn with_upload_dir(mut self, dir: &Path) -> Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to read upload directory {}", dir.display()))?;
+        self.upload_dir = Some(dir);
+        Ok(self)
+    }
+
+    /// The tools this server offers
+    pub fn tools(&self) -> &[MCPTool] {
+        &self.tools
+    }
+
+    /// Handle one JSON-RPC message or batch, returning the response if one is due
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(batch) if batch.is_empty() => Some(error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, "Empty batch"),
+            )),
+            Value::Array(batch) => {
+                let responses = futures_util::future::join_all(
+                    batch.into_iter().map(|message| self.handle_single(message)),
+                )
+                .await;
+                let responses: Vec<Value> = responses.into_iter().flatten().collect();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            message => self.handle_single(message).await,
+        }
+    }
+
+    async fn handle_single(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned().filter(|id| !id.is_null());
+        let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+            // Responses are never expected, since this server sends no requests
+            return id.map(|id| error_response(id, RpcError::new(INVALID_REQUEST, "Missing method")));
+        };
+
+        let Some(id) = id else {
+            tracing::debug!("MCP notification: {}", method);
+            return None;
+        };
+
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+        Some(match self.dispatch(method, params).await {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tools })),
+            "tools/call" => self.call_tool(params).await,
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        }
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        // Speak the client's revision when we support it, otherwise offer ours
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+        let protocol_version = match requested {
+            Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => version,
+            _ => PROTOCOL_VERSION,
+        };
+
+        let result = InitializeResult {
+            protocol_version: protocol_version.to_string(),
+            capabilities: ServerCapabilities {
+                tools: Some(ToolsCapability::default()),
+                ..Default::default()
+            },
+            server_info: Implementation {
+                name: "agent-backend".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            instructions: Some(
+                "Register and query ERC-8004 agents, give feedback, request and submit \
+                 validations, pin data to Filecoin and scan NFT collections."
+                    .to_string(),
+            ),
+        };
+        serde_json::to_value(result).unwrap_or_default()
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, RpcError> {
+        let name = params.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.name == name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown tool: {}", name)))?;
+
+        if let Err(e) = tool.validate_arguments(&arguments) {
+            let data = match &e {
+                MCPError::InvalidArguments { errors, .. } => serde_json::to_value(errors).ok(),
+                _ => None,
+            };
+            return Err(RpcError { code: INVALID_PARAMS, message: e.to_string(), data });
+        }
+
+        tracing::info!("MCP tool call: {} {}", name, arguments);
+        let result = match self.run_tool(name, arguments).await {
+            Ok(output) => MCPToolResult {
+                content: vec![MCPContent::Text {
+                    text: serde_json::to_string_pretty(&output).unwrap_or_default(),
+                }],
+                is_error: false,
+                structured_content: Some(output),
+            },
+            // Tool failures go back to the model as results, not protocol errors
+            Err(e) => MCPToolResult {
+                content: vec![MCPContent::Text { text: format!("{:#}", e) }],
+                is_error: true,
+                structured_content: None,
+            },
+        };
+
+        serde_json::to_value(result).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+    }
+
+    async fn run_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+        match name {
+            "register_agent" => self.register_agent(parse_arguments(arguments)?).await,
+            "query_agent" => self.query_agent(parse_arguments(arguments)?).await,
+            "give_feedback" => self.give_feedback(parse_arguments(arguments)?).await,
+            "request_validation" => self.request_validation(parse_arguments(arguments)?).await,
+            "submit_validation" => self.submit_validation(parse_arguments(arguments)?).await,
+            "pin_to_filecoin" => self.pin_to_filecoin(parse_arguments(arguments)?).await,
+            "scan_nft_collection" => self.scan_nft_collection(parse_arguments(arguments)?).await,
+            _ => anyhow::bail!("Tool {} is not implemented", name),
+        }
+    }
+
+    async fn register_agent(&self, args: RegisterAgentArgs) -> Result<Value> {
+        let mut metadata = agent_metadata(&args.name, &args.description, &args.mcp_endpoint);
+        if let Some(image_cid) = &args.image_cid {
+            metadata["image"] = json!(format!("ipfs://{}", image_cid));
+        }
+
+        let metadata_uri = self.storage.uri(&self.storage.put_json(&metadata).await?);
+        let agent_id = self.contracts.register_agent(metadata_uri.clone()).await?;
+
+        Ok(json!({ "agent_id": agent_id, "metadata_uri": metadata_uri }))
+    }
+
+    async fn query_agent(&self, args: AgentIdArgs) -> Result<Value> {
+        let agent = self.contracts.get_agent_info(args.agent_id.to_string()).await?;
+
+        // Metadata is best effort; the on-chain record is the answer
+        let metadata = self.reader.get_json(&agent.metadata_uri).await.ok();
+
+        Ok(json!({
+            "agent_id": args.agent_id,
+            "owner": agent.owner.to_string(),
+            "metadata_uri": agent.metadata_uri,
+            "registered_at": agent.registered_at.to_string(),
+            "is_active": agent.is_active,
+            "metadata": metadata,
+        }))
+    }
+
+    async fn give_feedback(&self, args: FeedbackArgs) -> Result<Value> {
+        let file_uri = match &args.message {
+            Some(message) => self.storage.uri(&self.storage.put_json(&feedback_details(message)).await?),
+            None => String::new(),
+        };
+
+        let feedback_id = self
+            .reputation
+            .give_feedback(args.agent_id.to_string(), args.score, args.tags, file_uri.clone())
+            .await?;
+
+        Ok(json!({ "feedback_id": feedback_id, "file_uri": file_uri }))
+    }
+
+    async fn request_validation(&self, args: RequestValidationArgs) -> Result<Value> {
+        let request_hash = self
+            .validation
+            .request_validation(args.agent_id.to_string(), args.work_uri, args.validator)
+            .await?;
+
+        Ok(json!({ "request_hash": request_hash }))
+    }
+
+    async fn submit_validation(&self, args: SubmitValidationArgs) -> Result<Value> {
+        let transaction_hash = self
+            .validation
+            .submit_validation(args.request_hash, args.is_valid, args.proof_uri)
+            .await?;
+
+        Ok(json!({ "transaction_hash": transaction_hash }))
+    }
+
+    async fn pin_to_filecoin(&self, args: PinArgs) -> Result<Value> {
+        let cid = match (args.cid, args.path) {
+            (Some(cid), _) => {
+                self.storage.pin(&cid).await?;
+                cid
+            }
+            (None, Some(path)) => self.storage.put_file(&self.upload_path(&path)?).await?,
+            (None, None) => anyhow::bail!("Either cid or path is required"),
+        };
+
+        let uri = self.storage.uri(&cid);
+        let gateway_url = uri.starts_with("ipfs://").then(|| self.ipfs.gateway_url(&cid));
+        Ok(json!({ "cid": cid, "uri": uri, "storage": self.storage.name(), "gateway_url": gateway_url }))
+    }
+
+    /// Resolve a client-supplied path, which must lie inside the upload directory
+    fn upload_path(&self, path: &str) -> Result<PathBuf> {
+        let Some(dir) = &self.upload_dir else {
+            anyhow::bail!("Uploading by path is disabled; start the server with --upload-dir to allow it");
+        };
+
+        // Canonicalizing resolves `..` and symlinks before the check
+        let path = dir
+            .join(path)
+            .canonicalize()
+            .with_context(|| format!("Failed to read {}", path))?;
+        if !path.starts_with(dir) {
+            anyhow::bail!("{} is outside the upload directory", path.display());
+        }
+        Ok(path)
+    }
+
+    async fn scan_nft_collection(&self, args: ScanArgs) -> Result<Value> {
+        let address = args
+            .contract_address
+            .parse()
+            .context("Invalid contract address")?;
+        let rpc_url = args.rpc_url.as_deref().unwrap_or(&self.rpc_url);
+
+        let scanner = NFTScannerAgent::new(address, rpc_url, SCAN_MAX_RETRIES).await?;
+        let tokens = match args.token_ids {
+            Some(token_ids) => {
+                let mut tokens = Vec::with_capacity(token_ids.len());
+                for token_id in token_ids {
+                    tokens.push(scanner.get_token_metadata(token_id.into()).await?);
+                }
+                tokens
+            }
+            None => scanner.scan_nft_project().await?,
+        };
+
+        Ok(json!({
+            "contract_address": args.contract_address,
+            "count": tokens.len(),
+            "tokens": tokens,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct RegisterAgentArgs {
+    name: String,
+    description: String,
+    mcp_endpoint: String,
+    image_cid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AgentIdArgs {
+    agent_id: u64,
+}
+
+#[derive(Deserialize)]
+struct FeedbackArgs {
+    agent_id: u64,
+    score: u8,
+    #[serde(default)]
+    tags: Vec<String>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RequestValidationArgs {
+    agent_id: u64,
+    work_uri: String,
+    validator: String,
+}
+
+#[derive(Deserialize)]
+struct SubmitValidationArgs {
+    request_hash: String,
+    is_valid: bool,
+    #[serde(default)]
+    proof_uri: String,
+}
+
+#[derive(Deserialize)]
+struct PinArgs {
+    cid: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanArgs {
+    contract_address: String,
+    rpc_url: Option<String>,
+    token_ids: Option<Vec<u64>>,
+}
+
+fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).context("Invalid tool arguments")
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+fn tool(name: &str, description: &str, input_schema: Value) -> MCPTool {
+    MCPTool {
+        name: name.to_string(),
+        description: description.to_string(),
+        input_schema,
+    }
+}
+
+fn tool_definitions() -> Vec<MCPTool> {
+    let agent_id = json!({ "type": "integer", "minimum": 0, "description": "On-chain agent ID" });
+    let address = json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$" });
+
+    vec![
+        tool(
+            "register_agent",
+            "Upload agent metadata to IPFS and register the agent in the identity registry",
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "description": { "type": "string" },
+                    "mcp_endpoint": { "type": "string", "description": "URL of the agent's MCP server" },
+                    "image_cid": { "type": "string", "description": "CID of an image already on IPFS" }
+                },
+                "required": ["name", "description", "mcp_endpoint"],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "query_agent",
+            "Look up an agent's on-chain record and its IPFS metadata",
+            json!({
+                "type": "object",
+                "properties": { "agent_id": agent_id },
+                "required": ["agent_id"],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "give_feedback",
+            "Give an agent a 0-100 score, optionally with tags and a message stored on IPFS",
+            json!({
+                "type": "object",
+                "properties": {
+                    "agent_id": agent_id,
+                    "score": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "message": { "type": "string" }
+                },
+                "required": ["agent_id", "score"],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "request_validation",
+            "Ask a validator to check an agent's work",
+            json!({
+                "type": "object",
+                "properties": {
+                    "agent_id": agent_id,
+                    "work_uri": { "type": "string", "description": "URI of the work to validate" },
+                    "validator": address
+                },
+                "required": ["agent_id", "work_uri", "validator"],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "submit_validation",
+            "Submit a validation result as the validator assigned to a request",
+            json!({
+                "type": "object",
+                "properties": {
+                    "request_hash": { "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" },
+                    "is_valid": { "type": "boolean" },
+                    "proof_uri": { "type": "string" }
+                },
+                "required": ["request_hash", "is_valid"],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "pin_to_filecoin",
+            "Pin an existing CID, or upload a file from the server's disk, with the configured storage backend",
+            json!({
+                "type": "object",
+                "properties": {
+                    "cid": { "type": "string" },
+                    "path": { "type": "string", "description": "File path inside the server's upload directory, if one is configured" }
+                },
+                "oneOf": [{ "required": ["cid"] }, { "required": ["path"] }],
+                "additionalProperties": false
+            }),
+        ),
+        tool(
+            "scan_nft_collection",
+            "Read token metadata and IPFS resources from an ERC-721 or ERC-1155 collection",
+            json!({
+                "type": "object",
+                "properties": {
+                    "contract_address": address,
+                    "rpc_url": { "type": "string", "description": "RPC endpoint of the collection's chain; defaults to the configured network" },
+                    "token_ids": {
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 0 },
+                        "description": "Only scan these tokens instead of the whole collection"
+                    }
+                },
+                "required": ["contract_address"],
+                "additionalProperties": false
+            }),
+        ),
+    ]
+}
+
+/// Serve newline-delimited JSON-RPC on stdin and stdout until stdin closes
+pub async fn serve_stdio(server: Arc<AgentMCPServer>) -> Result<()> {
+    serve_streams(server, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Serve newline-delimited JSON-RPC over any pair of streams
+///
+/// Requests are handled concurrently; responses are written as they complete.
+pub async fn serve_streams<R, W>(server: Arc<AgentMCPServer>, reader: R, writer: W) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Send + Unpin + 'static,
+{
+    let writer = Arc::new(Mutex::new(writer));
+    let mut lines = BufReader::new(reader).lines();
+    let mut tasks = JoinSet::new();
+
+    while let Some(line) = lines.next_line().await.context("Failed to read from stdin")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let server = server.clone();
+        let writer = writer.clone();
+        tasks.spawn(async move {
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => server.handle_message(message).await,
+                Err(e) => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+            };
+            let Some(response) = response else {
+                return;
+            };
+
+            let mut writer = writer.lock().await;
+            let line = format!("{}\n", response);
+            if let Err(e) = writer.write_all(line.as_bytes()).await {
+                tracing::warn!("Failed to write MCP response: {}", e);
+            }
+            let _ = writer.flush().await;
+        });
+    }
+
+    // Let calls that are still running finish before exiting
+    while tasks.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Serve MCP over Streamable HTTP at `HTTP_PATH`
+///
+/// Responses are plain JSON; the server never opens an SSE stream, so `GET`
+/// is answered with 405 as the specification allows.
+pub async fn serve_http(server: Arc<AgentMCPServer>, listener: TcpListener) -> Result<()> {
+    let router = Router::new()
+        .route(HTTP_PATH, post(handle_http).get(|| async { StatusCode::METHOD_NOT_ALLOWED }))
+        .with_state(server);
+
+    tracing::info!("Serving MCP on http://{}{}", listener.local_addr()?, HTTP_PATH);
+    axum::serve(listener, router).await.context("MCP HTTP server failed")
+}
+
+async fn handle_http(
+    State(server): State<Arc<AgentMCPServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()));
+            return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+        }
+    };
+
+    match server.handle_message(message).await {
+        Some(response) => Json(response).into_response(),
+        // Only notifications or responses were posted
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// Browsers may only reach the server from local pages, guarding against DNS rebinding
+fn origin_allowed(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let Some(host) = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| reqwest::Url::parse(origin).ok())
+        .and_then(|url| url.host_str().map(str::to_string))
+    else {
+        return false;
+    };
+    matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::MCPHandler;
+    use crate::mcp_client::MCPClient;
+
+    async fn test_server() -> Arc<AgentMCPServer> {
+        let config: Config = toml::from_str(
+            r#"
+            [network]
+            name = "local"
+            rpc_url = "http://127.0.0.1:1"
+            chain_id = 31337
+
+            [contracts]
+            identity = "0x0000000000000000000000000000000000000001"
+            reputation = "0x0000000000000000000000000000000000000002"
+            validation = "0x0000000000000000000000000000000000000003"
+
+            [storage]
+            ipfs_api = "http://127.0.0.1:1"
+
+            [wallet]
+            address = "0x00"
+            signer = { type = "private_key", key = "0x0123456789012345678901234567890123456789012345678901234567890123" }
+            "#,
+        )
+        .unwrap();
+
+        Arc::new(AgentMCPServer::new(&config).await.unwrap())
+    }
+
+    fn connect_stdio(server: Arc<AgentMCPServer>) -> MCPClient {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, server_write) = tokio::io::split(server_io);
+
+        tokio::spawn(serve_streams(server, server_read, server_write));
+        MCPClient::from_streams(client_read, client_write)
+    }
+
+    #[tokio::test]
+    async fn test_stdio_handshake_and_tools() {
+        let client = connect_stdio(test_server().await);
+
+        let info = client.initialize().await.unwrap();
+        assert_eq!(info.server_info.name, "agent-backend");
+        assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+
+        let tools = client.list_tools().await.unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "register_agent",
+                "query_agent",
+                "give_feedback",
+                "request_validation",
+                "submit_validation",
+                "pin_to_filecoin",
+                "scan_nft_collection"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_are_rejected() {
+        let client = connect_stdio(test_server().await);
+        client.initialize().await.unwrap();
+
+        // Bypass client-side validation to exercise the server's
+        let err = client
+            .send_request("tools/call", json!({ "name": "give_feedback", "arguments": { "agent_id": 1, "score": 101 } }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("-32602"), "{}", err);
+        assert!(err.to_string().contains("arguments/score"), "{}", err);
+
+        let err = client
+            .send_request("tools/call", json!({ "name": "mint_money", "arguments": {} }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown tool"), "{}", err);
+
+        let err = client.send_request("resources/list", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("-32601"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_tool_failure_is_a_result() {
+        let client = connect_stdio(test_server().await);
+        client.initialize().await.unwrap();
+
+        // The IPFS node is unreachable, so the tool runs and reports the failure
+        let result = client.call_tool("pin_to_filecoin", json!({ "cid": "bafy" })).await.unwrap();
+        assert!(result.is_error);
+        assert!(result.text().contains("Failed to pin CID"), "{}", result.text());
+    }
+
+    #[tokio::test]
+    async fn test_upload_by_path_is_confined() {
+        let client = connect_stdio(test_server().await);
+        client.initialize().await.unwrap();
+        let result = client.call_tool("pin_to_filecoin", json!({ "path": "/etc/passwd" })).await.unwrap();
+        assert!(result.is_error);
+        assert!(result.text().contains("--upload-dir"), "{}", result.text());
+
+        let dir = std::env::temp_dir().join(format!("agent-cli-uploads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("card.json"), "{}").unwrap();
+        let server = Arc::into_inner(test_server().await).unwrap().with_upload_dir(&dir).unwrap();
+
+        assert!(server.upload_path("card.json").unwrap().starts_with(dir.canonicalize().unwrap()));
+        for outside in ["../", "/etc/passwd"] {
+            let err = server.upload_path(outside).unwrap_err();
+            assert!(err.to_string().contains("outside the upload directory"), "{}", err);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_and_notifications() {
+        let server = test_server().await;
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle_message(notification.clone()).await.is_none());
+
+        let batch = json!([
+            notification,
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            { "jsonrpc": "2.0", "id": 2, "method": "initialize", "params": { "protocolVersion": "2024-11-05" } }
+        ]);
+        let responses = server.handle_message(batch).await.unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 2);
+        assert_eq!(responses[1]["result"]["protocolVersion"], "2024-11-05");
+    }
+
+    #[tokio::test]
+    async fn test_http_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), HTTP_PATH);
+        tokio::spawn(serve_http(test_server().await, listener));
+
+        let handler = MCPHandler::new(url.clone(), 5);
+        let info = handler.initialize().await.unwrap();
+        assert_eq!(info.server_info.name, "agent-backend");
+        assert_eq!(handler.list_tools().await.unwrap().len(), 7);
+
+        let response = reqwest::Client::new()
+            .post(&url)
+            .header("Origin", "https://evil.example")
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+}
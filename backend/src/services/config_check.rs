@@ -0,0 +1,253 @@
+use crate::config::Config;
+use crate::contracts::{read_provider, AgentIdentity};
+use crate::filecoin::{LighthouseClient, Web3StorageClient};
+use crate::ipfs::IpfsClient;
+use alloy::primitives::{Address, FixedBytes};
+use alloy::providers::Provider;
+use anyhow::Result;
+use serde::Serialize;
+
+/// ERC-165 interfaces the identity registry must report as supported
+const IDENTITY_INTERFACES: [(&str, [u8; 4]); 3] = [
+    ("ERC165", [0x01, 0xff, 0xc9, 0xa7]),
+    ("ERC721", [0x80, 0xac, 0x58, 0xcd]),
+    ("ERC721Metadata", [0x5b, 0x5e, 0x13, 0x9f]),
+];
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not run, because it is not configured or an earlier check failed
+    Skip,
+}
+
+/// A single named check and what it found
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Pass, detail: detail.into() }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Fail, detail: detail.into() }
+    }
+
+    fn skip(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Skip, detail: detail.into() }
+    }
+
+    fn from_result(name: &str, result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Self::pass(name, detail),
+            Err(e) => Self::fail(name, format!("{:#}", e)),
+        }
+    }
+}
+
+/// Pass/fail report for a whole configuration
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConfigReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConfigReport {
+    /// Number of failed checks
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail).count()
+    }
+
+    /// True when no check failed
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+
+    fn status_of(&self, name: &str) -> Option<CheckStatus> {
+        self.checks.iter().find(|c| c.name == name).map(|c| c.status)
+    }
+}
+
+/// Run every live check against the configured network and storage services
+pub async fn check_config(config: &Config) -> ConfigReport {
+    let mut report = ConfigReport::default();
+
+    check_network(config, &mut report).await;
+    check_contracts(config, &mut report).await;
+    check_storage(config, &mut report).await;
+
+    report
+}
+
+async fn check_network(config: &Config, report: &mut ConfigReport) {
+    let result = async {
+        let chain_id = read_provider(&config.network.rpc_url)?.get_chain_id().await?;
+        if chain_id != config.network.chain_id {
+            anyhow::bail!(
+                "endpoint reports chain ID {}, configuration says {}",
+                chain_id,
+                config.network.chain_id
+            );
+        }
+        Ok(format!("{} (chain ID {})", config.network.rpc_url, chain_id))
+    }
+    .await;
+
+    report.checks.push(CheckResult::from_result("network.rpc_url", result));
+}
+
+async fn check_contracts(config: &Config, report: &mut ConfigReport) {
+    let rpc_ok = report.status_of("network.rpc_url") == Some(CheckStatus::Pass);
+    let contracts = [
+        ("contracts.identity", &config.contracts.identity),
+        ("contracts.reputation", &config.contracts.reputation),
+        ("contracts.validation", &config.contracts.validation),
+    ];
+
+    for (name, address) in contracts {
+        let address = match address.parse::<Address>() {
+            Ok(address) => address,
+            Err(e) => {
+                let detail = format!("invalid address {:?}: {}", address, e);
+                report.checks.push(CheckResult::fail(name, detail));
+                continue;
+            }
+        };
+
+        if !rpc_ok {
+            report.checks.push(CheckResult::skip(name, "RPC endpoint check failed"));
+            continue;
+        }
+
+        let result = async {
+            let code = read_provider(&config.network.rpc_url)?.get_code_at(address).await?;
+            if code.is_empty() {
+                anyhow::bail!("no contract deployed at {}", address);
+            }
+            Ok(format!("{} ({} bytes of bytecode)", address, code.len()))
+        }
+        .await;
+        report.checks.push(CheckResult::from_result(name, result));
+    }
+
+    let name = "contracts.identity.supportsInterface";
+    if report.status_of("contracts.identity") != Some(CheckStatus::Pass) {
+        report.checks.push(CheckResult::skip(name, "identity contract check failed"));
+        return;
+    }
+
+    let result = async {
+        let identity = AgentIdentity::new(
+            config.contracts.identity.parse()?,
+            read_provider(&config.network.rpc_url)?,
+        );
+
+        let mut missing = Vec::new();
+        for (interface, id) in IDENTITY_INTERFACES {
+            if !identity.supportsInterface(FixedBytes(id)).call().await? {
+                missing.push(interface);
+            }
+        }
+
+        if !missing.is_empty() {
+            anyhow::bail!("contract does not support {}", missing.join(", "));
+        }
+        let supported: Vec<_> = IDENTITY_INTERFACES.iter().map(|(name, _)| *name).collect();
+        Ok(supported.join(", "))
+    }
+    .await;
+    report.checks.push(CheckResult::from_result(name, result));
+}
+
+async fn check_storage(config: &Config, report: &mut ConfigReport) {
+    let storage = &config.storage;
+
+    let result = async {
+        let version = IpfsClient::new(&storage.ipfs_api, &storage.ipfs_gateway)?.ping().await?;
+        Ok(format!("{} (version {})", storage.ipfs_api, version))
+    }
+    .await;
+    report.checks.push(CheckResult::from_result("storage.ipfs_api", result));
+
+    let name = "storage.lighthouse_api_key";
+    if storage.lighthouse_api_key.is_empty() {
+        report.checks.push(CheckResult::skip(name, "not configured"));
+    } else {
+        let result = LighthouseClient::new(storage.lighthouse_api_key.clone())
+            .check_api_key()
+            .await
+            .map(|_| "accepted".to_string());
+        report.checks.push(CheckResult::from_result(name, result));
+    }
+
+    let name = "storage.web3_storage_token";
+    if storage.web3_storage_token.is_empty() {
+        report.checks.push(CheckResult::skip(name, "not configured"));
+    } else {
+        let result = Web3StorageClient::new(storage.web3_storage_token.clone())
+            .check_token()
+            .await
+            .map(|_| "accepted".to_string());
+        report.checks.push(CheckResult::from_result(name, result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable_config() -> Config {
+        toml::from_str(
+            r#"
+            [network]
+            name = "local"
+            rpc_url = "http://127.0.0.1:1"
+            chain_id = 31337
+
+            [contracts]
+            identity = "0x0000000000000000000000000000000000000001"
+            reputation = "not-an-address"
+            validation = "0x0000000000000000000000000000000000000003"
+
+            [storage]
+            ipfs_api = "http://127.0.0.1:1"
+
+            [wallet]
+            address = "0x00"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_erc165_interface_id() {
+        use alloy::sol_types::SolCall;
+
+        // The ERC-165 interface ID is the selector of supportsInterface itself
+        assert_eq!(AgentIdentity::supportsInterfaceCall::SELECTOR, IDENTITY_INTERFACES[0].1);
+    }
+
+    #[tokio::test]
+    async fn test_check_config_reports_failures() {
+        let report = check_config(&unreachable_config()).await;
+        let status = |name| report.status_of(name).unwrap();
+
+        assert_eq!(status("network.rpc_url"), CheckStatus::Fail);
+        assert_eq!(status("contracts.identity"), CheckStatus::Skip);
+        assert_eq!(status("contracts.reputation"), CheckStatus::Fail);
+        assert_eq!(status("contracts.identity.supportsInterface"), CheckStatus::Skip);
+        assert_eq!(status("storage.ipfs_api"), CheckStatus::Fail);
+        assert_eq!(status("storage.lighthouse_api_key"), CheckStatus::Skip);
+        assert_eq!(status("storage.web3_storage_token"), CheckStatus::Skip);
+
+        assert_eq!(report.failures(), 3);
+        assert!(!report.passed());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod config_check;
 pub mod contract_service;
+pub mod nft_scanner;
+pub mod reputation_service;
+pub mod validation_service;
 
-pub use contract_service::AgentContractService;
\ No newline at end of file
+pub use config_check::{check_config, CheckResult, CheckStatus, ConfigReport};
+pub use contract_service::{AgentContractService, ContractConfig};
+pub use reputation_service::{AgentReputationService, ReputationConfig, ReputationReport};
+pub use validation_service::{AgentValidationService, ValidationConfig};
@@ -1,15 +1,13 @@
-///! NFT Scanner Agent
-///!
-///! 职责:
-///! - 扫描 OpenSea/链上 NFT 项目
-///! - 提取所有 token metadata
-///! - 解析 IPFS 资源链接
-///! - 支持 ERC-721 和 ERC-1155
+//! NFT Scanner Agent
+//!
+//! 职责:
+//! - 扫描 OpenSea/链上 NFT 项目
+//! - 提取所有 token metadata
+//! - 解析 IPFS 资源链接
+//! - 支持 ERC-721 和 ERC-1155
 
 use anyhow::{anyhow, Context, Result};
 use ethers::{
-    abi::{Abi, Token},
-    contract::Contract,
     core::types::{Address, U256},
     providers::{Http, Middleware, Provider},
 };
@@ -279,7 +277,7 @@ impl NFTScannerAgent {
         let metadata_json = self.download_metadata(&metadata_uri).await?;
 
         // 解析 JSON
-        let mut metadata: serde_json::Value = serde_json::from_str(&metadata_json)
+        let metadata: serde_json::Value = serde_json::from_str(&metadata_json)
             .context("Failed to parse metadata JSON")?;
 
         // 提取字段
@@ -348,7 +346,6 @@ impl NFTScannerAgent {
             return Err(anyhow!("Invalid tokenURI response"));
         }
 
-        let offset = U256::from_big_endian(&result[0..32]).as_usize();
         let length = U256::from_big_endian(&result[32..64]).as_usize();
 
         if result.len() < 64 + length {
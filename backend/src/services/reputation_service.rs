@@ -0,0 +1,467 @@
+use crate::contracts::{
+    AgentReputation, AgentReputationClient, FeedbackInfo, FeedbackOptions, ReputationBreakdown,
+    ReputationSummary, ResponseOptions,
+};
+use alloy::primitives::U256;
+use crate::signer::SignerSource;
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Configuration for the reputation contract client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationConfig {
+    pub rpc_url: String,
+    pub signer: SignerSource,
+    pub reputation_address: String,
+}
+
+/// Number of feedback scores that fall into one histogram bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreBucket {
+    pub min: u8,
+    pub max: u8,
+    pub count: usize,
+}
+
+/// One point of an agent's reputation history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReputationPoint {
+    pub timestamp: u64,
+    pub score: u8,
+    pub running_average: f64,
+}
+
+/// A single feedback entry as shown in a reputation report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackEntry {
+    pub feedback_id: String,
+    pub from: String,
+    pub score: u8,
+    pub tags: Vec<String>,
+    pub file_uri: String,
+    pub timestamp: u64,
+    pub revoked: bool,
+    pub response: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Full reputation report for an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationReport {
+    pub agent_id: String,
+    pub avg_score: u64,
+    pub total_feedbacks: u64,
+    pub active_feedbacks: u64,
+    pub histogram: Vec<ScoreBucket>,
+    pub tags: BTreeMap<String, usize>,
+    pub time_series: Vec<ReputationPoint>,
+    pub feedbacks: Vec<FeedbackEntry>,
+}
+
+/// Bucket scores into ten ranges: 0-9, 10-19, ..., 80-89 and 90-100
+pub fn score_histogram(scores: &[u8]) -> Vec<ScoreBucket> {
+    let mut buckets: Vec<ScoreBucket> = (0..10u8)
+        .map(|i| ScoreBucket {
+            min: i * 10,
+            max: if i == 9 { 100 } else { i * 10 + 9 },
+            count: 0,
+        })
+        .collect();
+
+    for score in scores {
+        let index = usize::from((*score).min(100) / 10).min(9);
+        buckets[index].count += 1;
+    }
+
+    buckets
+}
+
+/// Count how often each tag appears across non-revoked feedback
+pub fn tag_frequencies(feedbacks: &[FeedbackEntry]) -> BTreeMap<String, usize> {
+    let mut tags = BTreeMap::new();
+    for feedback in feedbacks.iter().filter(|f| !f.revoked) {
+        for tag in &feedback.tags {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+    }
+    tags
+}
+
+/// Build a chronological score history with the running average at each point
+pub fn reputation_time_series(breakdown: &ReputationBreakdown) -> Vec<ReputationPoint> {
+    let mut points: Vec<(u64, u8)> = breakdown
+        .timestamps
+        .iter()
+        .zip(breakdown.scores.iter())
+        .map(|(timestamp, score)| (timestamp.saturating_to::<u64>(), *score))
+        .collect();
+    points.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let mut sum = 0u64;
+    points
+        .into_iter()
+        .enumerate()
+        .map(|(i, (timestamp, score))| {
+            sum += u64::from(score);
+            ReputationPoint {
+                timestamp,
+                score,
+                running_average: sum as f64 / (i + 1) as f64,
+            }
+        })
+        .collect()
+}
+
+/// Off-chain feedback details, uploaded to IPFS and referenced by `file_uri`
+pub fn feedback_details(message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "message": message,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Agent reputation service
+pub struct AgentReputationService {
+    reputation_client: Arc<RwLock<AgentReputationClient>>,
+}
+
+impl AgentReputationService {
+    /// Create a new agent reputation service
+    pub async fn new(config: ReputationConfig) -> Result<Self> {
+        let signer = config.signer.load()
+            .context("Failed to load wallet signer")?;
+
+        Self::with_signer(&config.rpc_url, &config.reputation_address, signer)
+    }
+
+    /// Create the service around an already loaded signer
+    pub fn with_signer(rpc_url: &str, reputation_address: &str, signer: PrivateKeySigner) -> Result<Self> {
+        let reputation_client = AgentReputationClient::with_signer(
+            reputation_address.parse()?,
+            rpc_url,
+            signer,
+        )
+        .context("Failed to create AgentReputation client")?;
+
+        Ok(Self {
+            reputation_client: Arc::new(RwLock::new(reputation_client)),
+        })
+    }
+
+    /// Create the service for read-only calls; no wallet is loaded
+    pub fn read_only(rpc_url: &str, reputation_address: &str) -> Result<Self> {
+        let reputation_client = AgentReputationClient::read_only(reputation_address.parse()?, rpc_url)
+            .context("Failed to create AgentReputation client")?;
+
+        Ok(Self {
+            reputation_client: Arc::new(RwLock::new(reputation_client)),
+        })
+    }
+
+    /// Give feedback to an agent, returning the new feedback ID
+    pub async fn give_feedback(
+        &self,
+        agent_id: String,
+        score: u8,
+        tags: Vec<String>,
+        file_uri: String,
+    ) -> Result<String> {
+        tracing::info!("Giving feedback to agent {} with score {}", agent_id, score);
+
+        let client = self.reputation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let options = FeedbackOptions {
+            agent_id: U256::from(agent_id),
+            score,
+            tags,
+            file_uri,
+        };
+
+        let receipt = client
+            .give_feedback(options)
+            .await
+            .context("Failed to give feedback")?;
+
+        let feedback_id = receipt
+            .decoded_log::<AgentReputation::FeedbackGiven>()
+            .map(|log| log.data.feedbackId)
+            .context("FeedbackGiven event not found in receipt")?;
+
+        tracing::info!("Feedback submitted successfully with ID: {}", feedback_id);
+        Ok(feedback_id.to_string())
+    }
+
+    /// Revoke feedback previously given by the current signer
+    pub async fn revoke_feedback(&self, feedback_id: String) -> Result<String> {
+        tracing::info!("Revoking feedback {}", feedback_id);
+
+        let client = self.reputation_client.read().await;
+        let feedback_id = feedback_id.parse::<u128>()
+            .context("Invalid feedback ID format")?;
+
+        let receipt = client
+            .revoke_feedback(U256::from(feedback_id))
+            .await
+            .context("Failed to revoke feedback")?;
+
+        tracing::info!("Feedback revoked successfully. Transaction hash: {:?}", receipt.transaction_hash);
+        Ok(format!("{:?}", receipt.transaction_hash))
+    }
+
+    /// Append the agent owner's response to a feedback entry
+    pub async fn append_response(&self, feedback_id: String, response: String) -> Result<String> {
+        tracing::info!("Appending response to feedback {}", feedback_id);
+
+        let client = self.reputation_client.read().await;
+        let feedback_id = feedback_id.parse::<u128>()
+            .context("Invalid feedback ID format")?;
+
+        let options = ResponseOptions {
+            feedback_id: U256::from(feedback_id),
+            response,
+        };
+
+        let receipt = client
+            .append_response(options)
+            .await
+            .context("Failed to append response")?;
+
+        tracing::info!("Response appended successfully. Transaction hash: {:?}", receipt.transaction_hash);
+        Ok(format!("{:?}", receipt.transaction_hash))
+    }
+
+    /// Get feedback information
+    pub async fn get_feedback(&self, feedback_id: String) -> Result<FeedbackInfo> {
+        tracing::debug!("Fetching feedback info for ID: {}", feedback_id);
+
+        let client = self.reputation_client.read().await;
+        let feedback_id = feedback_id.parse::<u128>()
+            .context("Invalid feedback ID format")?;
+
+        let feedback = client
+            .get_feedback(U256::from(feedback_id))
+            .await
+            .context("Failed to get feedback information")?;
+
+        Ok(feedback)
+    }
+
+    /// Get aggregated reputation for an agent
+    pub async fn get_reputation(&self, agent_id: String) -> Result<ReputationSummary> {
+        tracing::debug!("Fetching reputation for agent ID: {}", agent_id);
+
+        let client = self.reputation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let summary = client
+            .get_reputation(U256::from(agent_id))
+            .await
+            .context("Failed to get reputation")?;
+
+        Ok(summary)
+    }
+
+    /// Get scores and timestamps of all non-revoked feedback for an agent
+    pub async fn get_reputation_breakdown(&self, agent_id: String) -> Result<ReputationBreakdown> {
+        tracing::debug!("Fetching reputation breakdown for agent ID: {}", agent_id);
+
+        let client = self.reputation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let breakdown = client
+            .get_reputation_breakdown(U256::from(agent_id))
+            .await
+            .context("Failed to get reputation breakdown")?;
+
+        Ok(breakdown)
+    }
+
+    /// Get all feedback IDs for an agent
+    pub async fn get_agent_feedbacks(&self, agent_id: String) -> Result<Vec<String>> {
+        tracing::debug!("Fetching feedback IDs for agent ID: {}", agent_id);
+
+        let client = self.reputation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let feedback_ids = client
+            .get_agent_feedbacks(U256::from(agent_id))
+            .await
+            .context("Failed to get agent feedbacks")?;
+
+        let feedback_id_strings: Vec<String> = feedback_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+
+        Ok(feedback_id_strings)
+    }
+
+    /// Get the number of feedback entries for an agent
+    pub async fn get_feedback_count(&self, agent_id: String) -> Result<String> {
+        tracing::debug!("Fetching feedback count for agent ID: {}", agent_id);
+
+        let client = self.reputation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let count = client
+            .get_feedback_count(U256::from(agent_id))
+            .await
+            .context("Failed to get feedback count")?;
+
+        Ok(count.to_string())
+    }
+
+    /// Build a full reputation report for an agent: summary, score histogram,
+    /// tag frequencies, time series and every individual feedback entry
+    pub async fn get_reputation_report(&self, agent_id: String) -> Result<ReputationReport> {
+        tracing::debug!("Building reputation report for agent ID: {}", agent_id);
+
+        let client = self.reputation_client.read().await;
+        let agent_id_num = U256::from(
+            agent_id.parse::<u128>().context("Invalid agent ID format")?,
+        );
+
+        let summary = client
+            .get_reputation(agent_id_num)
+            .await
+            .context("Failed to get reputation")?;
+
+        let breakdown = client
+            .get_reputation_breakdown(agent_id_num)
+            .await
+            .context("Failed to get reputation breakdown")?;
+
+        let feedback_ids = client
+            .get_agent_feedbacks(agent_id_num)
+            .await
+            .context("Failed to get agent feedbacks")?;
+
+        let mut feedbacks = Vec::with_capacity(feedback_ids.len());
+        for feedback_id in feedback_ids {
+            let feedback = client
+                .get_feedback(feedback_id)
+                .await
+                .with_context(|| format!("Failed to get feedback {}", feedback_id))?;
+
+            feedbacks.push(FeedbackEntry {
+                feedback_id: feedback_id.to_string(),
+                from: feedback.from.to_string(),
+                score: feedback.score,
+                tags: feedback.tags,
+                file_uri: feedback.file_uri,
+                timestamp: feedback.timestamp.saturating_to::<u64>(),
+                revoked: feedback.revoked,
+                response: feedback.response,
+                message: None,
+            });
+        }
+
+        Ok(ReputationReport {
+            agent_id,
+            avg_score: summary.avg_score.saturating_to::<u64>(),
+            total_feedbacks: summary.total_feedbacks.saturating_to::<u64>(),
+            active_feedbacks: summary.active_feedbacks.saturating_to::<u64>(),
+            histogram: score_histogram(&breakdown.scores),
+            tags: tag_frequencies(&feedbacks),
+            time_series: reputation_time_series(&breakdown),
+            feedbacks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reputation_service_creation() {
+        let config = ReputationConfig {
+            rpc_url: "https://api.calibration.node.glif.io/rpc/v1".to_string(),
+            signer: SignerSource::PrivateKey {
+                key: "0x1234567890123456789012345678901234567890123456789012345678901234".to_string(),
+            },
+            reputation_address: "0x0000000000000000000000000000000000000000".to_string(),
+        };
+
+        let result = AgentReputationService::new(config).await;
+        assert!(result.is_ok());
+    }
+
+    fn entry(score: u8, tags: &[&str], revoked: bool) -> FeedbackEntry {
+        FeedbackEntry {
+            feedback_id: "1".to_string(),
+            from: "0x0000000000000000000000000000000000000000".to_string(),
+            score,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            file_uri: String::new(),
+            timestamp: 0,
+            revoked,
+            response: String::new(),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_score_histogram() {
+        let histogram = score_histogram(&[0, 9, 10, 55, 90, 99, 100]);
+
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(histogram[0].count, 2);
+        assert_eq!(histogram[1].count, 1);
+        assert_eq!(histogram[5].count, 1);
+        assert_eq!(histogram[9], ScoreBucket { min: 90, max: 100, count: 3 });
+    }
+
+    #[test]
+    fn test_tag_frequencies_skip_revoked() {
+        let feedbacks = vec![
+            entry(80, &["fast", "accurate"], false),
+            entry(60, &["fast"], false),
+            entry(10, &["slow"], true),
+        ];
+
+        let tags = tag_frequencies(&feedbacks);
+        assert_eq!(tags.get("fast"), Some(&2));
+        assert_eq!(tags.get("accurate"), Some(&1));
+        assert!(!tags.contains_key("slow"));
+    }
+
+    #[test]
+    fn test_reputation_time_series_is_sorted_with_running_average() {
+        let breakdown = ReputationBreakdown {
+            scores: vec![60, 100, 80],
+            timestamps: vec![U256::from(300), U256::from(100), U256::from(200)],
+        };
+
+        let series = reputation_time_series(&breakdown);
+        let timestamps: Vec<u64> = series.iter().map(|p| p.timestamp).collect();
+        assert_eq!(timestamps, vec![100, 200, 300]);
+        assert_eq!(series[0].running_average, 100.0);
+        assert_eq!(series[1].running_average, 90.0);
+        assert!((series[2].running_average - 80.0).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_reputation_service_rejects_bad_address() {
+        let config = ReputationConfig {
+            rpc_url: "https://api.calibration.node.glif.io/rpc/v1".to_string(),
+            signer: SignerSource::PrivateKey {
+                key: "0x1234567890123456789012345678901234567890123456789012345678901234".to_string(),
+            },
+            reputation_address: "not-an-address".to_string(),
+        };
+
+        assert!(AgentReputationService::new(config).await.is_err());
+    }
+}
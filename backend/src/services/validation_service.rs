@@ -0,0 +1,261 @@
+use crate::contracts::{
+    compute_request_hash, parse_request_hash, AgentValidation, AgentValidationClient,
+    ValidationRequest, ValidationRequestOptions, ValidationStats, ValidationStatus,
+    ValidationSubmitOptions,
+};
+use alloy::primitives::{Address, U256};
+use crate::signer::SignerSource;
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Configuration for the validation contract client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationConfig {
+    pub rpc_url: String,
+    pub signer: SignerSource,
+    pub validation_address: String,
+}
+
+/// Agent validation service
+pub struct AgentValidationService {
+    validation_client: Arc<RwLock<AgentValidationClient>>,
+}
+
+impl AgentValidationService {
+    /// Create a new agent validation service
+    pub async fn new(config: ValidationConfig) -> Result<Self> {
+        let signer = config.signer.load()
+            .context("Failed to load wallet signer")?;
+
+        Self::with_signer(&config.rpc_url, &config.validation_address, signer)
+    }
+
+    /// Create the service around an already loaded signer
+    pub fn with_signer(rpc_url: &str, validation_address: &str, signer: PrivateKeySigner) -> Result<Self> {
+        let validation_client = AgentValidationClient::with_signer(
+            validation_address.parse()?,
+            rpc_url,
+            signer,
+        )
+        .context("Failed to create AgentValidation client")?;
+
+        Ok(Self {
+            validation_client: Arc::new(RwLock::new(validation_client)),
+        })
+    }
+
+    /// Create the service for read-only calls; no wallet is loaded
+    pub fn read_only(rpc_url: &str, validation_address: &str) -> Result<Self> {
+        let validation_client = AgentValidationClient::read_only(validation_address.parse()?, rpc_url)
+            .context("Failed to create AgentValidation client")?;
+
+        Ok(Self {
+            validation_client: Arc::new(RwLock::new(validation_client)),
+        })
+    }
+
+    /// Request validation of an agent's work, returning the request hash
+    pub async fn request_validation(
+        &self,
+        agent_id: String,
+        work_uri: String,
+        validator: String,
+    ) -> Result<String> {
+        tracing::info!("Requesting validation for agent {} from {}", agent_id, validator);
+
+        let client = self.validation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+        let validator = validator.parse::<Address>()
+            .context("Invalid validator address")?;
+
+        let options = ValidationRequestOptions {
+            agent_id: U256::from(agent_id),
+            work_uri,
+            validator,
+        };
+
+        let receipt = client
+            .request_validation(options)
+            .await
+            .context("Failed to request validation")?;
+
+        let request_hash = receipt
+            .decoded_log::<AgentValidation::ValidationRequested>()
+            .map(|log| log.data.requestHash)
+            .context("ValidationRequested event not found in receipt")?;
+
+        tracing::info!("Validation requested successfully with hash: {}", request_hash);
+        Ok(request_hash.to_string())
+    }
+
+    /// Submit a validation result as the assigned validator
+    pub async fn submit_validation(
+        &self,
+        request_hash: String,
+        is_valid: bool,
+        proof_uri: String,
+    ) -> Result<String> {
+        tracing::info!("Submitting validation for request {}: valid={}", request_hash, is_valid);
+
+        let client = self.validation_client.read().await;
+        let options = ValidationSubmitOptions {
+            request_hash: parse_request_hash(&request_hash)?,
+            is_valid,
+            proof_uri,
+        };
+
+        let receipt = client
+            .submit_validation(options)
+            .await
+            .context("Failed to submit validation")?;
+
+        tracing::info!("Validation submitted successfully. Transaction hash: {:?}", receipt.transaction_hash);
+        Ok(format!("{:?}", receipt.transaction_hash))
+    }
+
+    /// Expire a pending validation request whose window has passed
+    pub async fn expire_validation(&self, request_hash: String) -> Result<String> {
+        tracing::info!("Expiring validation request {}", request_hash);
+
+        let client = self.validation_client.read().await;
+
+        let receipt = client
+            .expire_validation(parse_request_hash(&request_hash)?)
+            .await
+            .context("Failed to expire validation")?;
+
+        tracing::info!("Validation expired successfully. Transaction hash: {:?}", receipt.transaction_hash);
+        Ok(format!("{:?}", receipt.transaction_hash))
+    }
+
+    /// Get validation request information
+    pub async fn get_validation_request(&self, request_hash: String) -> Result<ValidationRequest> {
+        tracing::debug!("Fetching validation request: {}", request_hash);
+
+        let client = self.validation_client.read().await;
+
+        let request = client
+            .get_validation_request(parse_request_hash(&request_hash)?)
+            .await
+            .context("Failed to get validation request")?;
+
+        Ok(request)
+    }
+
+    /// Get the status and result of a validation request
+    pub async fn get_validation_status(&self, request_hash: String) -> Result<(ValidationStatus, bool)> {
+        tracing::debug!("Fetching validation status: {}", request_hash);
+
+        let client = self.validation_client.read().await;
+
+        let status = client
+            .get_validation_status(parse_request_hash(&request_hash)?)
+            .await
+            .context("Failed to get validation status")?;
+
+        Ok(status)
+    }
+
+    /// Get all validation request hashes for an agent
+    pub async fn get_agent_validations(&self, agent_id: String) -> Result<Vec<String>> {
+        tracing::debug!("Fetching validations for agent ID: {}", agent_id);
+
+        let client = self.validation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let request_hashes = client
+            .get_agent_validations(U256::from(agent_id))
+            .await
+            .context("Failed to get agent validations")?;
+
+        let request_hash_strings: Vec<String> = request_hashes
+            .iter()
+            .map(|hash| hash.to_string())
+            .collect();
+
+        Ok(request_hash_strings)
+    }
+
+    /// Get validation counters for an agent
+    pub async fn get_validation_stats(&self, agent_id: String) -> Result<ValidationStats> {
+        tracing::debug!("Fetching validation stats for agent ID: {}", agent_id);
+
+        let client = self.validation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+
+        let stats = client
+            .get_validation_stats(U256::from(agent_id))
+            .await
+            .context("Failed to get validation stats")?;
+
+        Ok(stats)
+    }
+
+    /// Predict the request hash the contract will assign when the current
+    /// signer requests validation with these parameters on `chain_id`
+    pub async fn predict_request_hash(
+        &self,
+        agent_id: String,
+        work_uri: &str,
+        validator: String,
+        chain_id: u64,
+    ) -> Result<String> {
+        let client = self.validation_client.read().await;
+        let agent_id = agent_id.parse::<u128>()
+            .context("Invalid agent ID format")?;
+        let validator = validator.parse::<Address>()
+            .context("Invalid validator address")?;
+
+        let request_hash = compute_request_hash(
+            U256::from(agent_id),
+            work_uri,
+            validator,
+            client.signer_address()?,
+            chain_id,
+        );
+
+        Ok(request_hash.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> ValidationConfig {
+        ValidationConfig {
+            rpc_url: "https://api.calibration.node.glif.io/rpc/v1".to_string(),
+            signer: SignerSource::PrivateKey {
+                key: "0x1234567890123456789012345678901234567890123456789012345678901234".to_string(),
+            },
+            validation_address: "0x0000000000000000000000000000000000000000".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validation_service_creation() {
+        let result = AgentValidationService::new(test_config()).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_predict_request_hash_uses_signer() {
+        let service = AgentValidationService::new(test_config()).await.unwrap();
+        let validator = Address::repeat_byte(0x11);
+
+        let predicted = service
+            .predict_request_hash("1".to_string(), "ipfs://work", validator.to_string(), 314159)
+            .await
+            .unwrap();
+
+        let signer = service.validation_client.read().await.signer_address().unwrap();
+        let expected = compute_request_hash(U256::from(1), "ipfs://work", validator, signer, 314159);
+        assert_eq!(predicted, expected.to_string());
+    }
+}
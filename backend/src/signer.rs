@@ -0,0 +1,334 @@
+use alloy::signers::local::{coins_bip39::English, MnemonicBuilder, PrivateKeySigner};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Default BIP-44 derivation path for the first Ethereum account
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// Where the wallet signing key comes from.
+///
+/// Secret values (`key`, `phrase`) may be written as `$VAR` to read them from
+/// the environment. They are never written back to a config file in
+/// plaintext; use a keystore or a `$VAR` reference instead.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignerSource {
+    /// Hex-encoded private key
+    PrivateKey {
+        #[serde(serialize_with = "serialize_secret")]
+        key: String,
+    },
+    /// Encrypted JSON keystore (Web3 Secret Storage)
+    Keystore {
+        path: PathBuf,
+        /// File holding the keystore password; prompts on the terminal if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password_file: Option<PathBuf>,
+    },
+    /// BIP-39 mnemonic phrase
+    Mnemonic {
+        #[serde(serialize_with = "serialize_secret")]
+        phrase: String,
+        #[serde(default = "default_derivation_path")]
+        derivation_path: String,
+    },
+}
+
+fn default_derivation_path() -> String {
+    DEFAULT_DERIVATION_PATH.to_string()
+}
+
+// Keep keys and phrases out of logs and error messages
+impl std::fmt::Debug for SignerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerSource::PrivateKey { key } => f
+                .debug_struct("PrivateKey")
+                .field("key", &redact(key))
+                .finish(),
+            SignerSource::Keystore { path, password_file } => f
+                .debug_struct("Keystore")
+                .field("path", path)
+                .field("password_file", password_file)
+                .finish(),
+            SignerSource::Mnemonic { phrase, derivation_path } => f
+                .debug_struct("Mnemonic")
+                .field("phrase", &redact(phrase))
+                .field("derivation_path", derivation_path)
+                .finish(),
+        }
+    }
+}
+
+/// Hide a secret value unless it is only a `$VAR` reference
+pub(crate) fn redact(value: &str) -> &str {
+    if value.is_empty() || value.starts_with('$') {
+        value
+    } else {
+        REDACTED
+    }
+}
+
+const REDACTED: &str = "<redacted>";
+
+/// Write a secret only if it is a `$VAR` reference or already redacted
+fn serialize_secret<S: serde::Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if redact(value) == value || value == REDACTED {
+        serializer.serialize_str(value)
+    } else {
+        Err(serde::ser::Error::custom(
+            "refusing to write a plaintext key; use a keystore or a $VAR reference",
+        ))
+    }
+}
+
+impl SignerSource {
+    /// A copy that is safe to print, with keys and phrases redacted
+    pub fn redacted(&self) -> SignerSource {
+        match self {
+            SignerSource::PrivateKey { key } => SignerSource::PrivateKey {
+                key: redact(key).to_string(),
+            },
+            SignerSource::Keystore { .. } => self.clone(),
+            SignerSource::Mnemonic { phrase, derivation_path } => SignerSource::Mnemonic {
+                phrase: redact(phrase).to_string(),
+                derivation_path: derivation_path.clone(),
+            },
+        }
+    }
+
+    /// Build the signer described by this source
+    pub fn load(&self) -> Result<PrivateKeySigner> {
+        match self {
+            SignerSource::PrivateKey { key } => parse_private_key(&resolve_env(key)?),
+            SignerSource::Keystore { path, password_file } => {
+                let path = expand_home(path);
+                let password = match password_file {
+                    Some(password_file) => {
+                        let password_file = expand_home(password_file);
+                        std::fs::read_to_string(&password_file)
+                            .with_context(|| {
+                                format!("Failed to read password file {}", password_file.display())
+                            })?
+                            .trim_end_matches(['\r', '\n'])
+                            .to_string()
+                    }
+                    None => rpassword::prompt_password(format!(
+                        "Password for keystore {}: ",
+                        path.display()
+                    ))
+                    .context("Failed to read keystore password")?,
+                };
+
+                PrivateKeySigner::decrypt_keystore(&path, password)
+                    .with_context(|| format!("Failed to decrypt keystore {}", path.display()))
+            }
+            SignerSource::Mnemonic { phrase, derivation_path } => {
+                let phrase = resolve_env(phrase)?;
+                MnemonicBuilder::<English>::default()
+                    .phrase(phrase.trim())
+                    .derivation_path(derivation_path)
+                    .context("Invalid derivation path")?
+                    .build()
+                    .context("Invalid mnemonic")
+            }
+        }
+    }
+}
+
+/// Encrypt a hex private key into a keystore file in `dir`, named after its address
+pub fn write_keystore(dir: &Path, key: &str, password: &str) -> Result<PathBuf> {
+    let signer = parse_private_key(key)?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let name = format!("{}.json", signer.address());
+    PrivateKeySigner::encrypt_keystore(
+        dir,
+        &mut rand::thread_rng(),
+        signer.credential().to_bytes(),
+        password,
+        Some(&name),
+    )
+    .context("Failed to write keystore")?;
+
+    Ok(dir.join(name))
+}
+
+/// Parse a hex private key, checking its format first so that typos get a clear error
+pub fn parse_private_key(key: &str) -> Result<PrivateKeySigner> {
+    let hex = key.trim();
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+
+    if hex.len() != 64 {
+        anyhow::bail!(
+            "Invalid private key: expected 64 hex characters (32 bytes), got {}",
+            hex.len()
+        );
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid private key: contains non-hex characters");
+    }
+
+    hex.parse::<PrivateKeySigner>().context("Invalid private key")
+}
+
+/// Resolve a `$VAR` reference from the environment; other values are returned as-is
+pub fn resolve_env(value: &str) -> Result<String> {
+    match value.strip_prefix('$') {
+        Some(var) => std::env::var(var)
+            .with_context(|| format!("Environment variable {} is not set", var)),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Expand a leading `~/` to the user's home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Well-known development mnemonic used by anvil and hardhat
+    const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+    const TEST_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const TEST_ADDRESS: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    #[test]
+    fn test_private_key_source() {
+        let source = SignerSource::PrivateKey { key: TEST_KEY.to_string() };
+        let signer = source.load().unwrap();
+        assert_eq!(signer.address().to_string(), TEST_ADDRESS);
+    }
+
+    #[test]
+    fn test_parse_private_key_format() {
+        let unprefixed = TEST_KEY.trim_start_matches("0x");
+        assert_eq!(parse_private_key(unprefixed).unwrap().address().to_string(), TEST_ADDRESS);
+
+        let short = parse_private_key("0x1234").unwrap_err();
+        assert!(short.to_string().contains("64 hex characters"));
+
+        let not_hex = format!("0x{}", "zz".repeat(32));
+        assert!(parse_private_key(&not_hex).unwrap_err().to_string().contains("non-hex"));
+
+        // The zero scalar has the right shape but is not a valid key
+        assert!(parse_private_key(&"0".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_private_key_from_env() {
+        std::env::set_var("AGENT_CLI_TEST_SIGNER_KEY", TEST_KEY);
+        let source = SignerSource::PrivateKey {
+            key: "$AGENT_CLI_TEST_SIGNER_KEY".to_string(),
+        };
+        assert_eq!(source.load().unwrap().address().to_string(), TEST_ADDRESS);
+
+        let missing = SignerSource::PrivateKey {
+            key: "$AGENT_CLI_TEST_SIGNER_MISSING".to_string(),
+        };
+        assert!(missing.load().is_err());
+    }
+
+    #[test]
+    fn test_mnemonic_source() {
+        let source = SignerSource::Mnemonic {
+            phrase: TEST_MNEMONIC.to_string(),
+            derivation_path: DEFAULT_DERIVATION_PATH.to_string(),
+        };
+        assert_eq!(source.load().unwrap().address().to_string(), TEST_ADDRESS);
+
+        let second = SignerSource::Mnemonic {
+            phrase: TEST_MNEMONIC.to_string(),
+            derivation_path: "m/44'/60'/0'/0/1".to_string(),
+        };
+        assert_ne!(second.load().unwrap().address().to_string(), TEST_ADDRESS);
+    }
+
+    #[test]
+    fn test_keystore_source_with_password_file() {
+        let dir = std::env::temp_dir().join(format!("agent-cli-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let key = alloy::hex::decode(TEST_KEY).unwrap();
+        PrivateKeySigner::encrypt_keystore(&dir, &mut rand::thread_rng(), key, "hunter2", Some("key.json"))
+            .unwrap();
+        std::fs::write(dir.join("password"), "hunter2\n").unwrap();
+
+        let source = SignerSource::Keystore {
+            path: dir.join("key.json"),
+            password_file: Some(dir.join("password")),
+        };
+        let signer = source.load();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(signer.unwrap().address().to_string(), TEST_ADDRESS);
+    }
+
+    #[test]
+    fn test_signer_source_toml() {
+        let source: SignerSource = toml::from_str(
+            r#"
+            type = "mnemonic"
+            phrase = "$AGENT_MNEMONIC"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            source,
+            SignerSource::Mnemonic {
+                phrase: "$AGENT_MNEMONIC".to_string(),
+                derivation_path: DEFAULT_DERIVATION_PATH.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_plaintext_secrets_are_not_serialized() {
+        let literal = SignerSource::PrivateKey { key: TEST_KEY.to_string() };
+        let err = toml::to_string(&literal).unwrap_err();
+        assert!(err.to_string().contains("refusing to write a plaintext key"), "{}", err);
+
+        let phrase = SignerSource::Mnemonic {
+            phrase: TEST_MNEMONIC.to_string(),
+            derivation_path: DEFAULT_DERIVATION_PATH.to_string(),
+        };
+        assert!(toml::to_string(&phrase).is_err());
+
+        // References and redacted copies are fine to write
+        let env = SignerSource::PrivateKey { key: "$MY_KEY".to_string() };
+        assert!(toml::to_string(&env).unwrap().contains("$MY_KEY"));
+        assert!(toml::to_string(&literal.redacted()).unwrap().contains("<redacted>"));
+    }
+
+    #[test]
+    fn test_write_keystore() {
+        let dir = std::env::temp_dir().join(format!("agent-cli-write-keystore-{}", std::process::id()));
+
+        let path = write_keystore(&dir, TEST_KEY, "hunter2").unwrap();
+        assert_eq!(path, dir.join(format!("{}.json", TEST_ADDRESS)));
+        assert!(!std::fs::read_to_string(&path).unwrap().contains(TEST_KEY.trim_start_matches("0x")));
+
+        let signer = PrivateKeySigner::decrypt_keystore(&path, "hunter2");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(signer.unwrap().address().to_string(), TEST_ADDRESS);
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let source = SignerSource::PrivateKey { key: TEST_KEY.to_string() };
+        assert!(!format!("{:?}", source).contains("ac0974"));
+
+        let env = SignerSource::PrivateKey { key: "$MY_KEY".to_string() };
+        assert!(format!("{:?}", env).contains("$MY_KEY"));
+    }
+}
@@ -0,0 +1,832 @@
+//! One interface over the places agent data is stored
+//!
+//! `StorageBackend` is implemented by `IpfsClient`, `LighthouseClient`,
+//! `Web3StorageClient` and `MCPClient` (for the Filecoin MCP server), and by
+//! `ReplicatedStorage`, which writes to several of them under a quorum.
+//! `open_backend` builds whatever the `[storage]` section asks for.
+
+use crate::config::{Config, QuorumAction, StorageBackendKind};
+use crate::filecoin::{LighthouseClient, Web3StorageClient};
+use crate::ipfs::IpfsClient;
+use crate::mcp_client::MCPClient;
+use crate::unixfs;
+use crate::upload::Progress;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// What a backend knows about a piece of content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageStatus {
+    pub cid: String,
+    /// Whether the backend is keeping the content
+    pub pinned: bool,
+    /// The backend's own description, e.g. its pin or deal state
+    pub detail: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// The backend has no equivalent of the operation, e.g. unpinning a Filecoin deal
+    #[error("{backend} does not support {operation}")]
+    Unsupported {
+        backend: &'static str,
+        operation: &'static str,
+    },
+    /// The backend stored something other than the bytes we sent
+    #[error("{backend} returned {returned}, which does not match the uploaded bytes")]
+    CidMismatch {
+        backend: &'static str,
+        returned: String,
+    },
+}
+
+impl StorageError {
+    pub(crate) fn unsupported(backend: &'static str, operation: &'static str) -> anyhow::Error {
+        StorageError::Unsupported { backend, operation }.into()
+    }
+}
+
+/// Check the root a backend returned for a CAR against the CAR's own
+pub(crate) fn verify_car_root(backend: &'static str, car: &Path, cid: String) -> Result<String> {
+    let root = crate::car::root(car)?;
+    let returned = cid::Cid::try_from(cid.as_str()).with_context(|| format!("{} is not a valid CID", cid))?;
+    if returned.hash() == root.hash() {
+        Ok(cid)
+    } else {
+        Err(StorageError::CidMismatch { backend, returned: cid }.into())
+    }
+}
+
+/// Check the CID a backend returned for `data` against one computed locally
+pub(crate) fn verify_upload(backend: &'static str, data: &[u8], cid: String) -> Result<String> {
+    if unixfs::matches(data, &cid)? {
+        Ok(cid)
+    } else {
+        Err(StorageError::CidMismatch { backend, returned: cid }.into())
+    }
+}
+
+/// Content-addressed storage: put bytes in, get a CID back
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Short name used in messages, e.g. `lighthouse`
+    fn name(&self) -> &'static str;
+
+    /// Store `data` and return its CID
+    async fn put(&self, data: &[u8], filename: &str) -> Result<String>;
+
+    /// Fetch the content behind `cid`
+    async fn get(&self, cid: &str) -> Result<Vec<u8>>;
+
+    /// Keep content that is already on the network
+    async fn pin(&self, cid: &str) -> Result<()>;
+
+    /// Stop keeping `cid`
+    async fn unpin(&self, cid: &str) -> Result<()>;
+
+    async fn status(&self, cid: &str) -> Result<StorageStatus>;
+
+    /// Remove `cid` from the backend entirely
+    async fn delete(&self, cid: &str) -> Result<()>;
+
+    /// Store a JSON document
+    async fn put_json(&self, data: &Value) -> Result<String> {
+        self.put(&serde_json::to_vec(data)?, "data.json").await
+    }
+
+    /// Store the contents of a file under its file name
+    async fn put_file(&self, path: &Path) -> Result<String> {
+        self.put_file_with_progress(path, Progress::none()).await
+    }
+
+    /// Like `put_file`, reporting bytes sent to `progress`
+    ///
+    /// Backends that can stream read the file while sending it; this default
+    /// reads it into memory first.
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        let data = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+
+        let cid = self.put(&data, filename).await?;
+        progress.report(data.len() as u64, data.len() as u64);
+        Ok(cid)
+    }
+
+    /// Store the DAG in a CAR file as-is and return its root CID
+    async fn put_car(&self, _path: &Path, _progress: Progress) -> Result<String> {
+        Err(StorageError::unsupported(self.name(), "CAR upload"))
+    }
+
+    /// The URI to record for `cid`, e.g. in agent metadata
+    fn uri(&self, cid: &str) -> String {
+        format!("ipfs://{}", cid)
+    }
+
+    /// Fetch and parse a JSON document
+    async fn get_json(&self, cid: &str) -> Result<Value> {
+        let data = self.get(cid).await?;
+        serde_json::from_slice(&data).with_context(|| format!("{} is not JSON", cid))
+    }
+
+    /// Which backends hold `cid`, for backends that replicate uploads
+    fn replication(&self, _cid: &str) -> Option<ReplicationReport> {
+        None
+    }
+}
+
+/// Open the backend selected by the `[storage]` section
+pub async fn open_backend(config: &Config) -> Result<Box<dyn StorageBackend>> {
+    let backend: Box<dyn StorageBackend> = match &config.storage.replication {
+        Some(replication) => {
+            let quorum = replication.quorum()?;
+            let mut backends = Vec::with_capacity(replication.backends.len());
+            for kind in &replication.backends {
+                backends.push(open_kind(config, *kind).await?);
+            }
+            Box::new(ReplicatedStorage::new(backends, quorum, replication.below_quorum))
+        }
+        None => open_kind(config, config.storage.backend_kind()).await?,
+    };
+
+    tracing::debug!("Using {} storage backend", backend.name());
+    Ok(backend)
+}
+
+async fn open_kind(config: &Config, kind: StorageBackendKind) -> Result<Box<dyn StorageBackend>> {
+    let storage = &config.storage;
+
+    let backend: Box<dyn StorageBackend> = match kind {
+        StorageBackendKind::Ipfs => Box::new(IpfsClient::new(&storage.ipfs_api, &storage.ipfs_gateway)?),
+        StorageBackendKind::Lighthouse => {
+            if storage.lighthouse_api_key.is_empty() {
+                anyhow::bail!("storage.backend is \"lighthouse\" but no Lighthouse API key is configured");
+            }
+            Box::new(LighthouseClient::new(storage.lighthouse_api_key.clone()))
+        }
+        StorageBackendKind::Web3Storage => {
+            if storage.web3_storage_token.is_empty() {
+                anyhow::bail!("storage.backend is \"web3_storage\" but no Web3.Storage token is configured");
+            }
+            Box::new(Web3StorageClient::new(storage.web3_storage_token.clone()))
+        }
+        StorageBackendKind::Mcp => {
+            let name = storage
+                .mcp_server
+                .as_deref()
+                .context("storage.backend is \"mcp\" but storage.mcp_server is not set")?;
+            Box::new(MCPClient::spawn(name, config.mcp.server(name)?).await?)
+        }
+    };
+
+    Ok(backend)
+}
+
+/// Split a URI recorded by `StorageBackend::uri` into the kind of backend
+/// that serves it and the CID
+pub fn parse_uri(uri: &str) -> Option<(StorageBackendKind, &str)> {
+    if let Some(cid) = uri.strip_prefix("ipfs://") {
+        Some((StorageBackendKind::Ipfs, cid))
+    } else {
+        uri.strip_prefix("filecoin://").map(|cid| (StorageBackendKind::Mcp, cid))
+    }
+}
+
+/// Reads documents back from the URIs the backends record
+///
+/// `ipfs://` URIs are read from the IPFS node and `filecoin://` URIs through
+/// the Filecoin MCP server, which is only started when one is first needed.
+pub struct UriReader {
+    config: Config,
+    ipfs: IpfsClient,
+    filecoin: tokio::sync::OnceCell<Box<dyn StorageBackend>>,
+}
+
+impl UriReader {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            ipfs: IpfsClient::new(&config.storage.ipfs_api, &config.storage.ipfs_gateway)?,
+            filecoin: tokio::sync::OnceCell::new(),
+        })
+    }
+
+    pub async fn get_json(&self, uri: &str) -> Result<Value> {
+        match parse_uri(uri) {
+            Some((StorageBackendKind::Mcp, cid)) => {
+                let filecoin = self
+                    .filecoin
+                    .get_or_try_init(|| open_kind(&self.config, StorageBackendKind::Mcp))
+                    .await
+                    .with_context(|| format!("Cannot read {} without the Filecoin MCP server", uri))?;
+                filecoin.get_json(cid).await
+            }
+            Some((_, cid)) => self.ipfs.get_json(cid).await,
+            None => anyhow::bail!("Unsupported URI: {}", uri),
+        }
+    }
+}
+
+/// One backend's part in a replicated upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Replica {
+    pub backend: String,
+    /// The CID the backend returned, already checked against the uploaded
+    /// bytes; backends may address the same bytes differently, e.g. CIDv0
+    /// from IPFS and CIDv1 from Web3.Storage
+    pub cid: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Where a replicated upload ended up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReplicationReport {
+    /// The CID to record, from the first backend that stored the upload
+    pub cid: String,
+    pub quorum: usize,
+    pub replicas: Vec<Replica>,
+}
+
+impl ReplicationReport {
+    /// Backends that stored the upload
+    pub fn holders(&self) -> Vec<&str> {
+        self.replicas
+            .iter()
+            .filter(|replica| replica.cid.is_some())
+            .map(|replica| replica.backend.as_str())
+            .collect()
+    }
+
+    pub fn met_quorum(&self) -> bool {
+        self.holders().len() >= self.quorum
+    }
+
+    /// Why each backend that does not hold the upload failed
+    pub fn problems(&self) -> Vec<String> {
+        self.replicas
+            .iter()
+            .filter_map(|replica| {
+                let error = replica.error.as_ref()?;
+                Some(format!("{}: {}", replica.backend, error))
+            })
+            .collect()
+    }
+}
+
+/// Writes every upload to several backends and requires a quorum of them to succeed
+///
+/// Each backend checks the CID it returns against the uploaded bytes, so every
+/// success counts even when the backends' CIDs differ. The report keeps each
+/// backend's own CID, and later operations on the recorded CID are passed on
+/// to each backend under its own.
+pub struct ReplicatedStorage {
+    backends: Vec<Box<dyn StorageBackend>>,
+    quorum: usize,
+    below_quorum: QuorumAction,
+    reports: Mutex<HashMap<String, ReplicationReport>>,
+}
+
+impl ReplicatedStorage {
+    pub fn new(backends: Vec<Box<dyn StorageBackend>>, quorum: usize, below_quorum: QuorumAction) -> Self {
+        Self {
+            backends,
+            quorum,
+            below_quorum,
+            reports: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Store `data` on every backend at once and report which of them hold it
+    pub async fn put_replicated(&self, data: &[u8], filename: &str) -> Result<ReplicationReport> {
+        self.replicate(filename, |_, backend| backend.put(data, filename)).await
+    }
+
+    /// Stream a file to every backend at once
+    ///
+    /// `progress` sees the bytes sent to all backends together, out of the
+    /// file size times the number of backends.
+    pub async fn put_file_replicated(&self, path: &Path, progress: Progress) -> Result<ReplicationReport> {
+        let shares = self.share_progress(&progress);
+        self.replicate(&path.display().to_string(), |i, backend| {
+            backend.put_file_with_progress(path, shares[i].clone())
+        })
+        .await
+    }
+
+    /// Upload a CAR to every backend at once, with progress as for `put_file_replicated`
+    pub async fn put_car_replicated(&self, path: &Path, progress: Progress) -> Result<ReplicationReport> {
+        let shares = self.share_progress(&progress);
+        self.replicate(&path.display().to_string(), |i, backend| backend.put_car(path, shares[i].clone()))
+            .await
+    }
+
+    /// One `Progress` per backend, which together report to `progress`
+    fn share_progress(&self, progress: &Progress) -> Vec<Progress> {
+        let sent: Arc<Vec<AtomicU64>> = Arc::new(self.backends.iter().map(|_| AtomicU64::new(0)).collect());
+        let copies = self.backends.len() as u64;
+
+        (0..self.backends.len())
+            .map(|i| {
+                let sent = sent.clone();
+                let progress = progress.clone();
+                Progress::new(move |bytes, total| {
+                    sent[i].store(bytes, Ordering::Relaxed);
+                    let all = sent.iter().map(|s| s.load(Ordering::Relaxed)).sum();
+                    progress.report(all, total * copies);
+                })
+            })
+            .collect()
+    }
+
+    async fn replicate<'a, F, Fut>(&'a self, what: &str, put: F) -> Result<ReplicationReport>
+    where
+        F: Fn(usize, &'a dyn StorageBackend) -> Fut,
+        Fut: std::future::Future<Output = Result<String>>,
+    {
+        let results = futures_util::future::join_all(
+            self.backends.iter().enumerate().map(|(i, backend)| put(i, backend.as_ref())),
+        )
+        .await;
+
+        let replicas: Vec<Replica> = self
+            .backends
+            .iter()
+            .zip(results)
+            .map(|(backend, result)| match result {
+                Ok(cid) => Replica { backend: backend.name().to_string(), cid: Some(cid), error: None },
+                Err(e) => Replica { backend: backend.name().to_string(), cid: None, error: Some(format!("{:#}", e)) },
+            })
+            .collect();
+
+        let Some(cid) = replicas.iter().find_map(|r| r.cid.clone()) else {
+            let errors: Vec<_> = replicas
+                .iter()
+                .map(|r| format!("{}: {}", r.backend, r.error.as_deref().unwrap_or_default()))
+                .collect();
+            anyhow::bail!("No backend stored {}: {}", what, errors.join("; "));
+        };
+
+        let report = ReplicationReport { cid, quorum: self.quorum, replicas };
+        for problem in report.problems() {
+            tracing::warn!("Replica of {} missing on {}", report.cid, problem);
+        }
+        self.check_quorum(&report.cid, report.holders().len(), self.backends.len(), &report.problems())?;
+
+        tracing::info!("Stored {} on {}", report.cid, report.holders().join(", "));
+        self.reports.lock().unwrap().insert(report.cid.clone(), report.clone());
+        Ok(report)
+    }
+
+    /// Check `succeeded` of the `attempted` backends against the quorum, which
+    /// is capped at `attempted` when some backends could not take part
+    fn check_quorum(&self, cid: &str, succeeded: usize, attempted: usize, problems: &[String]) -> Result<()> {
+        let required = self.quorum.min(attempted);
+        if succeeded >= required {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Only {} of {} backends hold {}, {} required ({})",
+            succeeded,
+            attempted,
+            cid,
+            required,
+            problems.join("; ")
+        );
+        match self.below_quorum {
+            QuorumAction::Fail => anyhow::bail!(message),
+            QuorumAction::Warn => {
+                tracing::warn!("{}", message);
+                Ok(())
+            }
+        }
+    }
+
+    /// The CID backend `index` stored the upload recorded as `cid` under
+    fn backend_cid(&self, index: usize, cid: &str) -> String {
+        self.reports
+            .lock()
+            .unwrap()
+            .get(cid)
+            .and_then(|report| report.replicas.get(index)?.cid.clone())
+            .unwrap_or_else(|| cid.to_string())
+    }
+
+    /// Run `operation` on every backend with its own CID for `cid`
+    async fn for_each<'a, F, Fut, T>(&'a self, cid: &str, operation: F) -> Vec<Result<T>>
+    where
+        F: Fn(&'a dyn StorageBackend, String) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let cids: Vec<String> = (0..self.backends.len()).map(|i| self.backend_cid(i, cid)).collect();
+        futures_util::future::join_all(
+            self.backends.iter().zip(cids).map(|(backend, cid)| operation(backend.as_ref(), cid)),
+        )
+        .await
+    }
+
+    /// Run `operation` on every backend, skipping those that do not support it
+    async fn on_all<'a, F, Fut>(&'a self, cid: &str, operation: F) -> Result<()>
+    where
+        F: Fn(&'a dyn StorageBackend, String) -> Fut,
+        Fut: std::future::Future<Output = Result<()>>,
+    {
+        let results = self.for_each(cid, operation).await;
+
+        let errors: Vec<String> = self
+            .backends
+            .iter()
+            .zip(results)
+            .filter_map(|(backend, result)| match result {
+                Err(e) if e.downcast_ref::<StorageError>().is_none() => {
+                    Some(format!("{}: {:#}", backend.name(), e))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if !errors.is_empty() {
+            anyhow::bail!("{}", errors.join("; "));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for ReplicatedStorage {
+    fn name(&self) -> &'static str {
+        "replicated"
+    }
+
+    async fn put(&self, data: &[u8], filename: &str) -> Result<String> {
+        Ok(self.put_replicated(data, filename).await?.cid)
+    }
+
+    async fn put_file_with_progress(&self, path: &Path, progress: Progress) -> Result<String> {
+        Ok(self.put_file_replicated(path, progress).await?.cid)
+    }
+
+    async fn put_car(&self, path: &Path, progress: Progress) -> Result<String> {
+        Ok(self.put_car_replicated(path, progress).await?.cid)
+    }
+
+    /// Read from the first backend that can serve `cid`
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        let mut errors = Vec::new();
+        for (i, backend) in self.backends.iter().enumerate() {
+            match backend.get(&self.backend_cid(i, cid)).await {
+                Ok(data) => return Ok(data),
+                Err(e) => errors.push(format!("{}: {:#}", backend.name(), e)),
+            }
+        }
+        anyhow::bail!("No backend could serve {}: {}", cid, errors.join("; "))
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        let results = self.for_each(cid, |backend, cid| async move { backend.pin(&cid).await }).await;
+
+        // Backends that cannot pin neither count for nor against the quorum
+        let mut supported = 0;
+        let mut problems = Vec::new();
+        for (backend, result) in self.backends.iter().zip(results) {
+            match result {
+                Err(e) if e.downcast_ref::<StorageError>().is_some() => continue,
+                Err(e) => problems.push(format!("{}: {:#}", backend.name(), e)),
+                Ok(()) => {}
+            }
+            supported += 1;
+        }
+
+        if supported == 0 {
+            return Err(StorageError::unsupported(self.name(), "pin"));
+        }
+        self.check_quorum(cid, supported - problems.len(), supported, &problems)
+    }
+
+    async fn unpin(&self, cid: &str) -> Result<()> {
+        self.on_all(cid, |backend, cid| async move { backend.unpin(&cid).await }).await
+    }
+
+    /// Pinned when at least a quorum of backends report the CID pinned
+    async fn status(&self, cid: &str) -> Result<StorageStatus> {
+        let results = self.for_each(cid, |backend, cid| async move { backend.status(&cid).await }).await;
+
+        let mut pinned = 0;
+        let mut details = Vec::new();
+        for (backend, result) in self.backends.iter().zip(results) {
+            match result {
+                Ok(status) => {
+                    pinned += usize::from(status.pinned);
+                    details.push(format!("{}: {}", backend.name(), status.detail));
+                }
+                Err(e) => details.push(format!("{}: {:#}", backend.name(), e)),
+            }
+        }
+
+        Ok(StorageStatus {
+            cid: cid.to_string(),
+            pinned: pinned >= self.quorum,
+            detail: format!("{} of {} backends pinned; {}", pinned, self.backends.len(), details.join("; ")),
+        })
+    }
+
+    async fn delete(&self, cid: &str) -> Result<()> {
+        self.on_all(cid, |backend, cid| async move { backend.delete(&cid).await }).await?;
+        self.reports.lock().unwrap().remove(cid);
+        Ok(())
+    }
+
+    fn uri(&self, cid: &str) -> String {
+        self.backends[0].uri(cid)
+    }
+
+    fn replication(&self, cid: &str) -> Option<ReplicationReport> {
+        self.reports.lock().unwrap().get(cid).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory backend exercising the provided methods
+    #[derive(Default)]
+    struct MemoryBackend {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for MemoryBackend {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        async fn put(&self, data: &[u8], filename: &str) -> Result<String> {
+            let cid = format!("{}-{}", filename, data.len());
+            self.objects.lock().unwrap().insert(cid.clone(), data.to_vec());
+            Ok(cid)
+        }
+
+        async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+            self.objects.lock().unwrap().get(cid).cloned().context("not found")
+        }
+
+        async fn pin(&self, _cid: &str) -> Result<()> {
+            Err(StorageError::unsupported(self.name(), "pin"))
+        }
+
+        async fn unpin(&self, _cid: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn status(&self, cid: &str) -> Result<StorageStatus> {
+            let pinned = self.objects.lock().unwrap().contains_key(cid);
+            Ok(StorageStatus { cid: cid.to_string(), pinned, detail: String::new() })
+        }
+
+        async fn delete(&self, cid: &str) -> Result<()> {
+            self.objects.lock().unwrap().remove(cid);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provided_methods() {
+        let backend = MemoryBackend::default();
+
+        let cid = backend.put_json(&serde_json::json!({ "name": "agent" })).await.unwrap();
+        assert!(cid.starts_with("data.json-"));
+        assert_eq!(backend.get_json(&cid).await.unwrap()["name"], "agent");
+
+        let dir = std::env::temp_dir().join(format!("storage-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("avatar.png");
+        std::fs::write(&path, b"png").unwrap();
+        assert_eq!(backend.put_file(&path).await.unwrap(), "avatar.png-3");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        backend.delete(&cid).await.unwrap();
+        assert!(!backend.status(&cid).await.unwrap().pinned);
+
+        let err = backend.pin(&cid).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(StorageError::Unsupported { operation: "pin", .. })));
+        assert_eq!(err.to_string(), "memory does not support pin");
+    }
+
+    /// Backend that stores under a fixed CID, or always fails
+    struct Fixed {
+        name: &'static str,
+        cid: Result<&'static str, &'static str>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for Fixed {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn put(&self, _data: &[u8], _filename: &str) -> Result<String> {
+            self.cid.map(str::to_string).map_err(anyhow::Error::msg)
+        }
+
+        async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+            self.holds(cid).map(|()| cid.as_bytes().to_vec())
+        }
+
+        async fn pin(&self, cid: &str) -> Result<()> {
+            self.holds(cid)
+        }
+
+        async fn unpin(&self, _cid: &str) -> Result<()> {
+            Err(StorageError::unsupported(self.name, "unpin"))
+        }
+
+        async fn status(&self, cid: &str) -> Result<StorageStatus> {
+            Ok(StorageStatus { cid: cid.to_string(), pinned: self.holds(cid).is_ok(), detail: String::new() })
+        }
+
+        async fn delete(&self, cid: &str) -> Result<()> {
+            self.holds(cid)
+        }
+    }
+
+    impl Fixed {
+        /// Only the backend's own CID is known to it
+        fn holds(&self, cid: &str) -> Result<()> {
+            match self.cid {
+                Ok(own) if own == cid => Ok(()),
+                Ok(own) => anyhow::bail!("asked for {} but holds {}", cid, own),
+                Err(e) => anyhow::bail!("{}", e),
+            }
+        }
+    }
+
+    fn replicated(
+        backends: Vec<(&'static str, Result<&'static str, &'static str>)>,
+        quorum: usize,
+        below_quorum: QuorumAction,
+    ) -> ReplicatedStorage {
+        let backends = backends
+            .into_iter()
+            .map(|(name, cid)| Box::new(Fixed { name, cid }) as Box<dyn StorageBackend>)
+            .collect();
+        ReplicatedStorage::new(backends, quorum, below_quorum)
+    }
+
+    #[tokio::test]
+    async fn test_replication_quorum() {
+        let storage = replicated(
+            vec![("ipfs", Ok("bafy1")), ("lighthouse", Err("rate limited")), ("web3.storage", Ok("bafy1"))],
+            2,
+            QuorumAction::Fail,
+        );
+
+        let report = storage.put_replicated(b"{}", "data.json").await.unwrap();
+        assert_eq!(report.cid, "bafy1");
+        assert_eq!(report.holders(), ["ipfs", "web3.storage"]);
+        assert_eq!(report.problems(), ["lighthouse: rate limited"]);
+        assert_eq!(storage.replication("bafy1"), Some(report));
+
+        storage.pin("bafy1").await.unwrap();
+        assert!(storage.status("bafy1").await.unwrap().pinned);
+        assert_eq!(storage.get("bafy1").await.unwrap(), b"bafy1");
+
+        // Unsupported operations are skipped, real failures are not
+        storage.unpin("bafy1").await.unwrap();
+        let err = storage.delete("bafy1").await.unwrap_err();
+        assert_eq!(err.to_string(), "lighthouse: rate limited");
+    }
+
+    #[tokio::test]
+    async fn test_replicated_pin_skips_unsupported_backends() {
+        let backends: Vec<Box<dyn StorageBackend>> = vec![
+            Box::new(Fixed { name: "ipfs", cid: Ok("bafy1") }),
+            Box::new(Fixed { name: "lighthouse", cid: Ok("bafy1") }),
+            Box::new(MemoryBackend::default()),
+        ];
+        let storage = ReplicatedStorage::new(backends, 3, QuorumAction::Fail);
+        assert_eq!(storage.put_replicated(b"data", "data.txt").await.unwrap().holders().len(), 3);
+
+        // Memory cannot pin, so both backends that can must
+        storage.pin("bafy1").await.unwrap();
+
+        let backends: Vec<Box<dyn StorageBackend>> = vec![
+            Box::new(Fixed { name: "ipfs", cid: Err("rate limited") }),
+            Box::new(MemoryBackend::default()),
+        ];
+        let storage = ReplicatedStorage::new(backends, 1, QuorumAction::Fail);
+        let err = storage.pin("bafy1").await.unwrap_err();
+        assert_eq!(err.to_string(), "Only 0 of 1 backends hold bafy1, 1 required (ipfs: rate limited)");
+
+        let storage = ReplicatedStorage::new(vec![Box::new(MemoryBackend::default())], 1, QuorumAction::Fail);
+        let err = storage.pin("bafy1").await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(StorageError::Unsupported { operation: "pin", .. })));
+    }
+
+    #[tokio::test]
+    async fn test_replication_with_differing_cids() {
+        // IPFS answers with CIDv0 and Web3.Storage with CIDv1 of a different
+        // DAG; both checked the bytes, so both hold the upload
+        let storage = replicated(vec![("ipfs", Ok("Qm1")), ("web3.storage", Ok("bafy1"))], 2, QuorumAction::Fail);
+
+        let report = storage.put_replicated(b"data", "data.txt").await.unwrap();
+        assert_eq!(report.cid, "Qm1");
+        assert_eq!(report.holders(), ["ipfs", "web3.storage"]);
+        assert!(report.problems().is_empty());
+        assert_eq!(report.replicas[1].cid.as_deref(), Some("bafy1"));
+
+        // Each backend is asked about the upload under its own CID
+        storage.pin("Qm1").await.unwrap();
+        let status = storage.status("Qm1").await.unwrap();
+        assert!(status.pinned, "{}", status.detail);
+        storage.delete("Qm1").await.unwrap();
+        assert_eq!(storage.replication("Qm1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_replicated_file_progress() {
+        let storage = replicated(vec![("ipfs", Ok("bafy1")), ("lighthouse", Ok("bafy1"))], 2, QuorumAction::Fail);
+
+        let path = std::env::temp_dir().join(format!("storage-progress-{}.txt", std::process::id()));
+        std::fs::write(&path, b"data").unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reports = reports.clone();
+            Progress::new(move |sent, total| reports.lock().unwrap().push((sent, total)))
+        };
+
+        // Progress covers both copies of the file
+        assert_eq!(storage.put_file_with_progress(&path, progress).await.unwrap(), "bafy1");
+        assert_eq!(*reports.lock().unwrap(), vec![(4, 8), (8, 8)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replication_total_failure() {
+        let storage = replicated(vec![("ipfs", Err("offline")), ("lighthouse", Err("bad key"))], 1, QuorumAction::Warn);
+
+        let err = storage.put(b"data", "data.txt").await.unwrap_err();
+        assert_eq!(err.to_string(), "No backend stored data.txt: ipfs: offline; lighthouse: bad key");
+
+        let err = storage.get("bafy1").await.unwrap_err();
+        assert!(err.to_string().starts_with("No backend could serve bafy1"));
+    }
+
+    #[test]
+    fn test_parse_uri() {
+        assert_eq!(parse_uri("ipfs://Qm1"), Some((StorageBackendKind::Ipfs, "Qm1")));
+        assert_eq!(parse_uri("filecoin://baga1"), Some((StorageBackendKind::Mcp, "baga1")));
+        assert_eq!(parse_uri("https://example.com/agent.json"), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_configured_backend() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [network]
+            name = "local"
+            rpc_url = "http://127.0.0.1:8545"
+            chain_id = 31337
+
+            [contracts]
+            identity = "0x0000000000000000000000000000000000000001"
+            reputation = "0x0000000000000000000000000000000000000002"
+            validation = "0x0000000000000000000000000000000000000003"
+
+            [storage]
+
+            [wallet]
+            address = "0x00"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(open_backend(&config).await.unwrap().name(), "ipfs");
+
+        config.storage.lighthouse_api_key = "key".to_string();
+        assert_eq!(open_backend(&config).await.unwrap().name(), "lighthouse");
+
+        config.storage.backend = Some(StorageBackendKind::Web3Storage);
+        let err = open_backend(&config).await.err().unwrap();
+        assert!(err.to_string().contains("no Web3.Storage token"));
+
+        config.storage.backend = Some(StorageBackendKind::Mcp);
+        let err = open_backend(&config).await.err().unwrap();
+        assert!(err.to_string().contains("storage.mcp_server"));
+
+        // Replication takes over from `backend`
+        config.storage.replication = Some(crate::config::ReplicationConfig {
+            backends: vec![StorageBackendKind::Ipfs, StorageBackendKind::Lighthouse],
+            quorum: Some(1),
+            below_quorum: QuorumAction::Fail,
+        });
+        assert_eq!(open_backend(&config).await.unwrap().name(), "replicated");
+    }
+}
@@ -0,0 +1,495 @@
+//! Local UnixFS CID computation
+//!
+//! Rebuilds the DAG that IPFS, Lighthouse and Web3.Storage build for a file,
+//! so a CID can be known before uploading and checked after. Only files are
+//! covered (no directory wrapping), chunked at a fixed size with a balanced
+//! layout and sha2-256 hashes, which is what all three services use by default.
+
+use anyhow::{Context, Result};
+use cid::multihash::Multihash;
+use cid::{Cid, Version};
+use sha2::{Digest, Sha256};
+
+const SHA2_256: u64 = 0x12;
+pub(crate) const DAG_PB: u64 = 0x70;
+pub(crate) const RAW: u64 = 0x55;
+
+/// UnixFS `Data.Type` values
+pub(crate) const UNIXFS_DIRECTORY: u64 = 1;
+pub(crate) const UNIXFS_FILE: u64 = 2;
+
+/// How a file is cut up and linked together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidProfile {
+    pub version: Version,
+    /// Bytes per leaf
+    pub chunk_size: usize,
+    /// Children per intermediate node
+    pub max_links: usize,
+    /// Store leaves as raw blocks instead of UnixFS nodes (CIDv1 only)
+    pub raw_leaves: bool,
+}
+
+impl CidProfile {
+    /// `ipfs add` defaults, also used by Lighthouse
+    pub const KUBO_V0: CidProfile = CidProfile {
+        version: Version::V0,
+        chunk_size: 256 * 1024,
+        max_links: 174,
+        raw_leaves: false,
+    };
+
+    /// `ipfs add --cid-version=1`
+    pub const KUBO_V1: CidProfile = CidProfile {
+        version: Version::V1,
+        chunk_size: 256 * 1024,
+        max_links: 174,
+        raw_leaves: true,
+    };
+
+    /// The Web3.Storage upload client: 1 MiB chunks, 1024 links per node
+    pub const WEB3_STORAGE: CidProfile = CidProfile {
+        version: Version::V1,
+        chunk_size: 1024 * 1024,
+        max_links: 1024,
+        raw_leaves: true,
+    };
+
+    /// Every profile a service might have used
+    pub const ALL: [CidProfile; 3] = [Self::KUBO_V0, Self::KUBO_V1, Self::WEB3_STORAGE];
+}
+
+/// An encoded node and its CID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub cid: Cid,
+    pub data: Vec<u8>,
+}
+
+/// A node already in the DAG, as its parent links to it
+#[derive(Debug, Clone)]
+pub(crate) struct Link {
+    pub cid: Cid,
+    /// Bytes of this node and everything below it
+    pub tsize: u64,
+    /// File bytes under this node
+    filesize: u64,
+}
+
+/// Computes a file's CID from its bytes, fed in any number of pieces
+pub struct CidBuilder {
+    profile: CidProfile,
+    buffer: Vec<u8>,
+    leaves: Vec<Link>,
+    /// Encoded blocks not yet taken, when the caller wants them
+    blocks: Option<Vec<Block>>,
+}
+
+impl CidBuilder {
+    pub fn new(profile: CidProfile) -> Self {
+        Self {
+            profile,
+            buffer: Vec::with_capacity(profile.chunk_size),
+            leaves: Vec::new(),
+            blocks: None,
+        }
+    }
+
+    /// Keep the encoded blocks as well as the CIDs, e.g. to write a CAR
+    pub fn keep_blocks(mut self) -> Self {
+        self.blocks = Some(Vec::new());
+        self
+    }
+
+    /// Blocks encoded since the last call, if `keep_blocks` was set
+    pub fn take_blocks(&mut self) -> Vec<Block> {
+        self.blocks.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (self.profile.chunk_size - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.buffer.len() == self.profile.chunk_size {
+                self.flush_leaf();
+            }
+        }
+    }
+
+    /// The root CID of everything passed to `update`
+    pub fn finish(self) -> Cid {
+        self.finish_link().0.cid
+    }
+
+    /// The root CID and the blocks not yet taken, including the root's
+    pub fn finish_blocks(self) -> (Cid, Vec<Block>) {
+        let (root, blocks) = self.finish_link();
+        (root.cid, blocks)
+    }
+
+    pub(crate) fn finish_link(mut self) -> (Link, Vec<Block>) {
+        // An empty file is still one (empty) leaf
+        if !self.buffer.is_empty() || self.leaves.is_empty() {
+            self.flush_leaf();
+        }
+
+        let mut level = std::mem::take(&mut self.leaves);
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(self.profile.max_links));
+            for children in level.chunks(self.profile.max_links) {
+                let (link, node) = parent(&self.profile, children);
+                self.keep(&link.cid, node);
+                next.push(link);
+            }
+            level = next;
+        }
+
+        let blocks = self.take_blocks();
+        (level.remove(0), blocks)
+    }
+
+    fn keep(&mut self, cid: &Cid, data: Vec<u8>) {
+        if let Some(blocks) = &mut self.blocks {
+            blocks.push(Block { cid: *cid, data });
+        }
+    }
+
+    fn flush_leaf(&mut self) {
+        let chunk = std::mem::take(&mut self.buffer);
+        let filesize = chunk.len() as u64;
+
+        let (link, block) = if self.profile.raw_leaves {
+            let link = Link {
+                cid: Cid::new_v1(RAW, sha256(&chunk)),
+                tsize: filesize,
+                filesize,
+            };
+            (link, chunk)
+        } else {
+            let mut unixfs = Vec::new();
+            put_varint_field(&mut unixfs, 1, UNIXFS_FILE);
+            if !chunk.is_empty() {
+                put_bytes_field(&mut unixfs, 2, &chunk);
+            }
+            put_varint_field(&mut unixfs, 3, filesize);
+
+            let mut node = Vec::new();
+            put_bytes_field(&mut node, 1, &unixfs);
+
+            let link = Link {
+                cid: dag_pb_cid(&self.profile, &node),
+                tsize: node.len() as u64,
+                filesize,
+            };
+            (link, node)
+        };
+
+        self.keep(&link.cid, block);
+        self.leaves.push(link);
+        self.buffer.reserve(self.profile.chunk_size);
+    }
+}
+
+/// Compute the CID `data` gets when added under `profile`
+pub fn compute_cid(data: &[u8], profile: CidProfile) -> Cid {
+    let mut builder = CidBuilder::new(profile);
+    builder.update(data);
+    builder.finish()
+}
+
+/// Whether `cid` is what one of the known profiles produces for `data`
+pub fn matches(data: &[u8], cid: &str) -> Result<bool> {
+    let mut verifier = CidVerifier::new();
+    verifier.update(data);
+    verifier.matches(cid)
+}
+
+/// Builds the CID under every known profile at once, for checking a CID
+/// returned by a service that might have used any of them
+pub struct CidVerifier {
+    builders: Vec<CidBuilder>,
+}
+
+impl CidVerifier {
+    pub fn new() -> Self {
+        Self {
+            builders: CidProfile::ALL.into_iter().map(CidBuilder::new).collect(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for builder in &mut self.builders {
+            builder.update(data);
+        }
+    }
+
+    /// Whether `cid` is the root of the bytes seen so far under any profile
+    ///
+    /// CIDv0 and CIDv1 of the same DAG are treated as equal, since gateways and
+    /// APIs are free to report either.
+    pub fn matches(self, cid: &str) -> Result<bool> {
+        let cid = Cid::try_from(cid).with_context(|| format!("{} is not a valid CID", cid))?;
+
+        Ok(self
+            .builders
+            .into_iter()
+            .any(|builder| builder.finish().hash() == cid.hash()))
+    }
+}
+
+impl Default for CidVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A file node over `children`, and its encoding
+fn parent(profile: &CidProfile, children: &[Link]) -> (Link, Vec<u8>) {
+    let filesize = children.iter().map(|c| c.filesize).sum();
+
+    let mut unixfs = Vec::new();
+    put_varint_field(&mut unixfs, 1, UNIXFS_FILE);
+    put_varint_field(&mut unixfs, 3, filesize);
+    for child in children {
+        put_varint_field(&mut unixfs, 4, child.filesize);
+    }
+
+    let node = encode_node(children.iter().map(|c| ("", c)), &unixfs);
+    let link = Link {
+        cid: dag_pb_cid(profile, &node),
+        tsize: node.len() as u64 + children.iter().map(|c| c.tsize).sum::<u64>(),
+        filesize,
+    };
+    (link, node)
+}
+
+/// A directory node over named entries, which are sorted by name first
+///
+/// Large directories are not sharded into a HAMT the way `ipfs add -r`
+/// shards them, so their CIDs will differ from IPFS's.
+pub(crate) fn directory(profile: &CidProfile, mut entries: Vec<(String, Link)>) -> (Link, Block) {
+    entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let mut unixfs = Vec::new();
+    put_varint_field(&mut unixfs, 1, UNIXFS_DIRECTORY);
+
+    let node = encode_node(entries.iter().map(|(name, link)| (name.as_str(), link)), &unixfs);
+    let cid = dag_pb_cid(profile, &node);
+    let link = Link {
+        cid,
+        tsize: node.len() as u64 + entries.iter().map(|(_, l)| l.tsize).sum::<u64>(),
+        filesize: 0,
+    };
+    (link, Block { cid, data: node })
+}
+
+fn encode_node<'a>(links: impl Iterator<Item = (&'a str, &'a Link)>, unixfs: &[u8]) -> Vec<u8> {
+    // dag-pb puts links before data
+    let mut node = Vec::new();
+    for (name, child) in links {
+        let mut link = Vec::new();
+        put_bytes_field(&mut link, 1, &child.cid.to_bytes());
+        put_bytes_field(&mut link, 2, name.as_bytes());
+        put_varint_field(&mut link, 3, child.tsize);
+        put_bytes_field(&mut node, 2, &link);
+    }
+    put_bytes_field(&mut node, 1, unixfs);
+    node
+}
+
+/// A decoded dag-pb node with its UnixFS data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Node {
+    pub links: Vec<(String, Cid)>,
+    /// UnixFS `Data.Type`
+    pub kind: u64,
+    /// File bytes held in the node itself
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn decode_node(block: &[u8]) -> Result<Node> {
+    let mut links = Vec::new();
+    let mut unixfs = None;
+    for field in fields(block) {
+        match field? {
+            (1, Field::Bytes(data)) => unixfs = Some(data),
+            (2, Field::Bytes(link)) => {
+                let mut hash = None;
+                let mut name = String::new();
+                for field in fields(link) {
+                    match field? {
+                        (1, Field::Bytes(bytes)) => hash = Some(Cid::try_from(bytes)?),
+                        (2, Field::Bytes(bytes)) => name = String::from_utf8(bytes.to_vec())?,
+                        _ => {}
+                    }
+                }
+                links.push((name, hash.context("dag-pb link has no hash")?));
+            }
+            _ => {}
+        }
+    }
+
+    let mut kind = None;
+    let mut data = Vec::new();
+    for field in fields(unixfs.context("dag-pb node has no UnixFS data")?) {
+        match field? {
+            (1, Field::Varint(value)) => kind = Some(value),
+            (2, Field::Bytes(bytes)) => data = bytes.to_vec(),
+            _ => {}
+        }
+    }
+
+    Ok(Node {
+        links,
+        kind: kind.context("UnixFS data has no type")?,
+        data,
+    })
+}
+
+/// Check that `data` hashes to `cid`
+pub(crate) fn check_block(cid: &Cid, data: &[u8]) -> Result<()> {
+    if cid.hash().code() != SHA2_256 {
+        anyhow::bail!("{} uses an unsupported hash function", cid);
+    }
+    if *cid.hash() != sha256(data) {
+        anyhow::bail!("Block {} does not match its contents", cid);
+    }
+    Ok(())
+}
+
+fn sha256(data: &[u8]) -> Multihash<64> {
+    Multihash::wrap(SHA2_256, &Sha256::digest(data)).expect("sha2-256 digest fits")
+}
+
+fn dag_pb_cid(profile: &CidProfile, node: &[u8]) -> Cid {
+    let hash = sha256(node);
+    match profile.version {
+        Version::V0 => Cid::new_v0(hash).expect("dag-pb sha2-256 is a valid CIDv0"),
+        Version::V1 => Cid::new_v1(DAG_PB, hash),
+    }
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Iterate over the `(field number, value)` pairs of a protobuf message
+fn fields(mut buf: &[u8]) -> impl Iterator<Item = Result<(u64, Field<'_>)>> {
+    std::iter::from_fn(move || {
+        if buf.is_empty() {
+            return None;
+        }
+        let field = next_field(&mut buf);
+        if field.is_err() {
+            buf = &[];
+        }
+        Some(field)
+    })
+}
+
+fn next_field<'a>(buf: &mut &'a [u8]) -> Result<(u64, Field<'a>)> {
+    let key = read_varint(buf)?;
+    let field = match key & 7 {
+        0 => Field::Varint(read_varint(buf)?),
+        2 => {
+            let len = read_varint(buf)? as usize;
+            anyhow::ensure!(len <= buf.len(), "Truncated protobuf field");
+            let (value, rest) = buf.split_at(len);
+            *buf = rest;
+            Field::Bytes(value)
+        }
+        wire => anyhow::bail!("Unsupported protobuf wire type {}", wire),
+    };
+    Ok((key >> 3, field))
+}
+
+pub(crate) fn read_varint(buf: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().context("Truncated varint")?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("Varint is too long")
+}
+
+pub(crate) fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    put_varint(buf, field << 3);
+    put_varint(buf, value);
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u64, value: &[u8]) {
+    put_varint(buf, field << 3 | 2);
+    put_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_cids() {
+        assert_eq!(
+            compute_cid(b"", CidProfile::KUBO_V0).to_string(),
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+        );
+        assert_eq!(
+            compute_cid(b"hello world\n", CidProfile::KUBO_V0).to_string(),
+            "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+        );
+        assert_eq!(
+            compute_cid(b"hello world", CidProfile::KUBO_V1).to_string(),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+        assert_eq!(
+            compute_cid(b"", CidProfile::WEB3_STORAGE).to_string(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+    }
+
+    #[test]
+    fn test_chunked_file() {
+        let data: Vec<u8> = (0..600 * 1024).map(|i| (i % 251) as u8).collect();
+
+        // Three 256 KiB leaves under one dag-pb root
+        let v1 = compute_cid(&data, CidProfile::KUBO_V1);
+        assert_eq!(v1.codec(), DAG_PB);
+        assert_ne!(v1, compute_cid(&data[..256 * 1024], CidProfile::KUBO_V1));
+
+        // Fits in a single Web3.Storage chunk, so the root is the raw leaf
+        assert_eq!(compute_cid(&data, CidProfile::WEB3_STORAGE).codec(), RAW);
+
+        // Feeding the bytes in pieces gives the same root
+        let mut builder = CidBuilder::new(CidProfile::KUBO_V0);
+        for piece in data.chunks(1000) {
+            builder.update(piece);
+        }
+        assert_eq!(builder.finish(), compute_cid(&data, CidProfile::KUBO_V0));
+    }
+
+    #[test]
+    fn test_matches() {
+        let v0 = compute_cid(b"hello world\n", CidProfile::KUBO_V0);
+        let v1 = Cid::new_v1(DAG_PB, *v0.hash());
+
+        assert!(matches(b"hello world\n", &v0.to_string()).unwrap());
+        assert!(matches(b"hello world\n", &v1.to_string()).unwrap());
+        assert!(!matches(b"hello world", &v0.to_string()).unwrap());
+        assert!(matches(b"hello world", "not-a-cid").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Streaming file uploads
+//!
+//! Files are read from disk while they are being sent rather than loaded into
+//! memory first. `UploadReader` hashes everything that passes through it, so
+//! the CID a backend returns can still be checked locally, and reports
+//! progress as it goes.
+
+use crate::storage::StorageError;
+use crate::unixfs::CidVerifier;
+use anyhow::{Context, Result};
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context as TaskContext, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Bytes read from disk per chunk of a streamed HTTP body
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Receives `(bytes sent, total bytes)` as an upload proceeds
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<dyn Fn(u64, u64) + Send + Sync>>);
+
+impl Progress {
+    pub fn new(report: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(report)))
+    }
+
+    /// Progress that nobody is watching
+    pub fn none() -> Self {
+        Self(None)
+    }
+
+    pub fn report(&self, sent: u64, total: u64) {
+        if let Some(report) = &self.0 {
+            report(sent, total);
+        }
+    }
+}
+
+/// A file opened for upload
+pub struct FileUpload {
+    pub file_name: String,
+    pub len: u64,
+    reader: UploadReader,
+}
+
+impl FileUpload {
+    pub async fn open(path: &Path, progress: Progress) -> Result<Self> {
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let len = file
+            .metadata()
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+            .to_string();
+
+        Ok(Self {
+            file_name,
+            len,
+            reader: UploadReader {
+                file,
+                len,
+                sent: 0,
+                cids: Arc::new(Mutex::new(CidVerifier::new())),
+                progress,
+            },
+        })
+    }
+
+    /// Handle for checking the returned CID once the upload has finished
+    pub fn check(&self) -> CidCheck {
+        CidCheck(self.reader.cids.clone())
+    }
+
+    pub fn into_reader(self) -> UploadReader {
+        self.reader
+    }
+
+    /// A multipart part that streams the file under its file name
+    pub fn into_part(self) -> reqwest::multipart::Part {
+        let len = self.len;
+        let file_name = self.file_name.clone();
+        reqwest::multipart::Part::stream_with_length(self.into_body(), len).file_name(file_name)
+    }
+
+    /// A request body that streams the file
+    pub fn into_body(self) -> reqwest::Body {
+        let stream = tokio_util::io::ReaderStream::with_capacity(self.reader, STREAM_CHUNK_SIZE);
+        reqwest::Body::wrap_stream(stream)
+    }
+}
+
+/// Reads a file for upload, hashing it and reporting progress
+pub struct UploadReader {
+    file: tokio::fs::File,
+    len: u64,
+    sent: u64,
+    cids: Arc<Mutex<CidVerifier>>,
+    progress: Progress,
+}
+
+impl AsyncRead for UploadReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+        ready!(Pin::new(&mut this.file).poll_read(cx, buf))?;
+
+        let read = &buf.filled()[start..];
+        if !read.is_empty() {
+            this.cids.lock().unwrap().update(read);
+            this.sent += read.len() as u64;
+            this.progress.report(this.sent, this.len);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Checks a returned CID against the bytes an `UploadReader` sent
+pub struct CidCheck(Arc<Mutex<CidVerifier>>);
+
+impl CidCheck {
+    /// Pass `cid` through if it matches what was sent
+    pub fn verify(&self, backend: &'static str, cid: String) -> Result<String> {
+        let verifier = std::mem::take(&mut *self.0.lock().unwrap());
+        if verifier.matches(&cid)? {
+            Ok(cid)
+        } else {
+            Err(StorageError::CidMismatch { backend, returned: cid }.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unixfs::{compute_cid, CidProfile};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_upload_reader() {
+        let data: Vec<u8> = (0..300 * 1024).map(|i| (i % 253) as u8).collect();
+        let path = std::env::temp_dir().join(format!("agent-cli-upload-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reports = reports.clone();
+            Progress::new(move |sent, total| reports.lock().unwrap().push((sent, total)))
+        };
+
+        let upload = FileUpload::open(&path, progress).await.unwrap();
+        assert_eq!(upload.file_name, path.file_name().unwrap().to_str().unwrap());
+        assert_eq!(upload.len, data.len() as u64);
+
+        let check = upload.check();
+        let mut sent = Vec::new();
+        upload.into_reader().read_to_end(&mut sent).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sent, data);
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(reports.last(), Some(&(data.len() as u64, data.len() as u64)));
+
+        let cid = compute_cid(&data, CidProfile::KUBO_V0).to_string();
+        assert_eq!(check.verify("ipfs", cid.clone()).unwrap(), cid);
+
+        let other = compute_cid(b"other", CidProfile::KUBO_V0).to_string();
+        let err = check.verify("ipfs", other).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(StorageError::CidMismatch { backend: "ipfs", .. })));
+    }
+}